@@ -34,7 +34,7 @@
 use core::{
   alloc::{GlobalAlloc, Layout},
   ffi::c_void,
-  sync::atomic::AtomicPtr,
+  sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
 };
 
 use r_efi::efi;
@@ -55,12 +55,13 @@ struct AllocationTracker {
 /// see [`BootServicesAllocator::init()`].
 pub struct BootServicesAllocator {
   boot_services: AtomicPtr<efi::BootServices>,
+  outstanding: AtomicUsize,
 }
 
 impl BootServicesAllocator {
   // Create a new instance. const fn to allow static initialization.
   const fn new() -> Self {
-    BootServicesAllocator { boot_services: AtomicPtr::new(core::ptr::null_mut()) }
+    BootServicesAllocator { boot_services: AtomicPtr::new(core::ptr::null_mut()), outstanding: AtomicUsize::new(0) }
   }
 
   // implement allocation using EFI boot services AllocatePool() call.
@@ -134,24 +135,34 @@ impl BootServicesAllocator {
 
   /// initializes the allocator instance with a pointer to the UEFI Boot Services table.
   pub fn init(&self, boot_services: *mut efi::BootServices) {
-    self.boot_services.store(boot_services, core::sync::atomic::Ordering::SeqCst);
+    self.boot_services.store(boot_services, Ordering::SeqCst);
+  }
+
+  /// returns the number of allocations that have not been freed yet, e.g. to report leaks when an image is unloaded.
+  pub fn outstanding_allocations(&self) -> usize {
+    self.outstanding.load(Ordering::SeqCst)
   }
 }
 
 unsafe impl GlobalAlloc for BootServicesAllocator {
   unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-    let bs_ptr = self.boot_services.load(core::sync::atomic::Ordering::SeqCst);
+    let bs_ptr = self.boot_services.load(Ordering::SeqCst);
     if let Some(boot_services) = unsafe { bs_ptr.as_ref() } {
-      self.boot_services_alloc(layout, boot_services)
+      let ptr = self.boot_services_alloc(layout, boot_services);
+      if !ptr.is_null() {
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+      }
+      ptr
     } else {
       panic!("Attempted allocation on uninitialized allocator")
     }
   }
 
   unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-    let bs_ptr = self.boot_services.load(core::sync::atomic::Ordering::SeqCst);
+    let bs_ptr = self.boot_services.load(Ordering::SeqCst);
     if let Some(boot_services) = unsafe { bs_ptr.as_ref() } {
-      self.boot_services_dealloc(boot_services, ptr, layout)
+      self.boot_services_dealloc(boot_services, ptr, layout);
+      self.outstanding.fetch_sub(1, Ordering::SeqCst);
     } else {
       panic!("Attempted deallocation on uninitialized allocator")
     }
@@ -231,9 +242,11 @@ mod tests {
     let ptr = unsafe { ALLOCATOR.alloc_zeroed(layout) };
     assert!(!ptr.is_null());
     assert!(ALLOCATION_TRACKER.lock().contains_key(&(ptr as usize)));
+    assert_eq!(ALLOCATOR.outstanding_allocations(), 1);
 
     unsafe { ALLOCATOR.dealloc(ptr, layout) };
     assert!(!ALLOCATION_TRACKER.lock().contains_key(&(ptr as usize)));
+    assert_eq!(ALLOCATOR.outstanding_allocations(), 0);
   }
 
   #[test]
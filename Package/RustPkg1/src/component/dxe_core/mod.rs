@@ -1,8 +1,7 @@
 use core::marker::PhantomData;
 use log::info;
-use r_efi::efi;
 
-use mu_core::{Component, error::Result};
+use mu_core::{Component, ImageHandle, SystemTable, error::{EntryResult, Result}};
 use crate::interface::{DebugLib, CpuInterruptLib};
 
 pub struct DxeCoreComponent<D, C>
@@ -19,15 +18,13 @@ where
     D: DebugLib,
     C: CpuInterruptLib,
 {
-    fn main(_: efi::Handle, _: *mut efi::SystemTable) -> Result<()>{
+    fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
         info!("Starting DXE Core...");
-        Ok(())
+        Ok(None)
     }
 
-    fn init(ih: efi::Handle, st: *mut efi::SystemTable) -> Result<()>{
-        D::init(ih, st);
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()>{
         info!("Logger initialized.");
-        C::init();
         Ok(())
     }
 }
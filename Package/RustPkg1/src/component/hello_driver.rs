@@ -0,0 +1,40 @@
+use core::marker::PhantomData;
+use log::info;
+use r_efi::protocols::device_path;
+
+use mu_core::{ControllerHandle, DriverBindingComponent, ImageHandle, SystemTable, error::{EfiError, Result}};
+use crate::interface::DebugLib;
+
+/// A UEFI Driver Model driver that logs each Driver Binding call, and declines to manage any controller.
+pub struct HelloDriverComponent<D>
+where
+    D: DebugLib
+{
+    _d: PhantomData<D>,
+}
+
+impl <D> DriverBindingComponent for HelloDriverComponent<D>
+where
+    D: DebugLib,
+{
+    const DRIVER_NAME: Option<&'static str> = Some("Hello Driver");
+
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+        Ok(())
+    }
+
+    fn supported(_: ImageHandle, _: SystemTable, controller: ControllerHandle, _: Option<&device_path::Protocol>) -> Result<()> {
+        info!("Supported called for controller {:?}", controller);
+        Err(EfiError::Unsupported)
+    }
+
+    fn start(_: ImageHandle, _: SystemTable, controller: ControllerHandle, _: Option<&device_path::Protocol>) -> Result<()> {
+        info!("Start called for controller {:?}", controller);
+        Ok(())
+    }
+
+    fn stop(_: ImageHandle, _: SystemTable, controller: ControllerHandle, _: &[ControllerHandle]) -> Result<()> {
+        info!("Stop called for controller {:?}", controller);
+        Ok(())
+    }
+}
@@ -1,8 +1,7 @@
 use core::marker::PhantomData;
 use log::info;
-use r_efi::efi;
 
-use mu_core::{Component, error::Result};
+use mu_core::{Component, ImageHandle, SystemTable, error::{EntryResult, Result}};
 use crate::interface::DebugLib;
 
 pub struct HelloWorldComponent<D>
@@ -17,14 +16,15 @@ impl <D> Component for HelloWorldComponent<D>
 where
     D: DebugLib,
 {
-    fn main(_: efi::Handle, _: *mut efi::SystemTable) -> Result<()>{
+    const UNLOADABLE: bool = true;
+
+    fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
         info!("Hello, World! (With Love, From Joey)");
         info!("Writing some more bytes my dude");
-        Ok(())
+        Ok(None)
     }
 
-    fn init(ih: efi::Handle, st: *mut efi::SystemTable) -> Result<()>{
-        D::init(ih, st);
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()>{
         Ok(())
     }
-}
\ No newline at end of file
+}
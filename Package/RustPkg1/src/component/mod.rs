@@ -1,5 +1,7 @@
 mod hello_world;
+mod hello_driver;
 mod dxe_core;
 
 pub use hello_world::HelloWorldComponent;
+pub use hello_driver::HelloDriverComponent;
 pub use dxe_core::DxeCoreComponent;
@@ -1,10 +1,14 @@
-use r_efi::efi::{Handle, SystemTable};
+use mu_core::{error::Result, ImageHandle, LibraryConstructor, SystemTable};
 
 /// A Trait for a Rust-UEFI debugging library that use's the crate `log`'s macros.
-pub trait DebugLib {
-    fn init(image_handle: Handle, system_table: *mut SystemTable);
+pub trait DebugLib: LibraryConstructor {
+    /// Sets the library up as the `log` backend.
+    ///
+    /// Instances that install a logger fail with `AlreadyStarted` if a logger is already installed. An
+    /// instance that installs nothing, such as `DebugLibNull`, never fails.
+    fn init(image_handle: ImageHandle, system_table: SystemTable) -> Result<()>;
 }
 
-pub trait CpuInterruptLib {
-    fn init();
+pub trait CpuInterruptLib: LibraryConstructor {
+    fn init() -> Result<()>;
 }
@@ -1,4 +1,5 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 pub mod library;
 pub mod component;
-pub mod interface;
\ No newline at end of file
+pub mod interface;
+pub mod pcd;
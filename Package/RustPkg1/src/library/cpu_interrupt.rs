@@ -1,16 +1,31 @@
+use mu_core::{error::Result, ImageHandle, LibraryConstructor, SystemTable};
 use crate::interface::CpuInterruptLib;
 
 pub struct CpuInterruptLibX64;
 
 impl CpuInterruptLib for CpuInterruptLibX64 {
-    fn init() {
+    fn init() -> Result<()> {
         // Do nothing
+        Ok(())
+    }
+}
+
+impl LibraryConstructor for CpuInterruptLibX64 {
+    fn constructor(_: ImageHandle, _: SystemTable) -> Result<()> {
+        Self::init()
     }
 }
 
 pub struct CpuInterruptLibStd;
 impl CpuInterruptLib for CpuInterruptLibStd {
-    fn init() {
+    fn init() -> Result<()> {
         // Do nothing
+        Ok(())
+    }
+}
+
+impl LibraryConstructor for CpuInterruptLibStd {
+    fn constructor(_: ImageHandle, _: SystemTable) -> Result<()> {
+        Self::init()
     }
 }
@@ -3,17 +3,18 @@ extern crate alloc;
 use core::fmt::Write;
 use alloc::format;
 use log;
-use r_efi::efi;
 use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
 use x86_64::instructions::interrupts;
+use mu_core::{error::{EfiError, Result}, ImageHandle, LibraryConstructor, SystemTable};
 use crate::interface::DebugLib;
+use crate::pcd::{PcdDebugLogLevel, PcdSerialPortBase};
 
 // The static serial Port that DebugLibBase will write to.
 lazy_static! {
     static ref SERIAL1: Mutex<SerialPort> = {
-      let mut serial_port = unsafe { SerialPort::new(0x402) };
+      let mut serial_port = unsafe { SerialPort::new(PcdSerialPortBase.get()) };
       serial_port.init();
       Mutex::new(serial_port)
     };
@@ -27,7 +28,7 @@ lazy_static! {
 pub struct DebugLibBase;
 impl log::Log for DebugLibBase {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::Level::Debug
+        metadata.level() <= PcdDebugLogLevel.get()
     }
 
     fn log(&self, record: &log::Record) {
@@ -48,9 +49,22 @@ impl log::Log for DebugLibBase {
 }
 
 impl DebugLib for DebugLibBase {
-    fn init(_: efi::Handle, _: *mut efi::SystemTable) {
-        log::set_logger(&DebugLibBase)
-            .map(|()| log::set_max_level(log::LevelFilter::Debug)).unwrap();
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+        log::set_logger(&DebugLibBase).map_err(|_| EfiError::AlreadyStarted)?;
+        log::set_max_level(PcdDebugLogLevel.get());
+        Ok(())
+    }
+}
+
+impl LibraryConstructor for DebugLibBase {
+    fn constructor(ih: ImageHandle, st: SystemTable) -> Result<()> {
+        Self::init(ih, st)
+    }
+
+    /// Flushes any buffered output before the image is unloaded.
+    fn destructor(_: ImageHandle) -> Result<()> {
+        log::logger().flush();
+        Ok(())
     }
 }
 
@@ -58,11 +72,14 @@ impl DebugLib for DebugLibBase {
 pub struct DebugLibNull;
 
 impl DebugLib for DebugLibNull {
-    fn init(_: efi::Handle, _: *mut efi::SystemTable) {
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
         // Do nothing
+        Ok(())
     }
 }
 
+impl LibraryConstructor for DebugLibNull {}
+
 #[cfg(feature = "std" )]
 pub mod with_std {
     use super::*;
@@ -71,7 +88,7 @@ pub mod with_std {
 
     impl log::Log for DebugLibStd {
         fn enabled(&self, metadata: &log::Metadata) -> bool {
-            metadata.level() <= log::Level::Debug
+            metadata.level() <= PcdDebugLogLevel.get()
         }
     
         fn log(&self, record: &log::Record) {
@@ -88,9 +105,38 @@ pub mod with_std {
     }
 
     impl DebugLib for DebugLibStd {
-        fn init(_: efi::Handle, _: *mut efi::SystemTable) {
-            log::set_logger(&DebugLibStd)
-                .map(|()| log::set_max_level(log::LevelFilter::Debug)).unwrap();
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            log::set_logger(&DebugLibStd).map_err(|_| EfiError::AlreadyStarted)?;
+            log::set_max_level(PcdDebugLogLevel.get());
+            Ok(())
+        }
+    }
+
+    impl LibraryConstructor for DebugLibStd {
+        fn constructor(ih: ImageHandle, st: SystemTable) -> Result<()> {
+            Self::init(ih, st)
+        }
+
+        /// Flushes any buffered output before the image is unloaded.
+        fn destructor(_: ImageHandle) -> Result<()> {
+            log::logger().flush();
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_init_twice() {
+            let (ih, st) = (ImageHandle::host(), SystemTable::host());
+            assert_eq!(DebugLibStd::init(ih, st), Ok(()));
+            assert_eq!(DebugLibStd::init(ih, st), Err(EfiError::AlreadyStarted));
+
+            // The Null instance installs no logger, so it does not notice the one installed above.
+            assert_eq!(DebugLibNull::init(ih, st), Ok(()));
+            assert_eq!(DebugLibNull::init(ih, st), Ok(()));
         }
     }
 }
@@ -0,0 +1,9 @@
+//! PCDs read by the RustPkg1 libraries, set from the platform config file named by `MU_CONFIG`.
+mu_core::pcds! {
+    Env = "MU_CONFIG";
+
+    /// The I/O port of the 16550 UART that the serial debug libraries write to.
+    pub PcdSerialPortBase: u16 = 0x402;
+    /// The most verbose level that the debug libraries log.
+    pub PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+}
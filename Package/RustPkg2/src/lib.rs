@@ -2,4 +2,5 @@
 extern crate alloc;
 pub mod library;
 pub mod component;
-pub mod interface;
\ No newline at end of file
+pub mod interface;
+pub mod pcd;
@@ -1,6 +1,8 @@
 use pkg1::interface::DebugLib;
-use r_efi::efi;
-use alloc::format;
+use pkg1::pcd::{PcdDebugLogLevel, PcdSerialPortBase};
+use crate::pcd::PcdRingBufferSize;
+use mu_core::{error::{EfiError, Result}, ImageHandle, LibraryConstructor, SystemTable};
+use alloc::{boxed::Box, format};
 use log;
 use lazy_static::lazy_static;
 use spin::Mutex;
@@ -10,13 +12,12 @@ use core::fmt::Write;
 
 lazy_static! {
     static ref SERIAL1: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(0x402) };
+        let mut serial_port = unsafe { SerialPort::new(PcdSerialPortBase.get()) };
         serial_port.init();
         Mutex::new(serial_port)
     };
 }
 
-static LOGGER: RingBufferDebugLib = RingBufferDebugLib::new();
 struct RingBuffer<const N: usize> {
     buffer: [u8; N],
     read_index: usize,
@@ -45,12 +46,14 @@ impl<const N: usize> RingBuffer<N> {
 }
 
 
-pub struct RingBufferDebugLib {
-    buffer: Mutex<RingBuffer<1024>>,
+/// A DebugLib that buffers log messages in a ring buffer of `N` bytes, and writes them to the serial port
+/// when the logger is flushed. `N` defaults to `PcdRingBufferSize`.
+pub struct RingBufferDebugLib<const N: usize = { PcdRingBufferSize.get() }> {
+    buffer: Mutex<RingBuffer<N>>,
 }
 
 
-impl RingBufferDebugLib {
+impl<const N: usize> RingBufferDebugLib<N> {
     const fn new() -> Self {
         RingBufferDebugLib {
             buffer: Mutex::new(RingBuffer::new()),
@@ -58,14 +61,34 @@ impl RingBufferDebugLib {
     }
 }
 
-impl DebugLib for RingBufferDebugLib {
-    fn init(_: efi::Handle, _: *mut efi::SystemTable) {
-        log::set_logger(&LOGGER)
-            .map(|()| log::set_max_level(log::LevelFilter::Debug)).unwrap();
+impl<const N: usize> DebugLib for RingBufferDebugLib<N> {
+    fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+        // The logger is allocated, as a static cannot depend on `N`. It is only leaked once installed.
+        let logger = Box::into_raw(Box::new(Self::new()));
+        // SAFETY: `logger` is a valid allocation, which is never freed once `set_logger` has taken it.
+        if log::set_logger(unsafe { &*logger }).is_err() {
+            // SAFETY: `set_logger` did not keep the reference, so the allocation is still ours.
+            drop(unsafe { Box::from_raw(logger) });
+            return Err(EfiError::AlreadyStarted);
+        }
+        log::set_max_level(PcdDebugLogLevel.get());
+        Ok(())
+    }
+}
+
+impl<const N: usize> LibraryConstructor for RingBufferDebugLib<N> {
+    fn constructor(ih: ImageHandle, st: SystemTable) -> Result<()> {
+        Self::init(ih, st)
+    }
+
+    /// Flushes any buffered output before the image is unloaded.
+    fn destructor(_: ImageHandle) -> Result<()> {
+        log::logger().flush();
+        Ok(())
     }
 }
 
-impl log::Log for RingBufferDebugLib {
+impl<const N: usize> log::Log for RingBufferDebugLib<N> {
     fn enabled(&self, _metadata: &log::Metadata) -> bool {
         true
     }
@@ -0,0 +1,8 @@
+//! PCDs read by the RustPkg2 libraries, set from the platform config file named by `MU_CONFIG`.
+mu_core::pcds! {
+    Env = "MU_CONFIG";
+
+    /// The number of bytes `RingBufferDebugLib` buffers before they are written to the serial port. It is
+    /// the default of a const generic, so it can only be FixedAtBuild.
+    pub const PcdRingBufferSize: usize = 1024;
+}
@@ -0,0 +1,21 @@
+#![cfg_attr(target_os = "uefi", no_std)]
+#![cfg_attr(target_os = "uefi", no_main)]
+
+extern crate alloc;
+use pkg1::component::DxeCoreComponent;
+
+use mu_core::{component, entry};
+
+#[cfg(target_os = "uefi")]
+use pkg1::library::CpuInterruptLibX64 as CpuInterruptLibInstance;
+#[cfg(target_os = "uefi")]
+use pkg2::library::RingBufferDebugLib as DebugLibInstance;
+#[cfg(not(target_os = "uefi"))]
+use pkg1::library::{CpuInterruptLibStd as CpuInterruptLibInstance, DebugLibStd as DebugLibInstance};
+
+#[entry(DxeDriver, panic = Deadloop)]
+type Driver = component!(
+    DxeCoreComponent<DebugLib, CpuInterrupt>;
+    DebugLib=DebugLibInstance;
+    CpuInterrupt=CpuInterruptLibInstance;
+);
@@ -0,0 +1,13 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use pkg1::component::HelloDriverComponent;
+
+use mu_core::{component, entry};
+
+#[entry(UefiDriver)]
+type Driver = component!(
+    HelloDriverComponent<DebugLib>;
+    DebugLib=pkg1::library::DebugLibBase
+);
@@ -0,0 +1,18 @@
+#![cfg_attr(target_os = "uefi", no_std)]
+#![cfg_attr(target_os = "uefi", no_main)]
+
+extern crate alloc;
+use pkg1::component::HelloWorldComponent;
+
+use mu_core::{component, entry};
+
+#[cfg(target_os = "uefi")]
+use pkg1::library::DebugLibBase as DebugLibInstance;
+#[cfg(not(target_os = "uefi"))]
+use pkg1::library::DebugLibStd as DebugLibInstance;
+
+#[entry]
+type Driver = component!(
+    HelloWorldComponent<DebugLib>;
+    DebugLib=DebugLibInstance;
+);
@@ -0,0 +1,731 @@
+//! Importing the library classes of an EDK2 platform DSC into a `Config`, so that a platform can move to Rust
+//! library instances a few at a time.
+//!
+//! The `[LibraryClasses]` sections of the DSC, and of the files it `!include`s, are read with their arch and
+//! module type qualifiers, `[LibraryClasses.X64.DXE_DRIVER]`, after `DEFINE`d macros are substituted and
+//! `!if` sections are evaluated. A mapping file names the Rust library of each EDK2 library class, which is
+//! the class name unless `library` is set, and the Rust instance of each INF:
+//!
+//! ```toml
+//! [DebugLib]
+//! "MdePkg/Library/BaseDebugLibNull/BaseDebugLibNull.inf" = "pkg1::library::DebugLibNull"
+//!
+//! [SerialPortLib]
+//! library = "SerialLib"
+//! "PcAtChipsetPkg/Library/SerialPortLib16550/SerialPortLib16550.inf" = "pkg2::library::SerialLib16550|DXE_DRIVER"
+//! ```
+//!
+//! A library class or instance that the mapping does not name is left out of the config, and listed in
+//! `Import::unmapped`.
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use crate::{is_key, library_instance, Architecture, Config, ConfigError, LibraryInstances, LibraryKey, Module, Problems};
+
+/// The Rust library and instances of each EDK2 library class, by lowercase class name.
+#[derive(Debug, Default)]
+pub struct Mapping {
+    classes: HashMap<String, ClassMapping>,
+}
+
+#[derive(Debug)]
+struct ClassMapping {
+    /// The name of the Rust library.
+    library: String,
+    /// The Rust instance of each INF, by its path in lowercase with `/` separators.
+    instances: HashMap<String, String>,
+}
+
+impl Mapping {
+    /// Reads the mapping file at `path`, reporting every problem in it.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Mapping, Vec<ConfigError>> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| vec![ConfigError::file(path, format!("Failed to read: {}", e.kind()))])?;
+        Mapping::from_str(&source).map_err(|errors| errors.into_iter().map(|error| error.with_path(path)).collect())
+    }
+
+    /// Parses the contents of a mapping file, reporting every problem in it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> Result<Mapping, Vec<ConfigError>> {
+        let document = Config::parse(source)?;
+
+        let mut problems = Problems::new();
+        let mut classes = HashMap::new();
+        for (class, item) in document.as_table().into_iter().flatten() {
+            let Some(table) = item.get_ref().as_table() else {
+                problems.push((item.span(), format!("Mapping of library class {} must be a table", class.get_ref())));
+                continue;
+            };
+
+            let mut mapping = ClassMapping { library: class.get_ref().clone(), instances: HashMap::new() };
+            for (key, value) in table {
+                let Some(text) = value.get_ref().as_str() else {
+                    problems.push((value.span(), format!(
+                        "{} of library class {} must be a string", key.get_ref(), class.get_ref()
+                    )));
+                    continue;
+                };
+                if is_key(key, "library") {
+                    mapping.library = text.to_string();
+                } else {
+                    mapping.instances.insert(inf_key(key.get_ref()), text.to_string());
+                }
+            }
+            classes.insert(class.get_ref().to_lowercase(), mapping);
+        }
+
+        let errors = Config::errors(source, problems);
+        if errors.is_empty() {
+            Ok(Mapping { classes })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The library instances imported from a DSC.
+#[derive(Debug)]
+pub struct Import {
+    /// The library instances that the mapping names, and the DSC with every file it includes.
+    pub config: Config,
+    /// Each library class or instance of the DSC that the mapping does not name, with where it is.
+    pub unmapped: Vec<ConfigError>,
+}
+
+/// Reads the DSC at `path`, and the files it includes, with `defines` set as if by `DEFINE`, such as the
+/// `TARGET` of the build. An included file is found relative to the including file, or else relative to a
+/// directory above it, such as the workspace.
+pub fn import(path: impl AsRef<Path>, mapping: &Mapping, defines: &[(&str, &str)]) -> Result<Import, Vec<ConfigError>> {
+    let mut reader = Reader::new(mapping, defines);
+    reader.load(path.as_ref());
+    reader.finish()
+}
+
+/// Reads the contents of a DSC, with `defines` set as if by `DEFINE`. The DSC cannot include other files,
+/// as there is no file to find them from.
+pub fn import_str(source: &str, mapping: &Mapping, defines: &[(&str, &str)]) -> Result<Import, Vec<ConfigError>> {
+    let mut reader = Reader::new(mapping, defines);
+    reader.read(None, source);
+    reader.finish()
+}
+
+/// The state of reading a DSC, which carries on into the files it includes.
+struct Reader<'a> {
+    mapping: &'a Mapping,
+    defines: HashMap<String, String>,
+    /// The arch and module type of each `[LibraryClasses]` section of the current header, or none in other
+    /// sections.
+    sections: Vec<(Architecture, Module)>,
+    /// Whether the current section is `[Defines]`, whose statements are macros too.
+    in_defines: bool,
+    libraries: LibraryInstances,
+    files: Vec<PathBuf>,
+    /// The files including the one being read.
+    stack: Vec<PathBuf>,
+    unmapped: Vec<ConfigError>,
+    errors: Vec<ConfigError>,
+}
+
+/// An `!if`, `!ifdef` or `!ifndef` that the current line is in.
+struct Conditional {
+    /// Whether the lines of the current branch are read.
+    active: bool,
+    /// Whether a branch has been read.
+    taken: bool,
+    /// Whether the current branch is the `!else`.
+    in_else: bool,
+    span: Range<usize>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(mapping: &'a Mapping, defines: &[(&str, &str)]) -> Self {
+        Reader {
+            mapping,
+            defines: defines.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            sections: vec![],
+            in_defines: false,
+            libraries: LibraryInstances::default(),
+            files: vec![],
+            stack: vec![],
+            unmapped: vec![],
+            errors: vec![],
+        }
+    }
+
+    fn finish(self) -> Result<Import, Vec<ConfigError>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        let config = Config { libraries: self.libraries, files: self.files, ..Config::default() };
+        Ok(Import { config, unmapped: self.unmapped })
+    }
+
+    fn load(&mut self, path: &Path) {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return self.errors.push(ConfigError::file(path, format!("Failed to read: {}", e.kind()))),
+        };
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.files.push(canonical.clone());
+        self.stack.push(canonical);
+        self.read(Some(path), &source);
+        self.stack.pop();
+    }
+
+    /// Reads the lines of the DSC `source`, from the file at `path`.
+    fn read(&mut self, path: Option<&Path>, source: &str) {
+        let mut problems = Problems::new();
+        let mut unmapped = Problems::new();
+        let mut conditionals: Vec<Conditional> = vec![];
+
+        let mut offset = 0;
+        for line in source.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+
+            let text = strip_comment(line);
+            let statement = text.trim();
+            if statement.is_empty() {
+                continue;
+            }
+            let start = start + text.len() - text.trim_start().len();
+            let span = start..start + statement.len();
+
+            if let Some(directive) = statement.strip_prefix('!') {
+                self.directive(directive, span, path, &mut conditionals, &mut problems);
+                continue;
+            }
+            if !conditionals.iter().all(|conditional| conditional.active) {
+                continue;
+            }
+            match self.substitute(statement) {
+                Ok(statement) => self.statement(&statement, span, path, &mut problems, &mut unmapped),
+                Err(name) => problems.push((span, format!("Macro {} is not defined", name))),
+            }
+        }
+        for conditional in conditionals {
+            problems.push((conditional.span, "!if without !endif".to_string()));
+        }
+
+        let in_file = |errors: Vec<ConfigError>| -> Vec<ConfigError> {
+            match path {
+                Some(path) => errors.into_iter().map(|error| error.with_path(path)).collect(),
+                None => errors,
+            }
+        };
+        self.errors.extend(in_file(Config::errors(source, problems)));
+        self.unmapped.extend(in_file(Config::errors(source, unmapped)));
+    }
+
+    /// Handles a `!` directive. The conditionals are followed in lines that are not read, to find their
+    /// `!endif`, and the other directives are only run in lines that are read.
+    fn directive(
+        &mut self,
+        directive: &str,
+        span: Range<usize>,
+        path: Option<&Path>,
+        conditionals: &mut Vec<Conditional>,
+        problems: &mut Problems,
+    ) {
+        let (keyword, rest) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+        let rest = rest.trim();
+        let active = conditionals.iter().all(|conditional| conditional.active);
+        let enclosing = conditionals.len().saturating_sub(1);
+        let enclosing_active = conditionals[..enclosing].iter().all(|conditional| conditional.active);
+
+        match keyword.to_lowercase().as_str() {
+            keyword @ ("if" | "ifdef" | "ifndef") => {
+                let taken = active && match keyword {
+                    "if" => self.condition(rest, &span, problems),
+                    "ifdef" => self.is_defined(rest),
+                    _ => !self.is_defined(rest),
+                };
+                conditionals.push(Conditional { active: taken, taken, in_else: false, span });
+            }
+            "elseif" => match conditionals.last() {
+                None => problems.push((span, "!elseif without !if".to_string())),
+                Some(conditional) if conditional.in_else => problems.push((span, "!elseif after !else".to_string())),
+                Some(conditional) => {
+                    let active = enclosing_active && !conditional.taken && self.condition(rest, &span, problems);
+                    let conditional = conditionals.last_mut().unwrap();
+                    conditional.active = active;
+                    conditional.taken |= active;
+                }
+            },
+            "else" => match conditionals.last_mut() {
+                None => problems.push((span, "!else without !if".to_string())),
+                Some(conditional) if conditional.in_else => problems.push((span, "!else after !else".to_string())),
+                Some(conditional) => {
+                    conditional.active = enclosing_active && !conditional.taken;
+                    conditional.taken = true;
+                    conditional.in_else = true;
+                }
+            },
+            "endif" => {
+                if conditionals.pop().is_none() {
+                    problems.push((span, "!endif without !if".to_string()));
+                }
+            }
+            _ if !active => {}
+            "include" => self.include(rest, span, path, problems),
+            "error" => problems.push((span, self.substitute(rest).unwrap_or_else(|_| rest.to_string()))),
+            "message" => {}
+            _ => problems.push((span, format!("Unknown directive !{}", keyword))),
+        }
+    }
+
+    /// Evaluates the `!if` or `!elseif` `expression`, which is false when it is invalid.
+    fn condition(&self, expression: &str, span: &Range<usize>, problems: &mut Problems) -> bool {
+        let result = self.substitute(expression)
+            .map_err(|name| format!("Macro {} is not defined", name))
+            .and_then(|expression| evaluate(&expression));
+        result.unwrap_or_else(|e| {
+            problems.push((span.clone(), format!("Invalid expression {}: {}", expression, e)));
+            false
+        })
+    }
+
+    /// Whether the macro `name`, written as `NAME` or `$(NAME)`, is defined.
+    fn is_defined(&self, name: &str) -> bool {
+        let name = name.strip_prefix("$(").and_then(|name| name.strip_suffix(')')).unwrap_or(name);
+        self.defines.contains_key(name)
+    }
+
+    fn include(&mut self, file: &str, span: Range<usize>, path: Option<&Path>, problems: &mut Problems) {
+        let file = match self.substitute(file) {
+            Ok(file) => file.trim_matches('"').to_string(),
+            Err(name) => return problems.push((span, format!("Macro {} is not defined", name))),
+        };
+        let Some(path) = path else {
+            return problems.push((span, "!include is only supported in a DSC read with dsc::import".to_string()));
+        };
+
+        let include_path = path.parent().into_iter().flat_map(Path::ancestors).map(|dir| dir.join(&file)).find(|path| path.is_file());
+        let Some(include_path) = include_path else {
+            return problems.push((span, format!("Included file {} not found", file)));
+        };
+        let canonical = std::fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+        if let Some(start) = self.stack.iter().position(|file| *file == canonical) {
+            let cycle: Vec<String> = self.stack[start..]
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|file| file.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                .collect();
+            return problems.push((span, format!("Circular include: {}", cycle.join(" -> "))));
+        }
+        self.load(&include_path);
+    }
+
+    /// Handles a section header, a macro definition or, in a `[LibraryClasses]` section, a library class
+    /// instance, `DebugLib|MdePkg/Library/BaseDebugLibNull/BaseDebugLibNull.inf`.
+    fn statement(
+        &mut self,
+        statement: &str,
+        span: Range<usize>,
+        path: Option<&Path>,
+        problems: &mut Problems,
+        unmapped: &mut Problems,
+    ) {
+        if statement.starts_with('[') {
+            return self.section(statement, span, problems);
+        }
+
+        let (keyword, rest) = statement.split_once(char::is_whitespace).unwrap_or((statement, ""));
+        if keyword.eq_ignore_ascii_case("DEFINE") || keyword.eq_ignore_ascii_case("EDK_GLOBAL") {
+            match rest.split_once('=') {
+                Some((name, value)) => self.define(name, value),
+                None => problems.push((span, format!("Expected {} NAME = value", keyword))),
+            }
+            return;
+        }
+        if self.in_defines {
+            if let Some((name, value)) = statement.split_once('=') {
+                self.define(name, value);
+            }
+            return;
+        }
+        if self.sections.is_empty() {
+            return;
+        }
+
+        let Some((class, inf)) = statement.split_once('|') else {
+            return problems.push((span, "Expected LibraryClass|Path/To/Instance.inf".to_string()));
+        };
+        let (class, inf) = (class.trim(), inf.trim());
+        let Some(mapping) = self.mapping.classes.get(&class.to_lowercase()) else {
+            return unmapped.push((span, format!("Library class {} is not mapped", class)));
+        };
+        let Some(instance) = mapping.instances.get(&inf_key(inf)) else {
+            return unmapped.push((span, format!("Instance {} of library class {} is not mapped", inf, class)));
+        };
+
+        // A later instance of the same class, arch and module type replaces the earlier one, as in EDK2.
+        for (arch, module) in &self.sections {
+            let key = LibraryKey { name: mapping.library.to_lowercase(), arch: arch.clone(), module: module.clone() };
+            let mut instance = library_instance(&mapping.library, instance);
+            instance.source = path.map(Path::to_path_buf);
+            self.libraries.instances.insert(key, instance);
+        }
+    }
+
+    /// Starts the sections of the header `[LibraryClasses.X64, LibraryClasses.IA32.PEIM]`.
+    fn section(&mut self, header: &str, span: Range<usize>, problems: &mut Problems) {
+        self.sections.clear();
+        self.in_defines = false;
+        let Some(header) = header.strip_prefix('[').and_then(|header| header.strip_suffix(']')) else {
+            return problems.push((span, "Expected ] at the end of the section header".to_string()));
+        };
+
+        for section in header.split(',') {
+            let mut qualifiers = section.split('.').map(str::trim);
+            let name = qualifiers.next().unwrap_or_default();
+            if name.eq_ignore_ascii_case("Defines") {
+                self.in_defines = true;
+            }
+            if !name.eq_ignore_ascii_case("LibraryClasses") {
+                continue;
+            }
+            let arch = qualifiers
+                .next()
+                .and_then(|arch| Architecture::try_from(&Value::String(arch.to_string())).ok())
+                .unwrap_or(Architecture::Common);
+            let module = qualifiers.next().map_or(Module::Common, Module::from);
+            self.sections.push((arch, module));
+        }
+    }
+
+    fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.trim().to_string(), value.trim().to_string());
+    }
+
+    /// Replaces each `$(NAME)` in `text` with the value of the macro, failing with the name of a macro that is
+    /// not defined.
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some((before, after)) = rest.split_once("$(") {
+            let Some((name, after)) = after.split_once(')') else {
+                break;
+            };
+            result.push_str(before);
+            result.push_str(self.defines.get(name).ok_or_else(|| name.to_string())?);
+            rest = after;
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
+
+/// The line without its `#` comment.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// The key of the INF at `path`, which is matched without case and with either separator, as EDK2 does.
+fn inf_key(path: &str) -> String {
+    path.trim().replace('\\', "/").to_lowercase()
+}
+
+/// A value in an `!if` expression.
+#[derive(Debug, PartialEq)]
+enum Operand {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Symbol(&'static str),
+    /// A word, such as `TRUE`, `0x10` or a macro value, `DEBUG`.
+    Word(String),
+    Quoted(String),
+}
+
+/// Evaluates an `!if` expression, after its macros are substituted: `TRUE`, `FALSE`, numbers and strings,
+/// compared with `==`, `!=`, `<`, `>`, `<=` and `>=` (or `EQ`, `NE`, `LT`, `GT`, `LE` and `GE`) and combined
+/// with `AND`, `OR` and `NOT` (or `&&`, `||` and `!`) and parentheses.
+fn evaluate(expression: &str) -> Result<bool, String> {
+    let mut parser = Parser { tokens: tokens(expression)?, next: 0 };
+    let value = parser.or()?;
+    match parser.tokens.get(parser.next) {
+        Some(token) => Err(format!("Unexpected {}", describe(token))),
+        None => truth(&value),
+    }
+}
+
+fn tokens(expression: &str) -> Result<Vec<Token>, String> {
+    const SYMBOLS: [&str; 11] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")"];
+    const KEYWORDS: [(&str, &str); 9] = [
+        ("and", "&&"), ("or", "||"), ("not", "!"), ("eq", "=="), ("ne", "!="), ("lt", "<"), ("gt", ">"), ("le", "<="), ("ge", ">="),
+    ];
+
+    let mut tokens = vec![];
+    let mut rest = expression.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let (string, after) = quoted.split_once('"').ok_or("Unterminated string")?;
+            tokens.push(Token::Quoted(string.to_string()));
+            rest = after;
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || "=!<>&|()\"".contains(c)).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected {}", rest));
+            }
+            let word = &rest[..end];
+            match KEYWORDS.iter().find(|(keyword, _)| word.eq_ignore_ascii_case(keyword)) {
+                Some((_, symbol)) => tokens.push(Token::Symbol(symbol)),
+                None => tokens.push(Token::Word(word.to_string())),
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    /// Moves past the next token when it is `symbol`.
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.tokens.get(self.next), Some(Token::Symbol(s)) if *s == symbol);
+        self.next += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> Result<Operand, String> {
+        let mut value = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            value = Operand::Bool(truth(&value)? | truth(&right)?);
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<Operand, String> {
+        let mut value = self.not()?;
+        while self.eat("&&") {
+            let right = self.not()?;
+            value = Operand::Bool(truth(&value)? & truth(&right)?);
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<Operand, String> {
+        if self.eat("!") {
+            return Ok(Operand::Bool(!truth(&self.not()?)?));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Operand, String> {
+        let left = self.operand()?;
+        for symbol in ["==", "!=", "<=", ">=", "<", ">"] {
+            if !self.eat(symbol) {
+                continue;
+            }
+            let right = self.operand()?;
+            let result = match (symbol, &left, &right) {
+                ("==", ..) => left == right,
+                ("!=", ..) => left != right,
+                (_, Operand::Integer(left), Operand::Integer(right)) => match symbol {
+                    "<=" => left <= right,
+                    ">=" => left >= right,
+                    "<" => left < right,
+                    _ => left > right,
+                },
+                _ => return Err(format!("{} only compares numbers", symbol)),
+            };
+            return Ok(Operand::Bool(result));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        let token = self.tokens.get(self.next).cloned().ok_or("Unexpected end of expression")?;
+        self.next += 1;
+        match token {
+            Token::Symbol("(") => {
+                let value = self.or()?;
+                if !self.eat(")") {
+                    return Err("Expected )".to_string());
+                }
+                Ok(value)
+            }
+            Token::Symbol(_) => Err(format!("Unexpected {}", describe(&token))),
+            Token::Quoted(string) => Ok(Operand::String(string)),
+            Token::Word(word) => Ok(match word.to_lowercase().as_str() {
+                "true" => Operand::Bool(true),
+                "false" => Operand::Bool(false),
+                lower => match lower.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16).map(Operand::Integer).unwrap_or(Operand::String(word)),
+                    None => word.parse().map(Operand::Integer).unwrap_or(Operand::String(word)),
+                },
+            }),
+        }
+    }
+}
+
+fn truth(value: &Operand) -> Result<bool, String> {
+    match value {
+        Operand::Bool(value) => Ok(*value),
+        Operand::Integer(value) => Ok(*value != 0),
+        Operand::String(value) => Err(format!("{} is not TRUE or FALSE", value)),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Symbol(symbol) => symbol.to_string(),
+        Token::Word(word) => word.clone(),
+        Token::Quoted(string) => format!("\"{}\"", string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> Mapping {
+        Mapping::from_path("tests/data/dsc/mapping.toml").unwrap()
+    }
+
+    fn messages(errors: &[ConfigError]) -> Vec<String> {
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_import() {
+        let import = import("tests/data/dsc/Platform.dsc", &mapping(), &[("TARGET", "DEBUG")]).unwrap();
+        let libraries = &import.config.libraries;
+        let path = |name: &str, arch: Architecture, module: Module| libraries.get(name, &arch, &module).unwrap().path;
+
+        assert_eq!(path("DebugLib", Architecture::Common, Module::Common), "pkg1::library::DebugLibNull");
+        assert_eq!(path("DebugLib", Architecture::X64, Module::DxeDriver), "pkg1::library::DebugLibSerial");
+        assert_eq!(path("DebugLib", Architecture::Aarch64, Module::Common), "pkg1::library::DebugLibSerial");
+        assert_eq!(path("DebugLib", Architecture::Ia32, Module::Peim), "pkg1::library::DebugLibNull");
+        assert_eq!(path("PrintLib", Architecture::X64, Module::DxeDriver), "pkg1::library::PrintLibBase");
+        assert!(libraries.get("PrintLib", &Architecture::Common, &Module::Common).is_none());
+
+        let serial = libraries.get("SerialLib", &Architecture::X64, &Module::DxeDriver).unwrap();
+        assert_eq!(serial.path, "pkg2::library::SerialLib16550");
+        assert_eq!(serial.modules, vec![Module::DxeDriver, Module::UefiDriver]);
+        assert_eq!(serial.source.unwrap().file_name().unwrap(), "Platform.dsc");
+        let debug = libraries.get("DebugLib", &Architecture::Common, &Module::Peim).unwrap();
+        assert_eq!(debug.source.unwrap().file_name().unwrap(), "Common.dsc.inc");
+
+        let files: Vec<String> = import.config.files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["Platform.dsc", "Common.dsc.inc"]);
+
+        assert_eq!(messages(&import.unmapped), vec![
+            "tests/data/dsc/Platform.dsc:13:3: Library class BaseLib is not mapped",
+        ]);
+
+        // Without TARGET, the !ifdef section is left out.
+        let import = super::import("tests/data/dsc/Platform.dsc", &mapping(), &[]).unwrap();
+        assert!(import.config.libraries.get("PrintLib", &Architecture::X64, &Module::DxeDriver).is_none());
+
+        // The imported config can be written out as a config file.
+        let written = toml::to_string(&import.config).unwrap();
+        assert!(written.contains("[[libraryinstances]]\narch = [\"X64\"]\nmodule = [\"DXE_DRIVER\"]\n"));
+        Config::from_str(&written).unwrap();
+    }
+
+    #[test]
+    fn test_unmapped_instance() {
+        let import = import_str(
+            "[LibraryClasses.IA32]\n  DebugLib|MdePkg/Library/PeiDebugLib/PeiDebugLib.inf\n", &mapping(), &[],
+        ).unwrap();
+        assert!(import.config.libraries.instances.is_empty());
+        assert_eq!(messages(&import.unmapped), vec![
+            "2:3: Instance MdePkg/Library/PeiDebugLib/PeiDebugLib.inf of library class DebugLib is not mapped",
+        ]);
+    }
+
+    #[test]
+    fn test_directives() {
+        let source = concat!(
+            "DEFINE FEATURE = 2\n",
+            "!if $(FEATURE) == 1\n",
+            "  DEFINE LIB = One\n",
+            "!elseif $(FEATURE) == 2\n",
+            "  DEFINE LIB = Two\n",
+            "!else\n",
+            "  DEFINE LIB = Other\n",
+            "!endif\n",
+            "!ifndef LIB\n",
+            "!error LIB must be set\n",
+            "!endif\n",
+            "[LibraryClasses]\n",
+            "!if $(LIB) == \"Two\" && NOT (FALSE OR 0)\n",
+            "  DebugLib|MdePkg/Library/BaseDebugLibSerialPort/BaseDebugLibSerialPort.inf\n",
+            "!endif\n",
+        );
+        let import = import_str(source, &mapping(), &[]).unwrap();
+        let instance = import.config.libraries.get("DebugLib", &Architecture::Common, &Module::Common).unwrap();
+        assert_eq!(instance.path, "pkg1::library::DebugLibSerial");
+
+        let errors = import_str(concat!(
+            "!if $(MISSING)\n",
+            "!endif\n",
+            "!if TARGET < 4\n",
+            "!endif\n",
+            "!error Unsupported platform\n",
+            "!include Other.dsc\n",
+            "[LibraryClasses]\n",
+            "  DebugLib\n",
+            "!endif\n",
+            "!if TRUE\n",
+        ), &mapping(), &[]).unwrap_err();
+        assert_eq!(messages(&errors), vec![
+            "1:1: Invalid expression $(MISSING): Macro MISSING is not defined",
+            "3:1: Invalid expression TARGET < 4: < only compares numbers",
+            "5:1: Unsupported platform",
+            "6:1: !include is only supported in a DSC read with dsc::import",
+            "8:3: Expected LibraryClass|Path/To/Instance.inf",
+            "9:1: !endif without !if",
+            "10:1: !if without !endif",
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("TRUE"), Ok(true));
+        assert_eq!(evaluate("DEBUG == \"DEBUG\" AND 0x10 GT 8"), Ok(true));
+        assert_eq!(evaluate("!(1 == 1) || X64 != X64"), Ok(false));
+        assert_eq!(evaluate("DEBUG"), Err("DEBUG is not TRUE or FALSE".to_string()));
+        assert_eq!(evaluate("(TRUE"), Err("Expected )".to_string()));
+        assert_eq!(evaluate("TRUE FALSE"), Err("Unexpected FALSE".to_string()));
+    }
+
+    #[test]
+    fn test_mapping_errors() {
+        let errors = Mapping::from_str("DebugLib = \"pkg1::library::DebugLibNull\"\n[PrintLib]\nlibrary = 1\n").unwrap_err();
+        assert_eq!(messages(&errors), vec![
+            "1:12: Mapping of library class DebugLib must be a table",
+            "3:11: library of library class PrintLib must be a string",
+        ]);
+    }
+}
@@ -0,0 +1,91 @@
+//! Problems found while reading a config file.
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// A problem in a config file, with where it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The config file, when the config was read from one.
+    pub path: Option<PathBuf>,
+    /// Where the problem is in the file, when it is in the file's contents.
+    pub location: Option<Location>,
+    /// Where the definition that the problem conflicts with is, in the same file.
+    pub previous: Option<Location>,
+    pub message: String,
+}
+
+/// A range of a config file's contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The byte range in the file.
+    pub span: Range<usize>,
+    /// The line the range starts on, from 1.
+    pub line: usize,
+    /// The column the range starts at, in characters from 1.
+    pub column: usize,
+}
+
+impl Location {
+    /// The location of `span` in `source`.
+    pub fn new(source: &str, span: Range<usize>) -> Self {
+        let before = &source[..span.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Location { span, line, column }
+    }
+}
+
+impl ConfigError {
+    /// A problem at `span` of `source`.
+    pub fn new(source: &str, span: Range<usize>, message: impl Into<String>) -> Self {
+        ConfigError { path: None, location: Some(Location::new(source, span)), previous: None, message: message.into() }
+    }
+
+    /// A problem with the config file as a whole, such as failing to read it.
+    pub fn file(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        ConfigError { path: Some(path.into()), location: None, previous: None, message: message.into() }
+    }
+
+    /// The same problem, in the config file at `path`.
+    pub fn with_path(self, path: impl Into<PathBuf>) -> Self {
+        ConfigError { path: Some(path.into()), ..self }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, &self.location) {
+            (Some(path), Some(location)) => write!(f, "{}:{}:{}: ", path.display(), location.line, location.column)?,
+            (None, Some(location)) => write!(f, "{}:{}: ", location.line, location.column)?,
+            (Some(path), None) => write!(f, "{}: ", path.display())?,
+            (None, None) => {}
+        }
+        f.write_str(&self.message)?;
+        if let Some(previous) = &self.previous {
+            write!(f, " (first defined at {}:{})", previous.line, previous.column)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let source = "[[libraries]]\nDebugLib = 1\n";
+        let location = Location::new(source, 25..26);
+        assert_eq!((location.line, location.column), (2, 12));
+
+        let error = ConfigError::new(source, 25..26, "Instance of library DebugLib must be a string");
+        assert_eq!(error.to_string(), "2:12: Instance of library DebugLib must be a string");
+        assert_eq!(
+            error.with_path("Platform.toml").to_string(),
+            "Platform.toml:2:12: Instance of library DebugLib must be a string"
+        );
+    }
+}
@@ -1,25 +1,296 @@
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
-use toml::{Table, Value};
+use toml::{Spanned, Value};
 
+pub mod dsc;
+pub mod error;
+mod serialize;
+mod spanned;
 pub mod types;
-pub use types::{Architecture, Module};
-
+pub use error::{ConfigError, Location};
+pub use types::{Architecture, Module, PcdKind};
 
+use spanned::{Item, Table};
 
 /// A Serializavle/Deserializable toml file for platform
 /// build configurations.
-#[derive(Debug, Serialize, Deserialize, Default)]
+///
+/// A config serializes in the shape it is read in, so that it can be read back the same, from TOML or JSON.
+#[derive(Debug, Default)]
 pub struct Config {
     /// A lookup dictionary of library instances
-    #[serde(alias = "libraryinstances", alias="LibraryInstances")]
     pub libraries: LibraryInstances,
-    #[serde(alias = "components", alias="Components")]
     pub components: ComponentInstances,
+    /// A lookup dictionary of platform configuration values (PCDs)
+    pub pcds: Pcds,
+    /// The config files read, the file passed to `from_path` and every file it includes.
+    pub files: Vec<PathBuf>,
+}
+
+/// The problems found in a config file, each with the range of the file it is in, and, for a conflict, the
+/// range of the definition it conflicts with.
+#[derive(Default)]
+struct Problems(Vec<(Range<usize>, String, Option<Range<usize>>)>);
+
+impl Problems {
+    fn new() -> Self {
+        Problems::default()
+    }
+
+    fn push(&mut self, (span, message): (Range<usize>, String)) {
+        self.0.push((span, message, None));
+    }
+
+    fn conflict(&mut self, span: Range<usize>, message: String, previous: Range<usize>) {
+        self.0.push((span, message, Some(previous)));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Where each library instance, component build target or PCD of a config file is defined, by its name,
+/// arch and module, with the start of the table it is defined in.
+struct Definitions<K>(HashMap<K, (Range<usize>, usize)>);
+
+impl<K: Eq + std::hash::Hash> Definitions<K> {
+    fn new() -> Self {
+        Definitions(HashMap::new())
+    }
+
+    /// Records `key`, named at `span` in `table`. Defining a key again is a conflict, unless it is in a
+    /// later table that sets `override = true`.
+    fn define(&mut self, key: K, span: Range<usize>, table: &TableScope, describe: impl FnOnce() -> String, problems: &mut Problems) {
+        if let Some((previous, previous_table)) = self.0.get(&key) {
+            if *previous_table == table.start || !table.replace {
+                let hint = if *previous_table == table.start { "" } else { ", set override = true in the later table to replace it" };
+                problems.conflict(span, format!("{} is defined twice{}", describe(), hint), previous.clone());
+                return;
+            }
+        }
+        self.0.insert(key, (span, table.start));
+    }
+}
+
+/// A table of a config file, and whether it replaces earlier definitions with `override = true`.
+struct TableScope {
+    start: usize,
+    replace: bool,
+}
+
+/// Reads `override = true`.
+fn replace_flag(value: &Spanned<Item>, problems: &mut Problems) -> bool {
+    match value.get_ref() {
+        Item::Value(Value::Boolean(replace)) => *replace,
+        _ => {
+            problems.push((value.span(), "override must be true or false".to_string()));
+            false
+        }
+    }
+}
+
+/// The files a config file includes, each with the range of the file it is named in.
+type Includes = Vec<(Range<usize>, String)>;
+
+impl Config {
+    /// Reads the config file at `path`, and the files it includes, reporting every problem in them.
+    ///
+    /// `include = ["base.toml", "board.toml"]` names files relative to the including file. Each file is a
+    /// layer over the ones before it: the included files in order, and then the including file itself, so
+    /// a later layer replaces the library instances, components and PCDs of the earlier ones.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Config, Vec<ConfigError>> {
+        Config::load(path.as_ref(), &mut vec![])
+    }
+
+    /// Reads the config file at `path`, with the files on `stack` including it.
+    fn load(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Config, Vec<ConfigError>> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| vec![ConfigError::file(path, format!("Failed to read: {}", e.kind()))])?;
+        let in_file = |errors: Vec<ConfigError>| -> Vec<ConfigError> {
+            errors.into_iter().map(|error| error.with_path(path)).collect()
+        };
+        let document = Config::parse(&source).map_err(in_file)?;
+
+        let mut problems = Problems::new();
+        let mut includes = Includes::new();
+        let mut config = Config::from_document(&document, &mut problems, &mut includes);
+        config.set_source(path);
+
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        stack.push(canonical.clone());
+
+        let mut layered = Config::default();
+        let mut errors = vec![];
+        for (span, include) in includes {
+            let include_path = path.parent().unwrap_or(Path::new("")).join(&include);
+            let include_canonical = std::fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+            if let Some(start) = stack.iter().position(|file| *file == include_canonical) {
+                let cycle: Vec<String> = stack[start..]
+                    .iter()
+                    .chain(std::iter::once(&include_canonical))
+                    .map(|file| file.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                    .collect();
+                problems.push((span, format!("Circular include: {}", cycle.join(" -> "))));
+                continue;
+            }
+
+            if !include_path.is_file() {
+                problems.push((span, format!("Included file {} not found", include)));
+                continue;
+            }
+
+            match Config::load(&include_path, stack) {
+                Ok(included) => layered.merge(included),
+                Err(include_errors) => errors.extend(include_errors),
+            }
+        }
+        stack.pop();
+
+        errors.extend(in_file(Config::errors(&source, problems)));
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        layered.merge(config);
+        layered.files.insert(0, canonical);
+        Ok(layered)
+    }
+
+    /// Parses the contents of a config file, reporting every problem in it. The config cannot include
+    /// other files, as there is no file to find them from.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &str) -> Result<Config, Vec<ConfigError>> {
+        let document = Config::parse(source)?;
+
+        let mut problems = Problems::new();
+        let mut includes = Includes::new();
+        let config = Config::from_document(&document, &mut problems, &mut includes);
+        for (span, _) in includes {
+            problems.push((span, "include is only supported in a config file read with Config::from_path".to_string()));
+        }
+
+        let errors = Config::errors(source, problems);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn parse(source: &str) -> Result<Item, Vec<ConfigError>> {
+        toml::from_str::<Item>(source).map_err(|e| {
+            vec![ConfigError::new(source, e.span().unwrap_or(0..0), e.message())]
+        })
+    }
+
+    fn errors(source: &str, mut problems: Problems) -> Vec<ConfigError> {
+        problems.0.sort_by_key(|(span, _, _)| span.start);
+        problems.0
+            .into_iter()
+            .map(|(span, message, previous)| ConfigError {
+                previous: previous.map(|previous| Location::new(source, previous)),
+                ..ConfigError::new(source, span, message)
+            })
+            .collect()
+    }
+
+    /// Layers `other` over this config: each library instance, component and PCD of `other` replaces the
+    /// one of this config with the same name, arch and module.
+    pub fn merge(&mut self, other: Config) {
+        self.libraries.merge(other.libraries);
+        self.components.merge(other.components);
+        self.pcds.merge(other.pcds);
+        self.files.extend(other.files);
+    }
+
+    /// Records `path` as the file each library instance of this config comes from.
+    fn set_source(&mut self, path: &Path) {
+        let components = self.components.instances.values_mut().flatten().flat_map(|component| component.libraries.values_mut());
+        for instance in self.libraries.instances.values_mut().chain(components) {
+            instance.source = Some(path.to_path_buf());
+        }
+    }
+
+    fn from_document(document: &Item, problems: &mut Problems, includes: &mut Includes) -> Config {
+        let mut config = Config::default();
+        let Some(document) = document.as_table() else {
+            return config;
+        };
+
+        let mut libraries = Definitions::new();
+        let mut components = Definitions::new();
+        let mut pcds = Definitions::new();
+
+        for (key, item) in document {
+            let section = key.get_ref().to_lowercase();
+            if section == "include" {
+                match item.get_ref().as_array() {
+                    Some(files) => for file in files {
+                        match file.get_ref().as_str() {
+                            Some(name) => includes.push((file.span(), name.to_string())),
+                            None => problems.push((file.span(), "include must list file names".to_string())),
+                        }
+                    },
+                    None => problems.push((item.span(), format!(
+                        "include must be an array, such as include = [{}]", item.get_ref().to_value()
+                    ))),
+                }
+                continue;
+            }
+            if !matches!(section.as_str(), "libraries" | "libraryinstances" | "components" | "pcds") {
+                problems.push((key.span(), format!("Unknown key {}, expected include, libraries, components or pcds", key.get_ref())));
+                continue;
+            }
+
+            let Some(tables) = item.get_ref().as_array() else {
+                problems.push((item.span(), format!("{} must be an array of tables, written [[{}]]", key.get_ref(), key.get_ref())));
+                continue;
+            };
+            for table in tables {
+                let Some(entries) = table.get_ref().as_table() else {
+                    problems.push((table.span(), format!("{} must be an array of tables, written [[{}]]", key.get_ref(), key.get_ref())));
+                    continue;
+                };
+                match section.as_str() {
+                    "components" => config.components.append(
+                        process_component_table(table.span(), entries, &mut components, problems)
+                    ),
+                    "pcds" => config.pcds.merge(process_pcd_table(table.span(), entries, &mut pcds, problems)),
+                    _ => config.libraries.merge(process_library_table(table.span(), entries, &mut libraries, problems)),
+                }
+            }
+        }
+        config
+    }
+}
+
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Read without locations, so that any format can be read, such as JSON.
+        let document = Item::from_value(Value::deserialize(deserializer)?);
+        let mut problems = Problems::new();
+        let mut includes = Includes::new();
+        let config = Config::from_document(&document, &mut problems, &mut includes);
+        for (span, _) in includes {
+            problems.push((span, "include is only supported in a config file read with Config::from_path".to_string()));
+        }
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            let messages: Vec<String> = problems.0.into_iter().map(|(_, message, _)| message).collect();
+            Err(serde::de::Error::custom(messages.join("\n")))
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Clone)]
 pub struct LibraryKey {
     pub name: String,
     pub arch: Architecture,
@@ -31,10 +302,23 @@ pub struct LibraryKey {
 pub struct LibraryInstance {
     pub name: String,
     pub path: String,
+    /// The module types the instance supports, or empty when it supports all of them.
+    pub modules: Vec<Module>,
+    /// The config file the instance was read from, when it was read with `Config::from_path`.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl LibraryInstance {
+    /// Whether the instance can be used by a component of module type `module`. A component that does not
+    /// name its module type can use any instance.
+    pub fn supports(&self, module: &Module) -> bool {
+        self.modules.is_empty() || *module == Module::Common || self.modules.contains(module)
+    }
 }
 
 /// A lookup dictionary of library instances based off the library name and architecture.
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Default)]
 pub struct LibraryInstances {
   pub instances: HashMap<LibraryKey, LibraryInstance>,
 }
@@ -59,98 +343,256 @@ impl LibraryInstances {
         }
         None
     }
-}
-
-impl<'de> Deserialize<'de> for LibraryInstances {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let mut instances = LibraryInstances::default();
 
-        if let Some(arr) = Value::deserialize(deserializer)?.as_array() {
-            for table in arr {
-                let table = table.as_table().unwrap();
-                instances.merge(process_library_table(table).unwrap());
-            }
-        }
-        Ok(instances)
+    /// Looks up the instance of library `name` for `component`, in the component's own library overrides,
+    /// and then for the component's arch and module.
+    pub fn get_for_component(&self, name: &str, component: &ComponentInstance) -> Option<LibraryInstance> {
+        component.libraries.get(&name.to_lowercase())
+            .cloned()
+            .or_else(|| self.get(name, &component.arch, &component.module))
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Clone)]
 pub struct ComponentInstance {
+    /// The name of the component, as written.
+    pub name: String,
     pub arch: Architecture,
     pub module: Module,
+    /// The GUID of the image, `FILE_GUID` in an INF.
+    pub guid: Option<String>,
+    /// The version of the image, `VERSION_STRING` in an INF.
+    pub version: Option<String>,
+    /// The name of the image's entry point, `ENTRY_POINT` in an INF.
+    pub entry_point: Option<String>,
+    /// Library instances used by this component only, by lowercase library name.
+    pub libraries: HashMap<String, LibraryInstance>,
+    /// PCD values used by this component only, by lowercase PCD name.
+    pub pcds: HashMap<String, Pcd>,
 }
 
-/// A lookup dictionary of library instances based off the library name and architecture.
-#[derive(Debug, Serialize, Default)]
+/// A lookup dictionary of components based off the component name, with each arch and module type the
+/// component is built for.
+#[derive(Debug, Default)]
 pub struct ComponentInstances {
-  pub instances: HashMap<String, ComponentInstance>,
+  pub instances: HashMap<String, Vec<ComponentInstance>>,
+}
+
+/// A component, and an arch and module type it is built for.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BuildTarget {
+    pub component: String,
+    pub arch: Architecture,
+    pub module: Module,
 }
 
 impl ComponentInstances {
+    /// Replaces the build targets of each component of `other`.
     fn merge(&mut self, other: ComponentInstances) {
         self.instances.extend(other.instances);
     }
+
+    /// Adds the build targets of each component of `other`, from another table of the same file. A build
+    /// target for the same arch and module replaces the one before it.
+    fn append(&mut self, other: ComponentInstances) {
+        for (name, instances) in other.instances {
+            let targets = self.instances.entry(name).or_default();
+            for instance in instances {
+                match targets.iter_mut().find(|t| t.arch == instance.arch && t.module == instance.module) {
+                    Some(target) => *target = instance,
+                    None => targets.push(instance),
+                }
+            }
+        }
+    }
+
+    /// Looks up the first build target of component `name`.
     pub fn get(&self, name: &str) -> Option<ComponentInstance> {
-        self.instances.get(&name.to_lowercase()).cloned()
+        self.instances.get(&name.to_lowercase()).and_then(|instances| instances.first()).cloned()
+    }
+
+    /// Looks up component `name` as built for `target`, a target triple such as `x86_64-unknown-uefi`: the
+    /// first build target for the target's arch, or else the first that does not name an arch, which is
+    /// built for the target's arch. A component only listed for other arches is not built for the target.
+    pub fn get_for_target(&self, name: &str, target: &str) -> Option<ComponentInstance> {
+        self.get_for_arch(name, &Architecture::from_target(target))
+    }
+
+    /// Looks up component `name` as built for `arch`, the same way as [`ComponentInstances::get_for_target`].
+    pub fn get_for_arch(&self, name: &str, arch: &Architecture) -> Option<ComponentInstance> {
+        let arch = arch.clone();
+        let instances = self.instances.get(&name.to_lowercase())?;
+        if let Some(component) = instances.iter().find(|component| component.arch == arch) {
+            return Some(component.clone());
+        }
+
+        instances
+            .iter()
+            .find(|component| component.arch == Architecture::Common)
+            .map(|component| ComponentInstance { arch, ..component.clone() })
+    }
+
+    /// Every build target of every component, by component name.
+    pub fn targets(&self) -> Vec<BuildTarget> {
+        let mut names: Vec<&String> = self.instances.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| &self.instances[name])
+            .map(|component| BuildTarget {
+                component: component.name.clone(),
+                arch: component.arch.clone(),
+                module: component.module.clone(),
+            })
+            .collect()
     }
 }
 
-impl<'de> Deserialize<'de> for ComponentInstances {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let mut instances = ComponentInstances::default();
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Clone)]
+pub struct PcdKey {
+    pub name: String,
+    pub arch: Architecture,
+    pub module: Module,
+}
 
-        if let Some(arr) = Value::deserialize(deserializer)?.as_array() {
-            for table in arr {
-                let table = table.as_table().unwrap();
-                instances.merge(process_component_table(table).unwrap());
+/// The value a PCD is set to, and whether it is fixed at build time or patchable in the module.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Pcd {
+    pub name: String,
+    pub kind: PcdKind,
+    pub value: Value,
+}
+
+/// A lookup dictionary of PCDs based off the PCD name, architecture and module type.
+#[derive(Debug, Default)]
+pub struct Pcds {
+  pub values: HashMap<PcdKey, Pcd>,
+}
+
+impl Pcds {
+    fn merge(&mut self, other: Pcds) {
+        self.values.extend(other.values);
+    }
+
+    pub fn get(&self, name: &str, arch: &Architecture, module: &Module) -> Option<Pcd> {
+        let search_order = [
+            PcdKey { name: name.to_lowercase(), arch: arch.clone(), module: module.clone() },
+            PcdKey { name: name.to_lowercase(), arch: arch.clone(), module: Module::Common },
+            PcdKey { name: name.to_lowercase(), arch: Architecture::Common, module: module.clone() },
+            PcdKey { name: name.to_lowercase(), arch: Architecture::Common, module: Module::Common },
+        ];
+
+        for key in search_order.iter() {
+            if let Some(pcd) = self.values.get(key) {
+                return Some(pcd.clone())
             }
         }
-        Ok(instances)
+        None
+    }
+
+    /// Looks up PCD `name` for `component`, in the component's own PCD overrides, and then for the
+    /// component's arch and module.
+    pub fn get_for_component(&self, name: &str, component: &ComponentInstance) -> Option<Pcd> {
+        component.pcds.get(&name.to_lowercase())
+            .cloned()
+            .or_else(|| self.get(name, &component.arch, &component.module))
+    }
+}
+
+/// Converts each value of the array `item` to a `T`, such as the `arch` list of a table.
+fn list<T>(key: &Spanned<String>, item: &Spanned<Item>, problems: &mut Problems) -> Vec<T>
+where
+    for<'a> T: TryFrom<&'a Value, Error = String>,
+{
+    let Some(items) = item.get_ref().as_array() else {
+        problems.push((item.span(), format!(
+            "{} must be an array, such as {} = [{}]", key.get_ref(), key.get_ref(), item.get_ref().to_value()
+        )));
+        return vec![];
+    };
+
+    items
+        .iter()
+        .filter_map(|item| T::try_from(&item.get_ref().to_value()).map_err(|e| problems.push((item.span(), e))).ok())
+        .collect()
+}
+
+/// Converts the value `item`, or each value of the array `item`, to a `T`.
+fn one_or_list<T>(key: &Spanned<String>, item: &Spanned<Item>, problems: &mut Problems) -> Vec<T>
+where
+    for<'a> T: TryFrom<&'a Value, Error = String>,
+{
+    match item.get_ref() {
+        Item::Array(_) => list(key, item, problems),
+        _ => one(item, problems).into_iter().collect(),
+    }
+}
+
+/// Converts the value `item` to a `T`, such as the `kind` of a PCD table.
+fn one<T>(item: &Spanned<Item>, problems: &mut Problems) -> Option<T>
+where
+    for<'a> T: TryFrom<&'a Value, Error = String>,
+{
+    T::try_from(&item.get_ref().to_value()).map_err(|e| problems.push((item.span(), e))).ok()
+}
+
+fn is_key(key: &Spanned<String>, name: &str) -> bool {
+    key.get_ref().to_lowercase() == name
+}
+
+/// Reads the instance `value` of library `name`, `"pkg1::library::DebugLibBase"`, or, for an instance that
+/// supports only some module types, `"pkg1::library::DebugLibBase|DXE_DRIVER UEFI_DRIVER"`, as in the
+/// `LIBRARY_CLASS` of an INF.
+fn library_instance(name: &str, value: &str) -> LibraryInstance {
+    let (path, modules) = value.split_once('|').unwrap_or((value, ""));
+    LibraryInstance {
+        name: name.to_string(),
+        path: path.trim().to_string(),
+        modules: modules.split_whitespace().map(Module::from).collect(),
+        source: None,
     }
 }
 
-fn process_library_table(table: &Table) -> Result<LibraryInstances, ()>
+fn process_library_table(span: Range<usize>, table: &Table, definitions: &mut Definitions<LibraryKey>, problems: &mut Problems) -> LibraryInstances
 {
-    let mut library_list: Vec<(&str, &str)> = vec![];
+    let mut library_list: Vec<(&Spanned<String>, &str)> = vec![];
     let mut arch_list: Vec<Architecture> = vec![];
     let mut module_list: Vec<Module> = vec![];
+    let mut scope = TableScope { start: span.start, replace: false };
+    let mut empty = true;
 
-    // First loop, find the architecture and module values
+    // First loop, find the architecture, module and override values
     for (name, value) in table.iter() {
 
-        if name.to_lowercase() == "arch" {
-            arch_list = value.as_array()
-                .unwrap()
-                .iter()
-                .map(|arch| arch.try_into().unwrap())
-                .collect();
+        if is_key(name, "arch") {
+            arch_list = list(name, value, problems);
         }
-        else if name.to_lowercase() == "module" {
-            module_list = value.as_array()
-                .unwrap()
-                .iter()
-                .map(|module| module.try_into().unwrap())
-                .collect();
+        else if is_key(name, "module") {
+            module_list = list(name, value, problems);
+        }
+        else if is_key(name, "override") {
+            scope.replace = replace_flag(value, problems);
         }
         else {
-            library_list.push((name, value.as_str().unwrap()));
+            empty = false;
+            match value.get_ref().as_str() {
+                Some(path) => library_list.push((name, path)),
+                None => problems.push((value.span(), format!("Instance of library {} must be a string", name.get_ref()))),
+            }
         }
 
     }
 
+    if empty {
+        problems.push((span, "Library table lists no library instances".to_string()));
+    }
+
     // If no arch or module values are found, default to common
-    if arch_list.len() == 0 {
+    if arch_list.is_empty() {
         arch_list.push(Architecture::Common);
     }
 
-    if module_list.len() == 0 {
+    if module_list.is_empty() {
         module_list.push(Module::Common);
     }
 
@@ -159,52 +601,230 @@ fn process_library_table(table: &Table) -> Result<LibraryInstances, ()>
         for module in &module_list {
             for (name, path) in library_list.iter() {
                 let key = LibraryKey {
-                    name: name.to_lowercase(),
+                    name: name.get_ref().to_lowercase(),
                     arch: arch.clone(),
                     module: module.clone(),
                 };
-                instances.insert(key, LibraryInstance {
-                    name: name.to_lowercase(),
-                    path: path.to_string(),
-                });
+                let describe = || format!("Library {} for arch {} and module {}", name.get_ref(), arch, module);
+                definitions.define(key.clone(), name.span(), &scope, describe, problems);
+                instances.insert(key, library_instance(name.get_ref(), path));
             }
         }
     }
 
-    Ok(LibraryInstances { instances})
+    LibraryInstances { instances }
 }
 
-fn process_component_table(table: &Table) -> Result<ComponentInstances, ()>
+fn process_component_table(
+    span: Range<usize>,
+    table: &Table,
+    definitions: &mut Definitions<(String, Architecture, Module)>,
+    problems: &mut Problems,
+) -> ComponentInstances
 {
-    let mut arch = Architecture::Common;
-    let mut module = Module::Common;
+    let mut arch_list: Vec<Architecture> = vec![];
+    let mut module_list: Vec<Module> = vec![];
+    let mut scope = TableScope { start: span.start, replace: false };
     let mut component_list = vec![];
 
+    // A component is built for one arch and module type, `arch = "X64"`, or several, `arch = ["X64", "AARCH64"]`.
+    for (name, value) in table.iter() {
+        if is_key(name, "arch") {
+            arch_list = one_or_list(name, value, problems);
+        }
+        else if is_key(name, "module") {
+            module_list = one_or_list(name, value, problems);
+        }
+        else if is_key(name, "override") {
+            scope.replace = replace_flag(value, problems);
+        }
+        else {
+            component_list.push((name, value));
+        }
+    }
+
+    if component_list.is_empty() {
+        problems.push((span, "Component table lists no components".to_string()));
+    }
+
+    if arch_list.is_empty() {
+        arch_list.push(Architecture::Common);
+    }
+
+    if module_list.is_empty() {
+        module_list.push(Module::Common);
+    }
+
+    let mut instances: HashMap<String, Vec<ComponentInstance>> = HashMap::new();
+    for (name, value) in component_list {
+        let mut instance = ComponentInstance {
+            name: name.get_ref().to_string(),
+            arch: Architecture::Common,
+            module: Module::Common,
+            guid: None,
+            version: None,
+            entry_point: None,
+            libraries: HashMap::new(),
+            pcds: HashMap::new(),
+        };
+
+        match value.get_ref().as_table() {
+            Some(table) => process_component_overrides(name.get_ref(), table, &mut instance, problems),
+            None => problems.push((value.span(), format!(
+                "Component {} must be a table, such as {} = {{}}", name.get_ref(), name.get_ref()
+            ))),
+        }
+
+        let mut targets = vec![];
+        for arch in &arch_list {
+            for module in &module_list {
+                let key = (name.get_ref().to_lowercase(), arch.clone(), module.clone());
+                let describe = || format!("Component {} for arch {} and module {}", name.get_ref(), arch, module);
+                definitions.define(key, name.span(), &scope, describe, problems);
+                targets.push(ComponentInstance { arch: arch.clone(), module: module.clone(), ..instance.clone() });
+            }
+        }
+        instances.entry(name.get_ref().to_lowercase()).or_default().extend(targets);
+    }
+
+    ComponentInstances { instances }
+}
+
+/// Reads the inline table of component `name`: its metadata, under the names used here or in an INF
+/// (`guid` or `FILE_GUID`, `version` or `VERSION_STRING`, `entry_point` or `ENTRY_POINT`), a `pcds` table
+/// of PCD overrides, and library overrides, `DebugLib = "pkg1::library::DebugLibBase"`.
+fn process_component_overrides(name: &str, table: &Table, instance: &mut ComponentInstance, problems: &mut Problems)
+{
+    let string = |key: &Spanned<String>, value: &Spanned<Item>, problems: &mut Problems| match value.get_ref().as_str() {
+        Some(s) => Some(s.to_string()),
+        None => {
+            problems.push((value.span(), format!("{} of component {} must be a string", key.get_ref(), name)));
+            None
+        }
+    };
+
+    let mut pcds = None;
+    for (key, value) in table.iter() {
+        match key.get_ref().to_lowercase().replace('_', "").as_str() {
+            "guid" | "fileguid" => instance.guid = string(key, value, problems),
+            "version" | "versionstring" => instance.version = string(key, value, problems),
+            "entrypoint" => instance.entry_point = string(key, value, problems),
+            "pcds" => pcds = Some(value),
+            _ => match value.get_ref().as_str() {
+                Some(path) => {
+                    instance.libraries.insert(key.get_ref().to_lowercase(), library_instance(key.get_ref(), path));
+                }
+                None => problems.push((value.span(), format!(
+                    "Library {} of component {} must be a string", key.get_ref(), name
+                ))),
+            },
+        }
+    }
+
+    let Some(pcds) = pcds else {
+        return;
+    };
+    let Some(pcds) = pcds.get_ref().as_table() else {
+        problems.push((pcds.span(), format!("pcds of component {} must be a table", name)));
+        return;
+    };
+
+    let mut kind = PcdKind::FixedAtBuild;
+    for (_, value) in pcds.iter().filter(|(key, _)| is_key(key, "kind")) {
+        kind = one(value, problems).unwrap_or(kind);
+    }
+    for (pcd, value) in pcds.iter().filter(|(key, _)| !is_key(key, "kind")) {
+        if let Some(value) = pcd_value(pcd, value, problems) {
+            instance.pcds.insert(pcd.get_ref().to_lowercase(), Pcd {
+                name: pcd.get_ref().to_string(),
+                kind: kind.clone(),
+                value,
+            });
+        }
+    }
+}
+
+/// The value of PCD `name`, which must be an integer, float, boolean or string.
+fn pcd_value(name: &Spanned<String>, value: &Spanned<Item>, problems: &mut Problems) -> Option<Value> {
+    match value.get_ref() {
+        Item::Value(value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::String(_))) => {
+            Some(value.clone())
+        }
+        _ => {
+            problems.push((value.span(), format!(
+                "PCD {} must be an integer, float, boolean or string", name.get_ref()
+            )));
+            None
+        }
+    }
+}
+
+fn process_pcd_table(span: Range<usize>, table: &Table, definitions: &mut Definitions<PcdKey>, problems: &mut Problems) -> Pcds
+{
+    let mut pcd_list: Vec<(&Spanned<String>, Value)> = vec![];
+    let mut arch_list: Vec<Architecture> = vec![];
+    let mut module_list: Vec<Module> = vec![];
+    let mut kind = PcdKind::FixedAtBuild;
+    let mut scope = TableScope { start: span.start, replace: false };
+    let mut empty = true;
+
+    // First loop, find the architecture, module and kind values
     for (name, value) in table.iter() {
-        if name.to_lowercase() == "arch" {
-            arch = value.try_into().unwrap();
+
+        if is_key(name, "arch") {
+            arch_list = list(name, value, problems);
+        }
+        else if is_key(name, "module") {
+            module_list = list(name, value, problems);
+        }
+        else if is_key(name, "kind") {
+            kind = one(value, problems).unwrap_or(kind);
         }
-        else if name.to_lowercase() == "module" {
-            module = value.try_into().unwrap();
+        else if is_key(name, "override") {
+            scope.replace = replace_flag(value, problems);
         }
         else {
-            component_list.push(name);
+            empty = false;
+            if let Some(value) = pcd_value(name, value, problems) {
+                pcd_list.push((name, value));
+            }
         }
     }
-    
-    let instances = component_list.iter()
-    .map(|name| {
-        (
-            name.to_lowercase(),
-            ComponentInstance {
-                arch: arch.clone(),
-                module: module.clone(),
+
+    if empty {
+        problems.push((span, "PCD table lists no PCDs".to_string()));
+    }
+
+    // If no arch or module values are found, default to common
+    if arch_list.is_empty() {
+        arch_list.push(Architecture::Common);
+    }
+
+    if module_list.is_empty() {
+        module_list.push(Module::Common);
+    }
+
+    let mut values: HashMap<PcdKey, Pcd> = HashMap::new();
+    for arch in &arch_list {
+        for module in &module_list {
+            for (name, value) in pcd_list.iter() {
+                let key = PcdKey {
+                    name: name.get_ref().to_lowercase(),
+                    arch: arch.clone(),
+                    module: module.clone(),
+                };
+                let describe = || format!("PCD {} for arch {} and module {}", name.get_ref(), arch, module);
+                definitions.define(key.clone(), name.span(), &scope, describe, problems);
+                values.insert(key, Pcd {
+                    name: name.get_ref().to_string(),
+                    kind: kind.clone(),
+                    value: value.clone(),
+                });
             }
-        )}
-    )
-    .collect();
+        }
+    }
 
-    Ok(ComponentInstances{instances})
+    Pcds { values }
 }
 
 
@@ -247,4 +867,350 @@ mod tests {
             Module::DxeDriver
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pcds() {
+        let data = include_str!("../tests/data/config.toml");
+        let config = toml::from_str::<Config>(data).unwrap();
+
+        let pcd = config.pcds.get("PcdSerialPortBase", &Architecture::X64, &Module::DxeDriver).unwrap();
+        assert_eq!(pcd.value, Value::Integer(0x402));
+        assert_eq!(pcd.kind, PcdKind::FixedAtBuild);
+
+        let pcd = config.pcds.get("PcdDebugLogLevel", &Architecture::Common, &Module::Common).unwrap();
+        assert_eq!(pcd.value, Value::String("log::LevelFilter::Info".to_string()));
+        assert_eq!(pcd.kind, PcdKind::FixedAtBuild);
+
+        let pcd = config.pcds.get("pcddebugloglevel", &Architecture::X64, &Module::DxeDriver).unwrap();
+        assert_eq!(pcd.value, Value::String("log::LevelFilter::Debug".to_string()));
+        assert_eq!(pcd.kind, PcdKind::PatchableInModule);
+
+        assert!(config.pcds.get("PcdUnknown", &Architecture::Common, &Module::Common).is_none());
+
+        let error = toml::from_str::<Config>("[[pcds]]\nkind = \"Dynamic\"\nPcdFoo = 1\n").unwrap_err();
+        assert!(error.to_string().contains("Unknown PCD kind"));
+    }
+
+    #[test]
+    fn test_component_overrides() {
+        let data = include_str!("../tests/data/config.toml");
+        let config = toml::from_str::<Config>(data).unwrap();
+
+        let component = config.components.get("MyDriver3").unwrap();
+        assert_eq!(component.guid.as_deref(), Some("6987936E-ED34-44DB-AE97-1FA5E4ED2116"));
+        assert_eq!(component.version.as_deref(), Some("1.2"));
+        assert_eq!(component.entry_point.as_deref(), Some("DriverEntry"));
+
+        // The component's overrides come first, then the arch and module search order.
+        assert_eq!(
+            config.libraries.get_for_component("AdvLib", &component).unwrap().path,
+            "pkg2::library::AdvLibSpecial"
+        );
+        assert_eq!(
+            config.libraries.get_for_component("TestLib", &component).unwrap().path,
+            "pkg1::library::TestLibDxeOpt<AdvLib>"
+        );
+
+        let pcd = config.pcds.get_for_component("PcdSerialPortBase", &component).unwrap();
+        assert_eq!(pcd.value, Value::Integer(0x3F8));
+        assert_eq!(pcd.kind, PcdKind::PatchableInModule);
+        let pcd = config.pcds.get_for_component("PcdDebugLogLevel", &component).unwrap();
+        assert_eq!(pcd.value, Value::String("log::LevelFilter::Debug".to_string()));
+
+        // Components without overrides use the search order.
+        let component = config.components.get("MyDriver").unwrap();
+        assert!(component.guid.is_none());
+        assert_eq!(
+            config.libraries.get_for_component("AdvLib", &component).unwrap().path,
+            "pkg1::library::AdvLibDxeOpt"
+        );
+
+        let error = toml::from_str::<Config>("[[components]]\nMyDriver = { DebugLib = 1 }\n").unwrap_err();
+        assert!(error.to_string().contains("Library DebugLib of component MyDriver must be a string"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let data = include_str!("../tests/data/invalid_config.toml");
+        let errors: Vec<String> = Config::from_str(data).unwrap_err().iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec![
+            "2:8: arch must be an array, such as arch = [\"X64\"]",
+            "3:12: Instance of library DebugLib must be a string",
+            "5:1: Library table lists no library instances",
+            "10:12: Component MyDriver must be a table, such as MyDriver = {}",
+            "13:8: Unknown PCD kind dynamic, expected FixedAtBuild or PatchableInModule",
+            "14:10: PCD PcdFoo must be an integer, float, boolean or string",
+            "16:3: Unknown key librarys, expected include, libraries, components or pcds",
+        ]);
+
+        let error = Config::from_str("[[libraries]\nDebugLib = 1\n").unwrap_err();
+        assert_eq!(error.len(), 1);
+        assert_eq!(error[0].location.as_ref().unwrap().line, 1);
+
+        let error = Config::from_str("[libraries]\nDebugLib = \"DebugLibBase\"\n").unwrap_err();
+        assert_eq!(error[0].to_string(), "1:1: libraries must be an array of tables, written [[libraries]]");
+
+        let error = Config::from_path("tests/data/missing.toml").unwrap_err();
+        assert_eq!(error[0].to_string(), "tests/data/missing.toml: Failed to read: entity not found");
+
+        let error = Config::from_path("tests/data/invalid_config.toml").unwrap_err();
+        assert_eq!(error[1].to_string(), "tests/data/invalid_config.toml:3:12: Instance of library DebugLib must be a string");
+    }
+
+    #[test]
+    fn test_includes() {
+        let config = Config::from_path("tests/data/include/platform.toml").unwrap();
+        let library = |name: &str| config.libraries.get(name, &Architecture::Common, &Module::Common).unwrap();
+        let source = |name: &str| library(name).source.unwrap().file_name().unwrap().to_string_lossy().into_owned();
+
+        // The including file comes last, and a later include comes after an earlier one and its includes.
+        assert_eq!(library("DebugLib").path, "pkg1::library::DebugLibPlatform");
+        assert_eq!(source("DebugLib"), "platform.toml");
+        assert_eq!(library("PrintLib").path, "pkg1::library::PrintLibSerial");
+        assert_eq!(source("PrintLib"), "board.toml");
+        assert_eq!(library("WriteLib").path, "pkg1::library::WriteLibDebug");
+        assert_eq!(source("WriteLib"), "debug.toml");
+
+        assert_eq!(config.components.get("MyDriver").unwrap().module, Module::DxeDriver);
+        let pcd = config.pcds.get("PcdDebugLogLevel", &Architecture::Common, &Module::Common).unwrap();
+        assert_eq!(pcd.value, Value::String("log::LevelFilter::Trace".to_string()));
+
+        let files: Vec<String> = config.files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["platform.toml", "base.toml", "board.toml", "debug.toml"]);
+
+        // Layering configs by hand gives the same precedence.
+        let mut layered = Config::from_path("tests/data/include/base.toml").unwrap();
+        layered.merge(Config::from_path("tests/data/include/debug.toml").unwrap());
+        assert_eq!(
+            layered.libraries.get("PrintLib", &Architecture::Common, &Module::Common).unwrap().path,
+            "pkg1::library::PrintLibDebug"
+        );
+        assert_eq!(
+            layered.libraries.get("DebugLib", &Architecture::Common, &Module::Common).unwrap().path,
+            "pkg1::library::DebugLibBase"
+        );
+
+        let errors: Vec<String> = Config::from_path("tests/data/include/cycle_a.toml")
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(errors, vec![
+            "tests/data/include/cycle_b.toml:1:12: Included file missing.toml not found",
+            "tests/data/include/cycle_b.toml:1:28: Circular include: cycle_a.toml -> cycle_b.toml -> cycle_a.toml",
+        ]);
+
+        let error = Config::from_str("include = [\"base.toml\"]\n").unwrap_err();
+        assert_eq!(error[0].to_string(), "1:12: include is only supported in a config file read with Config::from_path");
+    }
+
+    #[test]
+    fn test_architectures() {
+        let config = Config::from_str(concat!(
+            "[[libraries]]\nDebugLib = \"DebugLibBase\"\n",
+            "[[libraries]]\narch = [\"IA32\", \"x86_64\"]\nDebugLib = \"DebugLibX86\"\n",
+            "[[libraries]]\narch = [\"aarch64\", \"RISCV64\", \"LoongArch64\"]\nDebugLib = \"DebugLibMmio\"\n",
+            "[[components]]\nMyDriver = {}\n",
+            "[[components]]\narch = \"ARM\"\nMyArmDriver = {}\n",
+        )).unwrap();
+
+        let debug_lib = |arch: Architecture| config.libraries.get("DebugLib", &arch, &Module::Common).unwrap().path;
+        assert_eq!(debug_lib(Architecture::Ia32), "DebugLibX86");
+        assert_eq!(debug_lib(Architecture::X64), "DebugLibX86");
+        assert_eq!(debug_lib(Architecture::Aarch64), "DebugLibMmio");
+        assert_eq!(debug_lib(Architecture::RiscV64), "DebugLibMmio");
+        assert_eq!(debug_lib(Architecture::LoongArch64), "DebugLibMmio");
+        assert_eq!(debug_lib(Architecture::Arm), "DebugLibBase");
+
+        assert_eq!(Architecture::from_target("x86_64-unknown-uefi"), Architecture::X64);
+        assert_eq!(Architecture::from_target("i686-unknown-uefi"), Architecture::Ia32);
+        assert_eq!(Architecture::from_target("aarch64-unknown-uefi"), Architecture::Aarch64);
+        assert_eq!(Architecture::from_target("riscv64gc-unknown-none-elf"), Architecture::RiscV64);
+        assert_eq!(Architecture::from_target("thumbv7em-none-eabi"), Architecture::Arm);
+        assert_eq!(Architecture::from_target("x86_64"), Architecture::X64);
+
+        // A component without an arch is built for the target's.
+        let component = config.components.get_for_target("MyDriver", "aarch64-unknown-uefi").unwrap();
+        assert_eq!(component.arch, Architecture::Aarch64);
+
+        // A component listed only for other arches has no build target for the target's.
+        let component = config.components.get_for_target("MyArmDriver", "thumbv7em-none-eabi").unwrap();
+        assert_eq!(component.arch, Architecture::Arm);
+        assert!(config.components.get_for_target("MyArmDriver", "x86_64-unknown-uefi").is_none());
+    }
+
+    #[test]
+    fn test_modules() {
+        let config = Config::from_str(concat!(
+            "[[libraries]]\n",
+            "DebugLib = \"pkg1::library::DebugLibStd|HOST_APPLICATION\"\n",
+            "SerialLib = \"pkg1::library::SerialLibBase | DXE_DRIVER PEIM SEC\"\n",
+            "PrintLib = \"pkg1::library::PrintLibBase\"\n",
+            "[[components]]\nmodule = \"DXE_SMM_DRIVER\"\nMyDriver = {}\n",
+        )).unwrap();
+
+        let library = |name: &str| config.libraries.get(name, &Architecture::Common, &Module::Common).unwrap();
+        assert_eq!(library("DebugLib").path, "pkg1::library::DebugLibStd");
+        assert_eq!(library("DebugLib").modules, vec![Module::HostApplication]);
+        assert!(library("DebugLib").supports(&Module::HostApplication));
+        assert!(!library("DebugLib").supports(&Module::DxeDriver));
+        assert!(library("DebugLib").supports(&Module::Common));
+
+        assert_eq!(library("SerialLib").path, "pkg1::library::SerialLibBase");
+        assert_eq!(library("SerialLib").modules, vec![Module::DxeDriver, Module::Peim, Module::Sec]);
+        assert!(!library("SerialLib").supports(&Module::PeiCore));
+        assert!(library("PrintLib").supports(&Module::PeiCore));
+
+        assert_eq!(config.components.get("MyDriver").unwrap().module, Module::DxeSmmDriver);
+        assert_eq!(Module::DxeSmmDriver.to_string(), "DXE_SMM_DRIVER");
+        for name in ["SEC", "PEI_CORE", "PEIM", "DXE_CORE", "DXE_RUNTIME_DRIVER", "UEFI_APPLICATION", "HOST_APPLICATION"] {
+            assert_eq!(Module::from(name).to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_build_targets() {
+        let config = Config::from_str(concat!(
+            "[[components]]\narch = [\"X64\", \"AARCH64\"]\nmodule = [\"DXE_DRIVER\", \"UEFI_DRIVER\"]\n",
+            "MyDriver = { guid = \"6987936E-ED34-44DB-AE97-1FA5E4ED2116\" }\n",
+            "[[components]]\narch = \"IA32\"\nmodule = \"PEIM\"\nMyDriver = {}\nMyPeim = {}\n",
+            "[[components]]\nMyApp = {}\n",
+        )).unwrap();
+
+        let target = |component: &str, arch: Architecture, module: Module| BuildTarget {
+            component: component.to_string(),
+            arch,
+            module,
+        };
+        assert_eq!(config.components.targets(), vec![
+            target("MyApp", Architecture::Common, Module::Common),
+            target("MyDriver", Architecture::X64, Module::DxeDriver),
+            target("MyDriver", Architecture::X64, Module::UefiDriver),
+            target("MyDriver", Architecture::Aarch64, Module::DxeDriver),
+            target("MyDriver", Architecture::Aarch64, Module::UefiDriver),
+            target("MyDriver", Architecture::Ia32, Module::Peim),
+            target("MyPeim", Architecture::Ia32, Module::Peim),
+        ]);
+
+        // The entry for the target's arch is picked, and the first of them for several module types.
+        let component = config.components.get_for_target("MyDriver", "aarch64-unknown-uefi").unwrap();
+        assert_eq!((component.arch, component.module), (Architecture::Aarch64, Module::DxeDriver));
+        assert_eq!(component.guid.as_deref(), Some("6987936E-ED34-44DB-AE97-1FA5E4ED2116"));
+        let component = config.components.get_for_target("MyDriver", "i686-unknown-uefi").unwrap();
+        assert_eq!((component.arch, component.module), (Architecture::Ia32, Module::Peim));
+        let component = config.components.get_for_target("MyApp", "x86_64-unknown-uefi").unwrap();
+        assert_eq!(component.arch, Architecture::X64);
+        assert!(config.components.get_for_target("MyPeim", "aarch64-unknown-uefi").is_none());
+
+        // A component layered over replaces all of its build targets.
+        let mut layered = config;
+        layered.merge(Config::from_str("[[components]]\narch = \"X64\"\nMyDriver = {}\n").unwrap());
+        let targets: Vec<BuildTarget> = layered.components.targets().into_iter().filter(|t| t.component == "MyDriver").collect();
+        assert_eq!(targets, vec![target("MyDriver", Architecture::X64, Module::Common)]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let errors = |source: &str| -> Vec<String> {
+            Config::from_str(source).unwrap_err().iter().map(ToString::to_string).collect()
+        };
+
+        // A library, component or PCD defined again in a later table, with both places reported.
+        assert_eq!(errors(concat!(
+            "[[libraries]]\narch = [\"X64\"]\nDebugLib = \"pkg1::library::DebugLibBase\"\n",
+            "[[libraries]]\narch = [\"X64\"]\nDebugLib = \"pkg1::library::DebugLibSerial\"\n",
+            "[[components]]\nMyDriver = {}\n",
+            "[[components]]\nmydriver = {}\n",
+            "[[pcds]]\nPcdDebugLogLevel = \"log::LevelFilter::Info\"\n",
+            "[[pcds]]\nPcdDebugLogLevel = \"log::LevelFilter::Trace\"\n",
+        )), vec![
+            "6:1: Library DebugLib for arch X64 and module COMMON is defined twice, \
+             set override = true in the later table to replace it (first defined at 3:1)",
+            "10:1: Component mydriver for arch COMMON and module COMMON is defined twice, \
+             set override = true in the later table to replace it (first defined at 8:1)",
+            "14:1: PCD PcdDebugLogLevel for arch COMMON and module COMMON is defined twice, \
+             set override = true in the later table to replace it (first defined at 12:1)",
+        ]);
+
+        // Names that differ only in case collide, even in the same table.
+        assert_eq!(errors("[[libraries]]\nDebugLib = \"a::DebugLib\"\nDEBUGLIB = \"b::DebugLib\"\n"), vec![
+            "3:1: Library DEBUGLIB for arch COMMON and module COMMON is defined twice (first defined at 2:1)",
+        ]);
+
+        // A table that covers a different arch or module does not conflict.
+        Config::from_str(concat!(
+            "[[libraries]]\nDebugLib = \"pkg1::library::DebugLibBase\"\n",
+            "[[libraries]]\narch = [\"X64\"]\nDebugLib = \"pkg1::library::DebugLibSerial\"\n",
+        )).unwrap();
+
+        // override = true replaces the earlier definitions.
+        let config = Config::from_str(concat!(
+            "[[libraries]]\nDebugLib = \"pkg1::library::DebugLibBase\"\n",
+            "[[libraries]]\noverride = true\nDebugLib = \"pkg1::library::DebugLibSerial\"\n",
+            "[[components]]\nMyDriver = { guid = \"6987936E-ED34-44DB-AE97-1FA5E4ED2116\" }\n",
+            "[[components]]\noverride = true\nMyDriver = { version = \"1.0\" }\n",
+        )).unwrap();
+        assert_eq!(
+            config.libraries.get("DebugLib", &Architecture::Common, &Module::Common).unwrap().path,
+            "pkg1::library::DebugLibSerial"
+        );
+        assert_eq!(config.components.targets().len(), 1);
+        assert_eq!(config.components.get("MyDriver").unwrap().version.as_deref(), Some("1.0"));
+
+        assert_eq!(errors("[[pcds]]\noverride = \"yes\"\nPcdDebugLogLevel = 1\n"), vec![
+            "2:12: override must be true or false",
+        ]);
+
+        // A later include layer replaces definitions without override.
+        Config::from_path("tests/data/include/platform.toml").unwrap();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config::from_path("tests/data/config.toml").unwrap();
+        let written = toml::to_string(&config).unwrap();
+
+        // Reading back what was written gives the same config, which is written the same way.
+        let read = Config::from_str(&written).unwrap();
+        assert_eq!(toml::to_string(&read).unwrap(), written);
+        assert_eq!(read.components.targets(), config.components.targets());
+        let component = read.components.get("MyDriver3").unwrap();
+        assert_eq!(component.guid.as_deref(), Some("6987936E-ED34-44DB-AE97-1FA5E4ED2116"));
+        let instance = read.libraries.get_for_component("AdvLib", &component).unwrap();
+        assert_eq!(instance.path, "pkg2::library::AdvLibSpecial");
+        let pcd = read.pcds.get_for_component("PcdSerialPortBase", &component).unwrap();
+        assert_eq!((pcd.kind, pcd.value), (PcdKind::PatchableInModule, Value::Integer(0x3F8)));
+
+        // Each arch and module has its own table, in order, with the names in order and as written.
+        let config = Config::from_str(concat!(
+            "[[libraries]]\nmodule = [\"DXE_DRIVER\"]\nPrintLib = \"pkg1::library::PrintLibDxe\"\n",
+            "[[libraries]]\nWriteLib = \"pkg1::library::WriteLibStd|STD HOST_APPLICATION\"\n",
+            "DebugLib = \"pkg1::library::DebugLibBase\"\n",
+            "[[components]]\narch = [\"X64\", \"IA32\"]\nMyDriver = { DebugLib = \"pkg1::library::DebugLibSerial\" }\n",
+        )).unwrap();
+        assert_eq!(toml::to_string(&config).unwrap(), concat!(
+            "[[libraryinstances]]\n",
+            "DebugLib = \"pkg1::library::DebugLibBase\"\n",
+            "WriteLib = \"pkg1::library::WriteLibStd|STD HOST_APPLICATION\"\n\n",
+            "[[libraryinstances]]\nmodule = [\"DXE_DRIVER\"]\nPrintLib = \"pkg1::library::PrintLibDxe\"\n\n",
+            "[[components]]\narch = [\"IA32\"]\n\n[components.MyDriver]\nDebugLib = \"pkg1::library::DebugLibSerial\"\n\n",
+            "[[components]]\narch = [\"X64\"]\n\n[components.MyDriver]\nDebugLib = \"pkg1::library::DebugLibSerial\"\n",
+        ));
+    }
+
+    #[test]
+    fn test_json() {
+        let config = Config::from_path("tests/data/config.toml").unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.starts_with(r#"{"libraryinstances":[{"AdvLib":"pkg1::library::AdvLibBase"},"#));
+
+        let read: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(toml::to_string(&read).unwrap(), toml::to_string(&config).unwrap());
+
+        let error = serde_json::from_str::<Config>(r#"{"pcds":[{"kind":"Dynamic","PcdFoo":1}]}"#).unwrap_err();
+        assert!(error.to_string().contains("Unknown PCD kind dynamic"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Writing a config back out in the shape it is read in, as `[[libraryinstances]]`, `[[components]]` and
+//! `[[pcds]]` tables, so that it can be read again with `Config::from_str`, or as JSON with the same shape.
+//!
+//! Each section has one table for each arch and module (and, for PCDs, kind) that something is defined for,
+//! in order, with the names in each table in order, so that the same config is always written the same way.
+use std::collections::BTreeMap;
+
+use serde::ser::{Error, Serialize, SerializeMap, SerializeSeq, Serializer};
+use toml::Value;
+
+use crate::{Architecture, ComponentInstance, ComponentInstances, Config, LibraryInstance, LibraryInstances, Module, PcdKind, Pcd, Pcds};
+
+impl Serialize for Config {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if !self.libraries.instances.is_empty() {
+            map.serialize_entry("libraryinstances", &self.libraries)?;
+        }
+        if !self.components.instances.is_empty() {
+            map.serialize_entry("components", &self.components)?;
+        }
+        if !self.pcds.values.is_empty() {
+            map.serialize_entry("pcds", &self.pcds)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for LibraryInstances {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut groups: BTreeMap<(Architecture, Module), Group<String>> = BTreeMap::new();
+        for (key, instance) in &self.instances {
+            groups
+                .entry((key.arch.clone(), key.module.clone()))
+                .or_insert_with(|| Group::new(&key.arch, &key.module, None))
+                .insert(&instance.name, instance_value(instance));
+        }
+        serializer.collect_seq(groups.values())
+    }
+}
+
+impl Serialize for ComponentInstances {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut groups: BTreeMap<(Architecture, Module), Group<&ComponentInstance>> = BTreeMap::new();
+        for component in self.instances.values().flatten() {
+            groups
+                .entry((component.arch.clone(), component.module.clone()))
+                .or_insert_with(|| Group::new(&component.arch, &component.module, None))
+                .insert(&component.name, component);
+        }
+        serializer.collect_seq(groups.values())
+    }
+}
+
+impl Serialize for Pcds {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut groups: BTreeMap<(Architecture, Module, PcdKind), Group<&Value>> = BTreeMap::new();
+        for (key, pcd) in &self.values {
+            groups
+                .entry((key.arch.clone(), key.module.clone(), pcd.kind.clone()))
+                .or_insert_with(|| Group::new(&key.arch, &key.module, Some(&pcd.kind)))
+                .insert(&pcd.name, &pcd.value);
+        }
+        serializer.collect_seq(groups.values())
+    }
+}
+
+impl Serialize for ComponentInstance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (key, value) in [("guid", &self.guid), ("version", &self.version), ("entry_point", &self.entry_point)] {
+            if let Some(value) = value {
+                map.serialize_entry(key, value)?;
+            }
+        }
+
+        let mut libraries: Vec<&LibraryInstance> = self.libraries.values().collect();
+        libraries.sort_by_key(|instance| instance.name.to_lowercase());
+        for instance in libraries {
+            map.serialize_entry(&instance.name, &instance_value(instance))?;
+        }
+
+        if !self.pcds.is_empty() {
+            map.serialize_entry("pcds", &ComponentPcds(self))?;
+        }
+        map.end()
+    }
+}
+
+/// The `pcds` table of a component, which has one kind for all of its PCDs.
+struct ComponentPcds<'a>(&'a ComponentInstance);
+
+impl Serialize for ComponentPcds<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut pcds: Vec<&Pcd> = self.0.pcds.values().collect();
+        pcds.sort_by_key(|pcd| pcd.name.to_lowercase());
+        let kind = &pcds[0].kind;
+        if pcds.iter().any(|pcd| pcd.kind != *kind) {
+            return Err(S::Error::custom(format!(
+                "PCDs of component {} are of different kinds, which its pcds table cannot hold", self.0.name
+            )));
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        if *kind != PcdKind::FixedAtBuild {
+            map.serialize_entry("kind", &kind_name(kind))?;
+        }
+        for pcd in pcds {
+            map.serialize_entry(&pcd.name, &pcd.value)?;
+        }
+        map.end()
+    }
+}
+
+/// One table of a section: its `arch`, `module` and `kind`, when they are not the default, and what it
+/// defines, by name.
+struct Group<T> {
+    arch: Option<String>,
+    module: Option<String>,
+    kind: Option<&'static str>,
+    entries: BTreeMap<String, (String, T)>,
+}
+
+impl<T> Group<T> {
+    fn new(arch: &Architecture, module: &Module, kind: Option<&PcdKind>) -> Self {
+        Group {
+            arch: (*arch != Architecture::Common).then(|| arch.to_string()),
+            module: (*module != Module::Common).then(|| module.to_string()),
+            kind: kind.filter(|kind| **kind != PcdKind::FixedAtBuild).map(kind_name),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, name: &str, value: T) {
+        self.entries.insert(name.to_lowercase(), (name.to_string(), value));
+    }
+}
+
+impl<T: Serialize> Serialize for Group<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(arch) = &self.arch {
+            map.serialize_entry("arch", &List(arch))?;
+        }
+        if let Some(module) = &self.module {
+            map.serialize_entry("module", &List(module))?;
+        }
+        if let Some(kind) = self.kind {
+            map.serialize_entry("kind", kind)?;
+        }
+        for (name, value) in self.entries.values() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A single `arch` or `module`, written as a list, which every section reads.
+struct List<'a>(&'a str);
+
+impl Serialize for List<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(1))?;
+        seq.serialize_element(self.0)?;
+        seq.end()
+    }
+}
+
+/// A library instance as it is written, with the module types it supports: `"path|DXE_DRIVER UEFI_DRIVER"`.
+fn instance_value(instance: &LibraryInstance) -> String {
+    if instance.modules.is_empty() {
+        return instance.path.clone();
+    }
+    let modules: Vec<String> = instance.modules.iter().map(ToString::to_string).collect();
+    format!("{}|{}", instance.path, modules.join(" "))
+}
+
+fn kind_name(kind: &PcdKind) -> &'static str {
+    match kind {
+        PcdKind::FixedAtBuild => "FixedAtBuild",
+        PcdKind::PatchableInModule => "PatchableInModule",
+    }
+}
@@ -0,0 +1,129 @@
+//! A TOML document in which every key and value keeps its location in the file, so that each problem
+//! found in a config file can be reported where it is.
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use toml::{Spanned, Value};
+
+/// The key of the datetime map, which is how toml hands a datetime to a deserializer.
+const DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// A table, with its keys in the order they are written.
+pub(crate) type Table = Vec<(Spanned<String>, Spanned<Item>)>;
+
+pub(crate) enum Item {
+    /// A string, integer, float, boolean or datetime.
+    Value(Value),
+    Array(Vec<Spanned<Item>>),
+    Table(Table),
+}
+
+impl Item {
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Item::Value(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Spanned<Item>]> {
+        match self {
+            Item::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_table(&self) -> Option<&Table> {
+        match self {
+            Item::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// The item of a plain TOML value, such as one read from JSON, with every location empty.
+    pub(crate) fn from_value(value: Value) -> Item {
+        let unspanned = |value| Spanned::new(0..0, Item::from_value(value));
+        match value {
+            Value::Array(items) => Item::Array(items.into_iter().map(unspanned).collect()),
+            Value::Table(table) => Item::Table(
+                table.into_iter().map(|(key, value)| (Spanned::new(0..0, key), unspanned(value))).collect()
+            ),
+            value => Item::Value(value),
+        }
+    }
+
+    /// The item as a plain TOML value, without locations.
+    pub(crate) fn to_value(&self) -> Value {
+        match self {
+            Item::Value(value) => value.clone(),
+            Item::Array(items) => Value::Array(items.iter().map(|item| item.get_ref().to_value()).collect()),
+            Item::Table(table) => Value::Table(
+                table.iter().map(|(key, item)| (key.get_ref().clone(), item.get_ref().to_value())).collect()
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Item {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ItemVisitor)
+    }
+}
+
+struct ItemVisitor;
+
+impl<'de> Visitor<'de> for ItemVisitor {
+    type Value = Item;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Item, E> {
+        Ok(Item::Value(Value::Boolean(value)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Item, E> {
+        Ok(Item::Value(Value::Integer(value)))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Item, E> {
+        i64::try_from(value)
+            .map(|value| Item::Value(Value::Integer(value)))
+            .map_err(|_| E::custom(format!("{} does not fit in a TOML integer", value)))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Item, E> {
+        Ok(Item::Value(Value::Float(value)))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Item, E> {
+        Ok(Item::Value(Value::String(value.to_string())))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Item, E> {
+        Ok(Item::Value(Value::String(value)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Item, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element::<Spanned<Item>>()? {
+            items.push(item);
+        }
+        Ok(Item::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Item, A::Error> {
+        let mut table = vec![];
+        while let Some(key) = map.next_key::<Spanned<String>>()? {
+            if key.get_ref() == DATETIME_KEY {
+                let datetime = map.next_value::<String>()?;
+                return datetime.parse()
+                    .map(|datetime| Item::Value(Value::Datetime(datetime)))
+                    .map_err(serde::de::Error::custom);
+            }
+            table.push((key, map.next_value::<Spanned<Item>>()?));
+        }
+        Ok(Item::Table(table))
+    }
+}
@@ -1,13 +1,73 @@
+use std::fmt;
+
 use serde::Serialize;
 use toml::Value;
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Clone)]
+/// A processor architecture, written as in EDK2 (`X64`) or as a Rust `target_arch` (`x86_64`).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Clone)]
 pub enum Architecture {
     Common,
+    /// `IA32`, or `x86`.
+    Ia32,
+    /// `X64`, or `x86_64`.
     X64,
+    /// `ARM`, or `arm`.
+    Arm,
+    /// `AARCH64`, or `aarch64`.
+    Aarch64,
+    /// `RISCV64`, or `riscv64`.
+    RiscV64,
+    /// `LOONGARCH64`, or `loongarch64`.
+    LoongArch64,
     Custom(String)
 }
 
+impl Architecture {
+    /// The architecture of a target triple, such as `x86_64-unknown-uefi`, or of a Rust `target_arch`.
+    pub fn from_target(target: &str) -> Architecture {
+        let arch = target.split('-').next().unwrap_or(target).to_lowercase();
+        match arch.as_str() {
+            "x86" | "i386" | "i586" | "i686" => Architecture::Ia32,
+            "x86_64" => Architecture::X64,
+            "aarch64" | "arm64" => Architecture::Aarch64,
+            "loongarch64" => Architecture::LoongArch64,
+            arch if arch.starts_with("riscv64") => Architecture::RiscV64,
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => Architecture::Arm,
+            arch => Architecture::Custom(arch.to_string()),
+        }
+    }
+
+    /// The Rust `target_arch` of the architecture, which `#[cfg(target_arch = ...)]` tests, or `None` for
+    /// `Common`.
+    pub fn target_arch(&self) -> Option<&str> {
+        match self {
+            Architecture::Common => None,
+            Architecture::Ia32 => Some("x86"),
+            Architecture::X64 => Some("x86_64"),
+            Architecture::Arm => Some("arm"),
+            Architecture::Aarch64 => Some("aarch64"),
+            Architecture::RiscV64 => Some("riscv64"),
+            Architecture::LoongArch64 => Some("loongarch64"),
+            Architecture::Custom(arch) => Some(arch),
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Architecture::Common => f.write_str("COMMON"),
+            Architecture::Ia32 => f.write_str("IA32"),
+            Architecture::X64 => f.write_str("X64"),
+            Architecture::Arm => f.write_str("ARM"),
+            Architecture::Aarch64 => f.write_str("AARCH64"),
+            Architecture::RiscV64 => f.write_str("RISCV64"),
+            Architecture::LoongArch64 => f.write_str("LOONGARCH64"),
+            Architecture::Custom(arch) => f.write_str(arch),
+        }
+    }
+}
+
 impl TryFrom<&Value> for Architecture {
     type Error = String;
 
@@ -15,7 +75,12 @@ impl TryFrom<&Value> for Architecture {
         match value {
             Value::String(s) => match s.to_lowercase().as_str() {
                 "common" => Ok(Architecture::Common),
-                "x64" => Ok(Architecture::X64),
+                "ia32" | "x86" => Ok(Architecture::Ia32),
+                "x64" | "x86_64" => Ok(Architecture::X64),
+                "arm" => Ok(Architecture::Arm),
+                "aarch64" => Ok(Architecture::Aarch64),
+                "riscv64" => Ok(Architecture::RiscV64),
+                "loongarch64" => Ok(Architecture::LoongArch64),
                 v => Ok(Architecture::Custom(v.to_string())),
             },
             _ => Err(format!("Architecture must be a string, got {:?}", value)),
@@ -23,26 +88,98 @@ impl TryFrom<&Value> for Architecture {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Clone)]
+/// An EDK2 module type, written as in an INF (`DXE_DRIVER`).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Clone)]
 pub enum Module {
     Common,
     Std,
+    Sec,
+    PeiCore,
+    Peim,
+    DxeCore,
     DxeDriver,
+    DxeRuntimeDriver,
+    DxeSmmDriver,
+    MmStandalone,
+    UefiDriver,
+    UefiApplication,
+    HostApplication,
     Custom(String),
 }
 
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Module::Common => f.write_str("COMMON"),
+            Module::Std => f.write_str("STD"),
+            Module::Sec => f.write_str("SEC"),
+            Module::PeiCore => f.write_str("PEI_CORE"),
+            Module::Peim => f.write_str("PEIM"),
+            Module::DxeCore => f.write_str("DXE_CORE"),
+            Module::DxeDriver => f.write_str("DXE_DRIVER"),
+            Module::DxeRuntimeDriver => f.write_str("DXE_RUNTIME_DRIVER"),
+            Module::DxeSmmDriver => f.write_str("DXE_SMM_DRIVER"),
+            Module::MmStandalone => f.write_str("MM_STANDALONE"),
+            Module::UefiDriver => f.write_str("UEFI_DRIVER"),
+            Module::UefiApplication => f.write_str("UEFI_APPLICATION"),
+            Module::HostApplication => f.write_str("HOST_APPLICATION"),
+            Module::Custom(module) => f.write_str(module),
+        }
+    }
+}
+
+impl From<&str> for Module {
+    fn from(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "common" => Module::Common,
+            "std" => Module::Std,
+            "sec" => Module::Sec,
+            "pei_core" => Module::PeiCore,
+            "peim" => Module::Peim,
+            "dxe_core" => Module::DxeCore,
+            "dxe_driver" => Module::DxeDriver,
+            "dxe_runtime_driver" => Module::DxeRuntimeDriver,
+            "dxe_smm_driver" => Module::DxeSmmDriver,
+            "mm_standalone" => Module::MmStandalone,
+            "uefi_driver" => Module::UefiDriver,
+            "uefi_application" => Module::UefiApplication,
+            "host_application" => Module::HostApplication,
+            v => Module::Custom(v.to_string()),
+        }
+    }
+}
+
 impl TryFrom<&Value> for Module {
     type Error = String;
 
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(Module::from(s.as_str())),
+            _ => Err(format!("Module must be a string, got {:?}", value)),
+        }
+    }
+}
+
+/// How a PCD is compiled into the module, matching the EDK2 PCD section it would be listed under.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Clone)]
+pub enum PcdKind {
+    /// A constant, usable in const contexts.
+    FixedAtBuild,
+    /// A static in the image, which can be patched in the binary or set at runtime.
+    PatchableInModule,
+}
+
+impl TryFrom<&Value> for PcdKind {
+    type Error = String;
+
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         match value {
             Value::String(s) => match s.to_lowercase().as_str() {
-                "common" => Ok(Module::Common),
-                "std" => Ok(Module::Std),
-                "dxe_driver" => Ok(Module::DxeDriver),
-                v => Ok(Module::Custom(v.to_string())),
+                "fixedatbuild" | "fixed_at_build" => Ok(PcdKind::FixedAtBuild),
+                "patchableinmodule" | "patchable_in_module" => Ok(PcdKind::PatchableInModule),
+                v => Err(format!("Unknown PCD kind {}, expected FixedAtBuild or PatchableInModule", v)),
             },
-            _ => Err(format!("Module must be a string, got {:?}", value)),
+            _ => Err(format!("PCD kind must be a string, got {:?}", value)),
         }
     }
-}
\ No newline at end of file
+}
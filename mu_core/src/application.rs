@@ -0,0 +1,44 @@
+//! Components built as UEFI applications.
+//!
+//! Unlike a driver, an application never stays resident: the firmware unloads it as soon as the entry
+//! point returns, and the status it returns is handed to whoever started it, e.g. the shell.
+use r_efi::efi;
+
+use crate::{
+    error::{self, EntryResult},
+    ImageHandle, SystemTable,
+};
+
+pub trait ApplicationComponent {
+    fn main(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> EntryResult;
+
+    fn init(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> error::Result<()>;
+
+    /// Validates the arguments passed to the image entry point and runs the application.
+    ///
+    /// The result of [`ApplicationComponent::main`] is the exit status of the application. As the image is
+    /// freed once it returns, the logger is flushed and outstanding allocations are reported first.
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or point to a system table that stays valid while the application runs.
+    /// See [`SystemTable::from_ptr`].
+    unsafe fn entry_point(
+        image_handle: efi::Handle,
+        system_table: *mut efi::SystemTable,
+    ) -> EntryResult {
+        let image_handle = ImageHandle::new(image_handle)?;
+        let system_table = SystemTable::from_ptr(system_table)?;
+        crate::init_image(image_handle, system_table);
+
+        let result = Self::init(image_handle, system_table).and_then(|()| Self::main(image_handle, system_table));
+        crate::image::report_leaks();
+        result
+    }
+}
@@ -0,0 +1,424 @@
+//! Components that follow the UEFI Driver Model.
+//!
+//! A [`DriverBindingComponent`] does not do its work in its entry point. Instead, the entry point installs
+//! an EFI_DRIVER_BINDING_PROTOCOL (and, if the driver has a name, an EFI_COMPONENT_NAME2_PROTOCOL) on the
+//! image handle, and the firmware calls back into [`DriverBindingComponent::supported`],
+//! [`DriverBindingComponent::start`] and [`DriverBindingComponent::stop`] as controllers are connected and
+//! disconnected.
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::slice;
+
+use r_efi::{
+    efi,
+    protocols::{device_path, driver_binding},
+};
+
+use crate::{
+    error::{self, EfiError, EntryResult, Result},
+    ImageHandle, Protocol, SystemTable,
+};
+
+/// A handle of a controller the driver is asked to manage.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerHandle(efi::Handle);
+
+impl ControllerHandle {
+    /// Wraps a controller handle passed in by the firmware.
+    ///
+    /// Returns [`EfiError::InvalidParameter`] if the handle is null.
+    pub fn new(handle: efi::Handle) -> Result<Self> {
+        if handle.is_null() {
+            return Err(EfiError::InvalidParameter);
+        }
+        Ok(ControllerHandle(handle))
+    }
+
+    /// Returns the raw handle, for passing back to firmware services.
+    pub fn as_raw(&self) -> efi::Handle {
+        self.0
+    }
+}
+
+pub trait DriverBindingComponent {
+    /// The version placed in the Driver Binding Protocol. Higher versions take precedence when several
+    /// drivers support the same controller.
+    const VERSION: u32 = 0x10;
+
+    /// The English name reported through the Component Name 2 Protocol. The protocol is only installed
+    /// when a name is given.
+    const DRIVER_NAME: Option<&'static str> = None;
+
+    fn init(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> Result<()>;
+
+    /// Tests whether the driver can manage `controller`. Return [`EfiError::Unsupported`] if it cannot.
+    fn supported(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        remaining_device_path: Option<&device_path::Protocol>,
+    ) -> Result<()>;
+
+    /// Starts managing `controller`.
+    fn start(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        remaining_device_path: Option<&device_path::Protocol>,
+    ) -> Result<()>;
+
+    /// Stops managing `controller`, destroying `children` if the driver is a bus driver.
+    fn stop(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        children: &[ControllerHandle],
+    ) -> Result<()>;
+
+    /// Validates the arguments passed to the image entry point, initializes the component and installs
+    /// its Driver Binding Protocol on the image handle.
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or point to a system table that stays valid while the image is loaded.
+    /// See [`SystemTable::from_ptr`].
+    unsafe fn entry_point(
+        image_handle: efi::Handle,
+        system_table: *mut efi::SystemTable,
+    ) -> EntryResult {
+        let image_handle = ImageHandle::new(image_handle)?;
+        let system_table = SystemTable::from_ptr(system_table)?;
+        crate::init_image(image_handle, system_table);
+
+        Self::init(image_handle, system_table)?;
+        install::<Self>(image_handle, system_table)?;
+        Ok(None)
+    }
+}
+
+/// The Driver Binding Protocol, along with the system table its callbacks hand to the component.
+#[repr(C)]
+struct DriverBindingInstance {
+    protocol: driver_binding::Protocol,
+    system_table: SystemTable<'static>,
+}
+
+// SAFETY: the instance starts with the Driver Binding Protocol.
+unsafe impl Protocol for DriverBindingInstance {
+    const GUID: efi::Guid = driver_binding::PROTOCOL_GUID;
+}
+
+/// Installs the Driver Binding Protocol, and the Component Name 2 Protocol if the driver has a name.
+fn install<C: DriverBindingComponent + ?Sized>(image_handle: ImageHandle, system_table: SystemTable<'static>) -> Result<()> {
+    let boot_services = system_table.boot_services().ok_or(EfiError::Unsupported)?;
+
+    let instance = Box::leak(Box::new(DriverBindingInstance {
+        protocol: driver_binding::Protocol {
+            supported: supported::<C>,
+            start: start::<C>,
+            stop: stop::<C>,
+            version: C::VERSION,
+            image_handle: image_handle.as_raw(),
+            driver_binding_handle: image_handle.as_raw(),
+        },
+        system_table,
+    }));
+    boot_services.install_protocol_interface(image_handle, instance)?;
+
+    if let Some(name) = C::DRIVER_NAME {
+        boot_services.install_protocol_interface(image_handle, Box::leak(Box::new(component_name2::Instance::new(name))))?;
+    }
+    Ok(())
+}
+
+/// Recovers the image handle and system table from the protocol instance passed to a callback.
+///
+/// # Safety
+///
+/// `this` must be the protocol installed by [`install`].
+unsafe fn context(this: *mut driver_binding::Protocol) -> Result<(ImageHandle, SystemTable<'static>)> {
+    let instance = (this as *mut DriverBindingInstance).as_ref().ok_or(EfiError::InvalidParameter)?;
+    Ok((ImageHandle::new(instance.protocol.image_handle)?, instance.system_table))
+}
+
+extern "efiapi" fn supported<C: DriverBindingComponent + ?Sized>(
+    this: *mut driver_binding::Protocol,
+    controller: efi::Handle,
+    remaining_device_path: *mut device_path::Protocol,
+) -> efi::Status {
+    // SAFETY: the firmware passes back the protocol we installed, and a device path that is null or valid.
+    let result = unsafe { context(this) }.and_then(|(image_handle, system_table)| {
+        C::supported(image_handle, system_table, ControllerHandle::new(controller)?, unsafe {
+            remaining_device_path.as_ref()
+        })
+    });
+    error::to_status(result.map(|()| None))
+}
+
+extern "efiapi" fn start<C: DriverBindingComponent + ?Sized>(
+    this: *mut driver_binding::Protocol,
+    controller: efi::Handle,
+    remaining_device_path: *mut device_path::Protocol,
+) -> efi::Status {
+    // SAFETY: the firmware passes back the protocol we installed, and a device path that is null or valid.
+    let result = unsafe { context(this) }.and_then(|(image_handle, system_table)| {
+        C::start(image_handle, system_table, ControllerHandle::new(controller)?, unsafe {
+            remaining_device_path.as_ref()
+        })
+    });
+    error::to_status(result.map(|()| None))
+}
+
+extern "efiapi" fn stop<C: DriverBindingComponent + ?Sized>(
+    this: *mut driver_binding::Protocol,
+    controller: efi::Handle,
+    number_of_children: usize,
+    child_handle_buffer: *mut efi::Handle,
+) -> efi::Status {
+    let children: &[ControllerHandle] = if number_of_children == 0 || child_handle_buffer.is_null() {
+        &[]
+    } else {
+        // SAFETY: the firmware passes `number_of_children` valid handles, and `ControllerHandle` is a
+        // transparent wrapper around `efi::Handle`.
+        unsafe { slice::from_raw_parts(child_handle_buffer as *const ControllerHandle, number_of_children) }
+    };
+    if children.iter().any(|child| child.as_raw().is_null()) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: the firmware passes back the protocol we installed.
+    let result = unsafe { context(this) }.and_then(|(image_handle, system_table)| {
+        C::stop(image_handle, system_table, ControllerHandle::new(controller)?, children)
+    });
+    error::to_status(result.map(|()| None))
+}
+
+/// The EFI_COMPONENT_NAME2_PROTOCOL, which r-efi does not define.
+mod component_name2 {
+    use super::*;
+
+    pub const PROTOCOL_GUID: efi::Guid =
+        efi::Guid::from_fields(0x6a7a5cff, 0xe8d9, 0x4f70, 0xba, 0xda, &[0x75, 0xab, 0x30, 0x25, 0xce, 0x14]);
+
+    /// The only language the driver name is provided in, as a null terminated RFC 4646 language code.
+    const SUPPORTED_LANGUAGES: &[u8] = b"en\0";
+
+    pub type ProtocolGetDriverName = extern "efiapi" fn(*mut Protocol, *mut efi::Char8, *mut *mut efi::Char16) -> efi::Status;
+
+    pub type ProtocolGetControllerName = extern "efiapi" fn(
+        *mut Protocol,
+        efi::Handle,
+        efi::Handle,
+        *mut efi::Char8,
+        *mut *mut efi::Char16,
+    ) -> efi::Status;
+
+    #[repr(C)]
+    pub struct Protocol {
+        pub get_driver_name: ProtocolGetDriverName,
+        pub get_controller_name: ProtocolGetControllerName,
+        pub supported_languages: *mut efi::Char8,
+    }
+
+    /// The Component Name 2 Protocol, along with the null terminated UCS-2 driver name.
+    #[repr(C)]
+    pub struct Instance {
+        protocol: Protocol,
+        driver_name: Vec<efi::Char16>,
+    }
+
+    // SAFETY: the instance starts with the Component Name 2 Protocol.
+    unsafe impl crate::Protocol for Instance {
+        const GUID: efi::Guid = PROTOCOL_GUID;
+    }
+
+    impl Instance {
+        pub fn new(name: &str) -> Self {
+            Instance {
+                protocol: Protocol {
+                    get_driver_name,
+                    get_controller_name,
+                    supported_languages: SUPPORTED_LANGUAGES.as_ptr() as *mut efi::Char8,
+                },
+                driver_name: name.encode_utf16().chain([0]).collect(),
+            }
+        }
+    }
+
+    /// Returns true if the null terminated `language` is one of the supported languages.
+    ///
+    /// # Safety
+    ///
+    /// `language` must be a valid null terminated ASCII string.
+    unsafe fn is_supported(language: *const efi::Char8) -> bool {
+        let supported = &SUPPORTED_LANGUAGES[..SUPPORTED_LANGUAGES.len() - 1];
+        supported.iter().enumerate().all(|(i, c)| *language.add(i) == *c) && *language.add(supported.len()) == 0
+    }
+
+    extern "efiapi" fn get_driver_name(
+        this: *mut Protocol,
+        language: *mut efi::Char8,
+        driver_name: *mut *mut efi::Char16,
+    ) -> efi::Status {
+        // SAFETY: the firmware passes back the protocol we installed, which is the start of an `Instance`.
+        let Some(instance) = (unsafe { (this as *mut Instance).as_ref() }) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        if language.is_null() || driver_name.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        // SAFETY: the pointers were checked for null, and the caller provides a valid language string.
+        unsafe {
+            if !is_supported(language) {
+                return efi::Status::UNSUPPORTED;
+            }
+            driver_name.write(instance.driver_name.as_ptr() as *mut efi::Char16);
+        }
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn get_controller_name(
+        _this: *mut Protocol,
+        _controller: efi::Handle,
+        _child: efi::Handle,
+        _language: *mut efi::Char8,
+        _controller_name: *mut *mut efi::Char16,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{
+        calculate_crc32,
+        tests::{mock_boot_services, mock_system_table},
+    };
+    use core::{
+        ffi::c_void,
+        ptr,
+        sync::atomic::{AtomicPtr, Ordering},
+    };
+
+    /// Records the interfaces installed on the mocked image handle, which points at this structure.
+    #[derive(Default)]
+    struct MockImage {
+        protocols: Vec<(efi::Guid, *mut c_void)>,
+    }
+
+    impl MockImage {
+        fn get(&self, guid: &efi::Guid) -> Option<*mut c_void> {
+            self.protocols.iter().find(|(g, _)| g == guid).map(|(_, interface)| *interface)
+        }
+    }
+
+    extern "efiapi" fn mock_install_protocol_interface(
+        handle: *mut efi::Handle,
+        guid: *mut efi::Guid,
+        interface_type: efi::InterfaceType,
+        interface: *mut c_void,
+    ) -> efi::Status {
+        assert_eq!(interface_type, efi::NATIVE_INTERFACE);
+        let image = unsafe { (*handle as *mut MockImage).as_mut() }.unwrap();
+        image.protocols.push((unsafe { *guid }, interface));
+        efi::Status::SUCCESS
+    }
+
+    static STARTED: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+    static STOPPED: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    struct MockDriver;
+
+    impl DriverBindingComponent for MockDriver {
+        const VERSION: u32 = 0x42;
+        const DRIVER_NAME: Option<&'static str> = Some("Mock Driver");
+
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            Ok(())
+        }
+
+        fn supported(_: ImageHandle, _: SystemTable, _: ControllerHandle, path: Option<&device_path::Protocol>) -> Result<()> {
+            match path {
+                Some(_) => Ok(()),
+                None => Err(EfiError::Unsupported),
+            }
+        }
+
+        fn start(_: ImageHandle, st: SystemTable, controller: ControllerHandle, _: Option<&device_path::Protocol>) -> Result<()> {
+            assert!(st.boot_services().is_some());
+            STARTED.store(controller.as_raw(), Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn stop(_: ImageHandle, _: SystemTable, controller: ControllerHandle, children: &[ControllerHandle]) -> Result<()> {
+            assert_eq!(children.len(), 2);
+            STOPPED.store(controller.as_raw(), Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_driver_binding_is_installed() {
+        let mut bs = mock_boot_services();
+        bs.install_protocol_interface = mock_install_protocol_interface;
+        bs.hdr.crc32 = unsafe { calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let mut image = MockImage::default();
+        let image_handle = &mut image as *mut MockImage as efi::Handle;
+
+        assert_eq!(unsafe { MockDriver::entry_point(image_handle, &mut st) }, Ok(None));
+
+        let binding = image.get(&driver_binding::PROTOCOL_GUID).unwrap() as *mut driver_binding::Protocol;
+        let protocol = unsafe { binding.as_ref() }.unwrap();
+        assert_eq!(protocol.version, 0x42);
+        assert_eq!(protocol.image_handle, image_handle);
+        assert_eq!(protocol.driver_binding_handle, image_handle);
+
+        let mut controller = 0u8;
+        let controller = &mut controller as *mut u8 as efi::Handle;
+        let mut path = device_path::Protocol { r#type: device_path::TYPE_END, sub_type: 0xFF, length: [4, 0] };
+
+        assert_eq!((protocol.supported)(binding, controller, ptr::null_mut()), efi::Status::UNSUPPORTED);
+        assert_eq!((protocol.supported)(binding, controller, &mut path), efi::Status::SUCCESS);
+        assert_eq!((protocol.supported)(binding, ptr::null_mut(), &mut path), efi::Status::INVALID_PARAMETER);
+
+        assert_eq!((protocol.start)(binding, controller, ptr::null_mut()), efi::Status::SUCCESS);
+        assert_eq!(STARTED.load(Ordering::SeqCst), controller);
+
+        let mut children = [controller, controller];
+        assert_eq!((protocol.stop)(binding, controller, children.len(), children.as_mut_ptr()), efi::Status::SUCCESS);
+        assert_eq!(STOPPED.load(Ordering::SeqCst), controller);
+    }
+
+    #[test]
+    fn test_component_name_is_installed() {
+        let mut bs = mock_boot_services();
+        bs.install_protocol_interface = mock_install_protocol_interface;
+        bs.hdr.crc32 = unsafe { calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let mut image = MockImage::default();
+        let image_handle = &mut image as *mut MockImage as efi::Handle;
+
+        assert_eq!(unsafe { MockDriver::entry_point(image_handle, &mut st) }, Ok(None));
+
+        let name = image.get(&component_name2::PROTOCOL_GUID).unwrap() as *mut component_name2::Protocol;
+        let protocol = unsafe { name.as_ref() }.unwrap();
+
+        let mut driver_name: *mut efi::Char16 = ptr::null_mut();
+        let status = (protocol.get_driver_name)(name, b"en\0".as_ptr() as *mut efi::Char8, &mut driver_name);
+        assert_eq!(status, efi::Status::SUCCESS);
+        let driver_name = unsafe { slice::from_raw_parts(driver_name, 12) };
+        assert_eq!(driver_name, "Mock Driver\0".encode_utf16().collect::<Vec<_>>().as_slice());
+
+        let status = (protocol.get_driver_name)(name, b"fr\0".as_ptr() as *mut efi::Char8, &mut ptr::null_mut());
+        assert_eq!(status, efi::Status::UNSUPPORTED);
+        let status = (protocol.get_driver_name)(name, b"en-US\0".as_ptr() as *mut efi::Char8, &mut ptr::null_mut());
+        assert_eq!(status, efi::Status::UNSUPPORTED);
+    }
+}
@@ -1,8 +1,37 @@
 pub type Result<T> = core::result::Result<T, EfiError>;
 
+/// The result of running a component.
+///
+/// `Ok(None)` is `EFI_SUCCESS`, `Ok(Some(warning))` is a successful completion that still reports a
+/// warning status, and `Err(error)` is an error status. Use [`to_status`] to convert it into the
+/// [`efi::Status`] returned from `efi_main`.
+pub type EntryResult = Result<Option<EfiWarning>>;
+
+use core::fmt;
 use r_efi::efi;
-// TODO: Handle difference between warning and error
-#[derive(Debug)]
+
+/// Converts the result of a component into the status code returned to the firmware.
+pub fn to_status(result: EntryResult) -> efi::Status {
+    match result {
+        Ok(None) => efi::Status::SUCCESS,
+        Ok(Some(warning)) => warning.into(),
+        Err(error) => error.into(),
+    }
+}
+
+/// Converts a status returned by a firmware service into a result.
+///
+/// Error statuses without an [`EfiError`] equivalent are reported as [`EfiError::DeviceError`], and
+/// warning statuses without an [`EfiWarning`] equivalent are treated as plain success.
+pub fn from_status(status: efi::Status) -> EntryResult {
+    if status.is_error() {
+        return Err(EfiError::try_from(status).unwrap_or(EfiError::DeviceError));
+    }
+    Ok(EfiWarning::try_from(status).ok())
+}
+
+/// UEFI error statuses, i.e. statuses with the high bit set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EfiError {
     LoadError,
     InvalidParameter,
@@ -37,6 +66,13 @@ pub enum EfiError {
     CompromisedData,
     IpAddressConflict,
     HttpError,
+    NetworkUnreachable,
+    HostUnreachable,
+    ProtocolUnreachable,
+    PortUnreachable,
+    ConnectionFin,
+    ConnectionReset,
+    ConnectionRefused,
 }
 
 impl From<EfiError> for efi::Status {
@@ -75,8 +111,291 @@ impl From<EfiError> for efi::Status {
             EfiError::CompromisedData => efi::Status::COMPROMISED_DATA,
             EfiError::IpAddressConflict => efi::Status::IP_ADDRESS_CONFLICT,
             EfiError::HttpError => efi::Status::HTTP_ERROR,
+            EfiError::NetworkUnreachable => efi::Status::NETWORK_UNREACHABLE,
+            EfiError::HostUnreachable => efi::Status::HOST_UNREACHABLE,
+            EfiError::ProtocolUnreachable => efi::Status::PROTOCOL_UNREACHABLE,
+            EfiError::PortUnreachable => efi::Status::PORT_UNREACHABLE,
+            EfiError::ConnectionFin => efi::Status::CONNECTION_FIN,
+            EfiError::ConnectionReset => efi::Status::CONNECTION_RESET,
+            EfiError::ConnectionRefused => efi::Status::CONNECTION_REFUSED,
+        }
+    }
+}
+
+impl TryFrom<efi::Status> for EfiError {
+    /// The original status, if it is not an error status.
+    type Error = efi::Status;
+
+    fn try_from(status: efi::Status) -> core::result::Result<Self, Self::Error> {
+        match status {
+            efi::Status::LOAD_ERROR => Ok(EfiError::LoadError),
+            efi::Status::INVALID_PARAMETER => Ok(EfiError::InvalidParameter),
+            efi::Status::UNSUPPORTED => Ok(EfiError::Unsupported),
+            efi::Status::BAD_BUFFER_SIZE => Ok(EfiError::BadBufferSize),
+            efi::Status::BUFFER_TOO_SMALL => Ok(EfiError::BufferTooSmall),
+            efi::Status::NOT_READY => Ok(EfiError::NotReady),
+            efi::Status::DEVICE_ERROR => Ok(EfiError::DeviceError),
+            efi::Status::WRITE_PROTECTED => Ok(EfiError::WriteProtected),
+            efi::Status::OUT_OF_RESOURCES => Ok(EfiError::OutOfResources),
+            efi::Status::VOLUME_CORRUPTED => Ok(EfiError::VolumeCorrupted),
+            efi::Status::VOLUME_FULL => Ok(EfiError::VolumeFull),
+            efi::Status::NO_MEDIA => Ok(EfiError::NoMedia),
+            efi::Status::MEDIA_CHANGED => Ok(EfiError::MediaChanged),
+            efi::Status::NOT_FOUND => Ok(EfiError::NotFound),
+            efi::Status::ACCESS_DENIED => Ok(EfiError::AccessDenied),
+            efi::Status::NO_RESPONSE => Ok(EfiError::NoResponse),
+            efi::Status::NO_MAPPING => Ok(EfiError::NoMapping),
+            efi::Status::TIMEOUT => Ok(EfiError::Timeout),
+            efi::Status::NOT_STARTED => Ok(EfiError::NotStarted),
+            efi::Status::ALREADY_STARTED => Ok(EfiError::AlreadyStarted),
+            efi::Status::ABORTED => Ok(EfiError::Aborted),
+            efi::Status::ICMP_ERROR => Ok(EfiError::IcmpError),
+            efi::Status::TFTP_ERROR => Ok(EfiError::TftpError),
+            efi::Status::PROTOCOL_ERROR => Ok(EfiError::ProtocolError),
+            efi::Status::INCOMPATIBLE_VERSION => Ok(EfiError::IncompatibleError),
+            efi::Status::SECURITY_VIOLATION => Ok(EfiError::SecurityViolation),
+            efi::Status::CRC_ERROR => Ok(EfiError::CrcError),
+            efi::Status::END_OF_MEDIA => Ok(EfiError::EndOfMedia),
+            efi::Status::END_OF_FILE => Ok(EfiError::EndOfFile),
+            efi::Status::INVALID_LANGUAGE => Ok(EfiError::InvalidLanguage),
+            efi::Status::COMPROMISED_DATA => Ok(EfiError::CompromisedData),
+            efi::Status::IP_ADDRESS_CONFLICT => Ok(EfiError::IpAddressConflict),
+            efi::Status::HTTP_ERROR => Ok(EfiError::HttpError),
+            efi::Status::NETWORK_UNREACHABLE => Ok(EfiError::NetworkUnreachable),
+            efi::Status::HOST_UNREACHABLE => Ok(EfiError::HostUnreachable),
+            efi::Status::PROTOCOL_UNREACHABLE => Ok(EfiError::ProtocolUnreachable),
+            efi::Status::PORT_UNREACHABLE => Ok(EfiError::PortUnreachable),
+            efi::Status::CONNECTION_FIN => Ok(EfiError::ConnectionFin),
+            efi::Status::CONNECTION_RESET => Ok(EfiError::ConnectionReset),
+            efi::Status::CONNECTION_REFUSED => Ok(EfiError::ConnectionRefused),
+            status => Err(status),
         }
     }
 }
 
+impl fmt::Display for EfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            EfiError::LoadError => "The image failed to load",
+            EfiError::InvalidParameter => "A parameter was incorrect",
+            EfiError::Unsupported => "The operation is not supported",
+            EfiError::BadBufferSize => "The buffer was not the proper size for the request",
+            EfiError::BufferTooSmall => "The buffer is not large enough to hold the requested data",
+            EfiError::NotReady => "There is no data pending upon return",
+            EfiError::DeviceError => "The physical device reported an error while attempting the operation",
+            EfiError::WriteProtected => "The device cannot be written to",
+            EfiError::OutOfResources => "A resource has run out",
+            EfiError::VolumeCorrupted => "An inconsistency was detected on the file system",
+            EfiError::VolumeFull => "There is no more space on the file system",
+            EfiError::NoMedia => "The device does not contain any medium to perform the operation",
+            EfiError::MediaChanged => "The medium in the device has changed since the last access",
+            EfiError::NotFound => "The item was not found",
+            EfiError::AccessDenied => "Access was denied",
+            EfiError::NoResponse => "The server was not found or did not respond to the request",
+            EfiError::NoMapping => "A mapping to a device does not exist",
+            EfiError::Timeout => "The timeout time expired",
+            EfiError::NotStarted => "The protocol has not been started",
+            EfiError::AlreadyStarted => "The protocol has already been started",
+            EfiError::Aborted => "The operation was aborted",
+            EfiError::IcmpError => "An ICMP error occurred during the network operation",
+            EfiError::TftpError => "A TFTP error occurred during the network operation",
+            EfiError::ProtocolError => "A protocol error occurred during the network operation",
+            EfiError::IncompatibleError => "The function encountered an internal version that was incompatible",
+            EfiError::SecurityViolation => "The function was not performed due to a security violation",
+            EfiError::CrcError => "A CRC error was detected",
+            EfiError::EndOfMedia => "Beginning or end of media was reached",
+            EfiError::EndOfFile => "The end of the file was reached",
+            EfiError::InvalidLanguage => "The language specified was invalid",
+            EfiError::CompromisedData => "The security status of the data is unknown or compromised",
+            EfiError::IpAddressConflict => "There is an address conflict during address allocation",
+            EfiError::HttpError => "A HTTP error occurred during the network operation",
+            EfiError::NetworkUnreachable => "The network is unreachable",
+            EfiError::HostUnreachable => "The host is unreachable",
+            EfiError::ProtocolUnreachable => "The protocol is unreachable",
+            EfiError::PortUnreachable => "The port is unreachable",
+            EfiError::ConnectionFin => "The connection was closed by the remote host",
+            EfiError::ConnectionReset => "The connection was reset by the remote host",
+            EfiError::ConnectionRefused => "The connection was refused by the remote host",
+        };
+        f.write_str(msg)
+    }
+}
 
+#[cfg(feature = "std")]
+impl std::error::Error for EfiError {}
+#[cfg(not(feature = "std"))]
+impl core::error::Error for EfiError {}
+
+/// UEFI warning statuses, i.e. successful completions that still report a non-zero status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EfiWarning {
+    UnknownGlyph,
+    DeleteFailure,
+    WriteFailure,
+    BufferTooSmall,
+    StaleData,
+    FileSystem,
+    ResetRequired,
+}
+
+impl From<EfiWarning> for efi::Status {
+    fn from(w: EfiWarning) -> efi::Status {
+        match w {
+            EfiWarning::UnknownGlyph => efi::Status::WARN_UNKNOWN_GLYPH,
+            EfiWarning::DeleteFailure => efi::Status::WARN_DELETE_FAILURE,
+            EfiWarning::WriteFailure => efi::Status::WARN_WRITE_FAILURE,
+            EfiWarning::BufferTooSmall => efi::Status::WARN_BUFFER_TOO_SMALL,
+            EfiWarning::StaleData => efi::Status::WARN_STALE_DATA,
+            EfiWarning::FileSystem => efi::Status::WARN_FILE_SYSTEM,
+            EfiWarning::ResetRequired => efi::Status::WARN_RESET_REQUIRED,
+        }
+    }
+}
+
+impl TryFrom<efi::Status> for EfiWarning {
+    /// The original status, if it is not a warning status.
+    type Error = efi::Status;
+
+    fn try_from(status: efi::Status) -> core::result::Result<Self, Self::Error> {
+        match status {
+            efi::Status::WARN_UNKNOWN_GLYPH => Ok(EfiWarning::UnknownGlyph),
+            efi::Status::WARN_DELETE_FAILURE => Ok(EfiWarning::DeleteFailure),
+            efi::Status::WARN_WRITE_FAILURE => Ok(EfiWarning::WriteFailure),
+            efi::Status::WARN_BUFFER_TOO_SMALL => Ok(EfiWarning::BufferTooSmall),
+            efi::Status::WARN_STALE_DATA => Ok(EfiWarning::StaleData),
+            efi::Status::WARN_FILE_SYSTEM => Ok(EfiWarning::FileSystem),
+            efi::Status::WARN_RESET_REQUIRED => Ok(EfiWarning::ResetRequired),
+            status => Err(status),
+        }
+    }
+}
+
+impl fmt::Display for EfiWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            EfiWarning::UnknownGlyph => "The string contained one or more characters that could not be rendered",
+            EfiWarning::DeleteFailure => "The handle was closed, but the file was not deleted",
+            EfiWarning::WriteFailure => "The handle was closed, but the data to the file was not flushed properly",
+            EfiWarning::BufferTooSmall => "The resulting buffer was too small, and the data was truncated",
+            EfiWarning::StaleData => "The data has not been updated within the timeframe set by local policy",
+            EfiWarning::FileSystem => "The resulting buffer contains a UEFI-compliant file system",
+            EfiWarning::ResetRequired => "The operation will be processed across a system reset",
+        };
+        f.write_str(msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EfiWarning {}
+#[cfg(not(feature = "std"))]
+impl core::error::Error for EfiWarning {}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::*;
+    use alloc::string::ToString;
+
+    const ERRORS: [efi::Status; 40] = [
+        efi::Status::LOAD_ERROR,
+        efi::Status::INVALID_PARAMETER,
+        efi::Status::UNSUPPORTED,
+        efi::Status::BAD_BUFFER_SIZE,
+        efi::Status::BUFFER_TOO_SMALL,
+        efi::Status::NOT_READY,
+        efi::Status::DEVICE_ERROR,
+        efi::Status::WRITE_PROTECTED,
+        efi::Status::OUT_OF_RESOURCES,
+        efi::Status::VOLUME_CORRUPTED,
+        efi::Status::VOLUME_FULL,
+        efi::Status::NO_MEDIA,
+        efi::Status::MEDIA_CHANGED,
+        efi::Status::NOT_FOUND,
+        efi::Status::ACCESS_DENIED,
+        efi::Status::NO_RESPONSE,
+        efi::Status::NO_MAPPING,
+        efi::Status::TIMEOUT,
+        efi::Status::NOT_STARTED,
+        efi::Status::ALREADY_STARTED,
+        efi::Status::ABORTED,
+        efi::Status::ICMP_ERROR,
+        efi::Status::TFTP_ERROR,
+        efi::Status::PROTOCOL_ERROR,
+        efi::Status::INCOMPATIBLE_VERSION,
+        efi::Status::SECURITY_VIOLATION,
+        efi::Status::CRC_ERROR,
+        efi::Status::END_OF_MEDIA,
+        efi::Status::END_OF_FILE,
+        efi::Status::INVALID_LANGUAGE,
+        efi::Status::COMPROMISED_DATA,
+        efi::Status::IP_ADDRESS_CONFLICT,
+        efi::Status::HTTP_ERROR,
+        efi::Status::NETWORK_UNREACHABLE,
+        efi::Status::HOST_UNREACHABLE,
+        efi::Status::PROTOCOL_UNREACHABLE,
+        efi::Status::PORT_UNREACHABLE,
+        efi::Status::CONNECTION_FIN,
+        efi::Status::CONNECTION_RESET,
+        efi::Status::CONNECTION_REFUSED,
+    ];
+
+    const WARNINGS: [efi::Status; 7] = [
+        efi::Status::WARN_UNKNOWN_GLYPH,
+        efi::Status::WARN_DELETE_FAILURE,
+        efi::Status::WARN_WRITE_FAILURE,
+        efi::Status::WARN_BUFFER_TOO_SMALL,
+        efi::Status::WARN_STALE_DATA,
+        efi::Status::WARN_FILE_SYSTEM,
+        efi::Status::WARN_RESET_REQUIRED,
+    ];
+
+    #[test]
+    fn test_error_round_trip() {
+        for status in ERRORS {
+            assert!(status.is_error());
+            let error = EfiError::try_from(status).unwrap();
+            assert_eq!(efi::Status::from(error), status);
+            assert_eq!(EfiWarning::try_from(status), Err(status));
+        }
+    }
+
+    #[test]
+    fn test_warning_round_trip() {
+        for status in WARNINGS {
+            assert!(status.is_warning());
+            let warning = EfiWarning::try_from(status).unwrap();
+            assert_eq!(efi::Status::from(warning), status);
+            assert_eq!(EfiError::try_from(status), Err(status));
+        }
+    }
+
+    #[test]
+    fn test_success_is_neither() {
+        assert_eq!(EfiError::try_from(efi::Status::SUCCESS), Err(efi::Status::SUCCESS));
+        assert_eq!(EfiWarning::try_from(efi::Status::SUCCESS), Err(efi::Status::SUCCESS));
+    }
+
+    #[test]
+    fn test_to_status() {
+        assert_eq!(to_status(Ok(None)), efi::Status::SUCCESS);
+        assert_eq!(to_status(Ok(Some(EfiWarning::StaleData))), efi::Status::WARN_STALE_DATA);
+        assert_eq!(to_status(Err(EfiError::Aborted)), efi::Status::ABORTED);
+    }
+
+    #[test]
+    fn test_from_status() {
+        for status in ERRORS.iter().chain(WARNINGS.iter()).chain([efi::Status::SUCCESS].iter()) {
+            assert_eq!(to_status(from_status(*status)), *status);
+        }
+        // Statuses this crate does not know about.
+        assert_eq!(from_status(efi::Status::from_usize(0x1000)), Ok(None));
+        assert_eq!(from_status(efi::Status::from_usize(0x1000 | 1 << (usize::BITS - 1))), Err(EfiError::DeviceError));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(EfiError::NotFound.to_string(), "The item was not found");
+        assert_eq!(EfiWarning::UnknownGlyph.to_string(), "The string contained one or more characters that could not be rendered");
+    }
+}
@@ -0,0 +1,196 @@
+//! Support for unloading images built from a [`Component`].
+use r_efi::{efi, protocols::loaded_image};
+
+use crate::{
+    error::{EfiError, Result},
+    Component, ImageHandle, SystemTable,
+};
+
+/// Installs [`Component::unload`] as the unload handler in the image's Loaded Image Protocol.
+pub(crate) fn install_unload_handler<C: Component + ?Sized>(
+    image_handle: ImageHandle,
+    system_table: SystemTable,
+) -> Result<()> {
+    let boot_services = system_table.boot_services().ok_or(EfiError::Unsupported)?;
+    // SAFETY: the Loaded Image Protocol of our own image stays installed while the image is loaded, and
+    // nothing else accesses it while the entry point sets its unload handler.
+    let loaded_image = unsafe { boot_services.handle_protocol::<loaded_image::Protocol>(image_handle) }?;
+    loaded_image.unload = unload_handler::<C>;
+    Ok(())
+}
+
+/// The `Unload` function called by the firmware's `UnloadImage` service.
+///
+/// Runs the component's teardown and, once it has succeeded, reports any allocations the image still
+/// owns. The allocator was the first thing initialized by the entry point, so it is the last thing
+/// torn down.
+extern "efiapi" fn unload_handler<C: Component + ?Sized>(image_handle: efi::Handle) -> efi::Status {
+    let result = ImageHandle::new(image_handle).and_then(C::unload);
+    if result.is_ok() {
+        report_leaks();
+    }
+
+    match result {
+        Ok(()) => efi::Status::SUCCESS,
+        Err(e) => e.into(),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn report_leaks() {
+    let outstanding = rust_boot_services_allocator_dxe::GLOBAL_ALLOCATOR.outstanding_allocations();
+    if outstanding != 0 {
+        log::warn!("Image unloaded with {} allocation(s) still outstanding", outstanding);
+    }
+    log::logger().flush();
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn report_leaks() {
+    log::logger().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::EntryResult,
+        library::Constructed,
+        table::tests::{mock_boot_services, mock_system_table},
+        LibraryConstructor,
+    };
+    use core::{
+        ffi::c_void,
+        ptr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// The mocked image handle is the address of the image's Loaded Image Protocol.
+    extern "efiapi" fn mock_handle_protocol(
+        handle: efi::Handle,
+        guid: *mut efi::Guid,
+        interface: *mut *mut c_void,
+    ) -> efi::Status {
+        if unsafe { *guid } != loaded_image::PROTOCOL_GUID {
+            return efi::Status::UNSUPPORTED;
+        }
+        unsafe { interface.write(handle) };
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn mock_unload(_: efi::Handle) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    fn mock_loaded_image() -> loaded_image::Protocol {
+        loaded_image::Protocol {
+            revision: loaded_image::REVISION,
+            parent_handle: ptr::null_mut(),
+            system_table: ptr::null_mut(),
+            device_handle: ptr::null_mut(),
+            file_path: ptr::null_mut(),
+            reserved: ptr::null_mut(),
+            load_options_size: 0,
+            load_options: ptr::null_mut(),
+            image_base: ptr::null_mut(),
+            image_size: 0,
+            image_code_type: efi::LOADER_CODE,
+            image_data_type: efi::LOADER_DATA,
+            unload: mock_unload,
+        }
+    }
+
+    /// Records the order things were torn down in, as a base-16 number with one digit per step.
+    static TORN_DOWN: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(step: usize) {
+        TORN_DOWN.store(TORN_DOWN.load(Ordering::SeqCst) * 16 + step, Ordering::SeqCst);
+    }
+
+    struct LibA;
+    impl LibraryConstructor for LibA {
+        fn destructor(_: ImageHandle) -> Result<()> {
+            record(1);
+            Ok(())
+        }
+    }
+
+    struct LibB;
+    impl LibraryConstructor for LibB {
+        fn destructor(_: ImageHandle) -> Result<()> {
+            record(2);
+            Ok(())
+        }
+    }
+
+    struct UnloadableComponent;
+
+    impl Component for UnloadableComponent {
+        const UNLOADABLE: bool = true;
+
+        fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
+            Ok(None)
+        }
+
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            Ok(())
+        }
+
+        fn unload(_: ImageHandle) -> Result<()> {
+            record(3);
+            Ok(())
+        }
+    }
+
+    struct ResidentComponent;
+
+    impl Component for ResidentComponent {
+        fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
+            Ok(None)
+        }
+
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_unload_handler_is_installed() {
+        let mut bs = mock_boot_services();
+        bs.handle_protocol = mock_handle_protocol;
+        bs.hdr.crc32 = unsafe { crate::table::calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let mut loaded_image = mock_loaded_image();
+        let image_handle = &mut loaded_image as *mut loaded_image::Protocol as efi::Handle;
+
+        type Unloadable = Constructed<UnloadableComponent, (LibA, (LibB, ()))>;
+        assert_eq!(unsafe { Unloadable::entry_point(image_handle, &mut st) }, Ok(None));
+        assert_eq!(TORN_DOWN.load(Ordering::SeqCst), 0);
+
+        // The component is unloaded first, then its libraries in the reverse of their construction order.
+        assert_eq!((loaded_image.unload)(image_handle), efi::Status::SUCCESS);
+        assert_eq!(TORN_DOWN.load(Ordering::SeqCst), 0x321);
+    }
+
+    #[test]
+    fn test_resident_component_keeps_default_handler() {
+        let mut bs = mock_boot_services();
+        bs.handle_protocol = mock_handle_protocol;
+        bs.hdr.crc32 = unsafe { crate::table::calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let mut loaded_image = mock_loaded_image();
+        let image_handle = &mut loaded_image as *mut loaded_image::Protocol as efi::Handle;
+
+        assert_eq!(unsafe { ResidentComponent::entry_point(image_handle, &mut st) }, Ok(None));
+        assert_eq!((loaded_image.unload)(image_handle), efi::Status::UNSUPPORTED);
+    }
+
+    #[test]
+    fn test_unloadable_component_requires_boot_services() {
+        let mut st = mock_system_table(ptr::null_mut());
+        let mut loaded_image = mock_loaded_image();
+        let image_handle = &mut loaded_image as *mut loaded_image::Protocol as efi::Handle;
+
+        assert_eq!(unsafe { UnloadableComponent::entry_point(image_handle, &mut st) }, Err(EfiError::Unsupported));
+    }
+}
@@ -1,29 +1,105 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(feature = "std"), feature(error_in_core))]
+pub mod application;
+pub mod driver_binding;
 pub mod error;
+mod image;
+pub mod library;
+pub mod mm;
+pub mod module_type;
+pub mod panic;
+pub mod pcd;
+pub mod runtime;
+pub mod table;
 
 use r_efi::efi;
 
-pub use uefi_macro::component;
+pub use application::ApplicationComponent;
+pub use driver_binding::{ControllerHandle, DriverBindingComponent};
+pub use library::LibraryConstructor;
+pub use mm::{MmStandaloneComponent, MmSystemTable};
+pub use runtime::RuntimeComponent;
+pub use table::{BootServices, ImageHandle, Protocol, RuntimeServices, SystemTable};
+pub use uefi_macro::{component, component_from_path, entry, pcds};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use r_efi::efi;
+
+    /// Names `T` through a const argument, so that a type can carry the `include_bytes!` of the config file
+    /// it was read from, and cargo rebuilds the crate when the file changes.
+    pub trait DependsOn<const N: usize> {
+        type Type: ?Sized;
+    }
+
+    impl<T: ?Sized, const N: usize> DependsOn<N> for T {
+        type Type = T;
+    }
+}
 
 pub trait Component {
+    /// Whether the image can be unloaded.
+    ///
+    /// When `true`, `entry_point` installs [`Component::unload`] as the unload handler of the image's
+    /// Loaded Image Protocol.
+    const UNLOADABLE: bool = false;
+
     fn main(
-        image_handle: efi::Handle,
-        system_table: *mut efi::SystemTable,
-    ) -> error::Result<()>;
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> error::EntryResult;
 
     fn init(
-        image_handle: efi::Handle,
-        system_table: *mut efi::SystemTable,
+        image_handle: ImageHandle,
+        system_table: SystemTable,
     ) -> error::Result<()>;
 
-    fn entry_point(
-        image_handle: efi::Handle,
-        system_table: *mut efi::SystemTable,
-    ) -> error::Result<()> {
-        #[cfg(not(feature = "std"))]
-        rust_boot_services_allocator_dxe::GLOBAL_ALLOCATOR.init(unsafe { (*system_table).boot_services });
-        
+    /// Tears the component down when the image is unloaded.
+    ///
+    /// Libraries should be torn down in the reverse of the order they were initialized in. Returning an
+    /// error keeps the image loaded.
+    fn unload(_image_handle: ImageHandle) -> error::Result<()> {
+        Ok(())
+    }
+
+    /// Runs the component with an already validated image handle and system table.
+    fn run(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> error::EntryResult {
         Self::init(image_handle, system_table)?;
         Self::main(image_handle, system_table)
     }
-}
\ No newline at end of file
+
+    /// Validates the arguments passed to the image entry point and runs the component.
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or point to a system table that stays valid while the component runs.
+    /// See [`SystemTable::from_ptr`].
+    unsafe fn entry_point(
+        image_handle: efi::Handle,
+        system_table: *mut efi::SystemTable,
+    ) -> error::EntryResult {
+        let image_handle = ImageHandle::new(image_handle)?;
+        let system_table = SystemTable::from_ptr(system_table)?;
+        init_image(image_handle, system_table);
+
+        if Self::UNLOADABLE {
+            image::install_unload_handler::<Self>(image_handle, system_table)?;
+        }
+
+        Self::run(image_handle, system_table)
+    }
+}
+
+/// Points the global allocator at the image's Boot Services, if it has any, and records the image for
+/// the panic handler.
+fn init_image(image_handle: ImageHandle, system_table: SystemTable) {
+    panic::register(image_handle, system_table);
+
+    #[cfg(not(feature = "std"))]
+    if let Some(boot_services) = system_table.boot_services() {
+        rust_boot_services_allocator_dxe::GLOBAL_ALLOCATOR.init(boot_services.as_ptr());
+    }
+}
@@ -0,0 +1,229 @@
+//! Library constructors, run the way EDK2 runs them.
+//!
+//! `component!` and `component_from_path!` wrap the component they generate in [`Constructed`], along with
+//! every library instance of the resolved tree, in dependency order. Each library is constructed once,
+//! after the libraries it depends on and before [`Component::init`]. The first constructor to fail stops
+//! the entry point with its status. When an unloadable component is unloaded, its libraries are destructed
+//! in the reverse order, after [`Component::unload`] has succeeded.
+use core::marker::PhantomData;
+
+use r_efi::protocols::device_path;
+
+use crate::{
+    error::{EntryResult, Result},
+    ApplicationComponent, Component, ControllerHandle, DriverBindingComponent, ImageHandle, MmStandaloneComponent,
+    MmSystemTable, RuntimeComponent, RuntimeServices, SystemTable,
+};
+
+/// Implemented by every library instance, to set the library up before the component using it runs, and
+/// to tear it down once the component is unloaded.
+pub trait LibraryConstructor {
+    fn constructor(_image_handle: ImageHandle, _system_table: SystemTable) -> Result<()> {
+        Ok(())
+    }
+
+    fn destructor(_image_handle: ImageHandle) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A list of library instances, `(First, (Second, (Third, ())))`, constructed front to back and destructed
+/// back to front.
+pub trait LibraryConstructors {
+    fn construct(image_handle: ImageHandle, system_table: SystemTable) -> Result<()>;
+
+    fn destruct(image_handle: ImageHandle) -> Result<()>;
+}
+
+impl LibraryConstructors for () {
+    fn construct(_image_handle: ImageHandle, _system_table: SystemTable) -> Result<()> {
+        Ok(())
+    }
+
+    fn destruct(_image_handle: ImageHandle) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<L: LibraryConstructor, Rest: LibraryConstructors> LibraryConstructors for (L, Rest) {
+    fn construct(image_handle: ImageHandle, system_table: SystemTable) -> Result<()> {
+        L::constructor(image_handle, system_table)?;
+        Rest::construct(image_handle, system_table)
+    }
+
+    fn destruct(image_handle: ImageHandle) -> Result<()> {
+        Rest::destruct(image_handle)?;
+        L::destructor(image_handle)
+    }
+}
+
+/// Component `C`, whose libraries `L` are constructed before [`Component::init`] (or the `init` of the
+/// other component traits) runs.
+pub struct Constructed<C: ?Sized, L> {
+    _libraries: PhantomData<L>,
+    _component: PhantomData<C>,
+}
+
+impl<C: Component + ?Sized, L: LibraryConstructors> Component for Constructed<C, L> {
+    const UNLOADABLE: bool = C::UNLOADABLE;
+
+    fn main(image_handle: ImageHandle, system_table: SystemTable) -> EntryResult {
+        C::main(image_handle, system_table)
+    }
+
+    fn init(image_handle: ImageHandle, system_table: SystemTable) -> Result<()> {
+        L::construct(image_handle, system_table)?;
+        C::init(image_handle, system_table)
+    }
+
+    fn unload(image_handle: ImageHandle) -> Result<()> {
+        C::unload(image_handle)?;
+        L::destruct(image_handle)
+    }
+}
+
+impl<C: DriverBindingComponent + ?Sized, L: LibraryConstructors> DriverBindingComponent for Constructed<C, L> {
+    const VERSION: u32 = C::VERSION;
+    const DRIVER_NAME: Option<&'static str> = C::DRIVER_NAME;
+
+    fn init(image_handle: ImageHandle, system_table: SystemTable) -> Result<()> {
+        L::construct(image_handle, system_table)?;
+        C::init(image_handle, system_table)
+    }
+
+    fn supported(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        remaining_device_path: Option<&device_path::Protocol>,
+    ) -> Result<()> {
+        C::supported(image_handle, system_table, controller, remaining_device_path)
+    }
+
+    fn start(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        remaining_device_path: Option<&device_path::Protocol>,
+    ) -> Result<()> {
+        C::start(image_handle, system_table, controller, remaining_device_path)
+    }
+
+    fn stop(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+        controller: ControllerHandle,
+        children: &[ControllerHandle],
+    ) -> Result<()> {
+        C::stop(image_handle, system_table, controller, children)
+    }
+}
+
+impl<C: ApplicationComponent + ?Sized, L: LibraryConstructors> ApplicationComponent for Constructed<C, L> {
+    fn main(image_handle: ImageHandle, system_table: SystemTable) -> EntryResult {
+        C::main(image_handle, system_table)
+    }
+
+    fn init(image_handle: ImageHandle, system_table: SystemTable) -> Result<()> {
+        L::construct(image_handle, system_table)?;
+        C::init(image_handle, system_table)
+    }
+}
+
+impl<C: RuntimeComponent + ?Sized, L: LibraryConstructors> RuntimeComponent for Constructed<C, L> {
+    fn main(image_handle: ImageHandle, system_table: SystemTable) -> EntryResult {
+        C::main(image_handle, system_table)
+    }
+
+    fn init(image_handle: ImageHandle, system_table: SystemTable) -> Result<()> {
+        L::construct(image_handle, system_table)?;
+        C::init(image_handle, system_table)
+    }
+
+    fn exit_boot_services() {
+        C::exit_boot_services()
+    }
+
+    fn virtual_address_change(runtime_services: RuntimeServices) {
+        C::virtual_address_change(runtime_services)
+    }
+}
+
+/// Library constructors take the UEFI System Table, which a standalone MM image does not have, so only
+/// an MM component without libraries can be wrapped.
+impl<C: MmStandaloneComponent + ?Sized> MmStandaloneComponent for Constructed<C, ()> {
+    fn main(image_handle: ImageHandle, mm_system_table: MmSystemTable) -> EntryResult {
+        C::main(image_handle, mm_system_table)
+    }
+
+    fn init(image_handle: ImageHandle, mm_system_table: MmSystemTable) -> Result<()> {
+        C::init(image_handle, mm_system_table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::EfiError, table::tests::{mock_boot_services, mock_system_table}};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Records the order things ran in, as a base-16 number with one digit per step.
+    static ORDER: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(step: usize) {
+        ORDER.store(ORDER.load(Ordering::SeqCst) * 16 + step, Ordering::SeqCst);
+    }
+
+    struct LibA;
+    impl LibraryConstructor for LibA {
+        fn constructor(_: ImageHandle, _: SystemTable) -> Result<()> {
+            record(1);
+            Ok(())
+        }
+    }
+
+    struct LibB;
+    impl LibraryConstructor for LibB {
+        fn constructor(_: ImageHandle, _: SystemTable) -> Result<()> {
+            record(2);
+            Ok(())
+        }
+    }
+
+    struct Failing;
+    impl LibraryConstructor for Failing {
+        fn constructor(_: ImageHandle, _: SystemTable) -> Result<()> {
+            Err(EfiError::DeviceError)
+        }
+    }
+
+    struct MockComponent;
+    impl Component for MockComponent {
+        fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
+            record(4);
+            Ok(None)
+        }
+
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            record(3);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_constructors() {
+        let mut bs = mock_boot_services();
+        let mut st = mock_system_table(&mut bs);
+        let mut image = 0u8;
+        let image_handle = ImageHandle::new(&mut image as *mut u8 as r_efi::efi::Handle).unwrap();
+        let system_table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+
+        ORDER.store(0, Ordering::SeqCst);
+        let result = <Constructed<MockComponent, (LibA, (LibB, ()))> as Component>::run(image_handle, system_table);
+        assert_eq!(result, Ok(None));
+        assert_eq!(ORDER.load(Ordering::SeqCst), 0x1234);
+
+        let result = <Constructed<MockComponent, (LibA, (Failing, (LibB, ())))> as Component>::run(image_handle, system_table);
+        assert_eq!(result, Err(EfiError::DeviceError));
+    }
+}
@@ -0,0 +1,118 @@
+//! Components built as standalone MM drivers.
+//!
+//! Standalone MM drivers run in Management Mode and are entered with the MM System Table instead of
+//! the UEFI System Table. Boot services, and therefore the global allocator, are not available.
+use core::ffi::c_void;
+
+use r_efi::efi;
+
+use crate::{
+    error::{EfiError, EntryResult, Result},
+    ImageHandle,
+};
+
+/// The signature of the MM System Table, `SIGNATURE_32('S', 'M', 'S', 'T')`.
+pub const MM_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_4d53;
+
+/// A validated reference to the MM System Table.
+///
+/// r-efi does not describe the MM System Table, so only its header is typed. The MM core does not
+/// populate the header CRC32, so only the signature is checked.
+#[derive(Debug, Clone, Copy)]
+pub struct MmSystemTable<'a> {
+    hdr: &'a efi::TableHeader,
+}
+
+impl MmSystemTable<'_> {
+    /// Validates and wraps the MM System Table passed to the entry point.
+    ///
+    /// # Safety
+    ///
+    /// `mm_system_table` must be null or point to an MM System Table that stays valid for `'a`.
+    pub unsafe fn from_ptr(mm_system_table: *mut c_void) -> Result<Self> {
+        let hdr = (mm_system_table as *const efi::TableHeader).as_ref().ok_or(EfiError::InvalidParameter)?;
+        if hdr.signature != MM_SYSTEM_TABLE_SIGNATURE {
+            return Err(EfiError::InvalidParameter);
+        }
+        Ok(MmSystemTable { hdr })
+    }
+
+    /// The revision of the PI specification the MM System Table conforms to.
+    pub fn revision(&self) -> u32 {
+        self.hdr.revision
+    }
+
+    /// Returns the raw MM System Table, for passing to MM services.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.hdr as *const efi::TableHeader as *mut c_void
+    }
+}
+
+pub trait MmStandaloneComponent {
+    fn main(
+        image_handle: ImageHandle,
+        mm_system_table: MmSystemTable,
+    ) -> EntryResult;
+
+    fn init(
+        image_handle: ImageHandle,
+        mm_system_table: MmSystemTable,
+    ) -> Result<()>;
+
+    /// Validates the arguments passed to the image entry point and runs the component.
+    ///
+    /// # Safety
+    ///
+    /// `mm_system_table` must be null or point to an MM System Table that stays valid while the image
+    /// is loaded.
+    unsafe fn entry_point(
+        image_handle: efi::Handle,
+        mm_system_table: *mut c_void,
+    ) -> EntryResult {
+        let image_handle = ImageHandle::new(image_handle)?;
+        let mm_system_table = MmSystemTable::from_ptr(mm_system_table)?;
+
+        Self::init(image_handle, mm_system_table)?;
+        Self::main(image_handle, mm_system_table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    struct MockMmDriver;
+
+    impl MmStandaloneComponent for MockMmDriver {
+        fn main(_: ImageHandle, mmst: MmSystemTable) -> EntryResult {
+            assert_eq!(mmst.revision(), 0x10);
+            Ok(None)
+        }
+
+        fn init(_: ImageHandle, _: MmSystemTable) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mm_system_table_is_validated() {
+        let mut image = 0u8;
+        let image_handle = &mut image as *mut u8 as efi::Handle;
+        let mut hdr = efi::TableHeader {
+            signature: MM_SYSTEM_TABLE_SIGNATURE,
+            revision: 0x10,
+            header_size: core::mem::size_of::<efi::TableHeader>() as u32,
+            crc32: 0,
+            reserved: 0,
+        };
+
+        let mmst = &mut hdr as *mut efi::TableHeader as *mut c_void;
+        assert_eq!(unsafe { MockMmDriver::entry_point(image_handle, mmst) }, Ok(None));
+        assert_eq!(unsafe { MockMmDriver::entry_point(image_handle, ptr::null_mut()) }, Err(EfiError::InvalidParameter));
+
+        hdr.signature = efi::SYSTEM_TABLE_SIGNATURE;
+        let mmst = &mut hdr as *mut efi::TableHeader as *mut c_void;
+        assert_eq!(unsafe { MockMmDriver::entry_point(image_handle, mmst) }, Err(EfiError::InvalidParameter));
+    }
+}
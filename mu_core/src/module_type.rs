@@ -0,0 +1,47 @@
+//! Compile time checks that a component implements the trait matching its EDK2 module type.
+//!
+//! `component_from_path!` wraps the generated component type in a projection through [`BuildAs`] for the
+//! module type the config file selects, e.g.
+//! `<HelloWorldComponent<..> as BuildAs<DxeDriver>>::Component`. The projection resolves to the component
+//! itself, but only compiles if the component implements the matching component trait.
+use crate::{ApplicationComponent, Component, DriverBindingComponent, MmStandaloneComponent, RuntimeComponent};
+
+/// DXE_DRIVER: a boot services driver, see [`Component`].
+pub struct DxeDriver;
+
+/// UEFI_DRIVER: a UEFI Driver Model driver, see [`DriverBindingComponent`].
+pub struct UefiDriver;
+
+/// UEFI_APPLICATION, see [`ApplicationComponent`].
+pub struct UefiApplication;
+
+/// DXE_RUNTIME_DRIVER, see [`RuntimeComponent`].
+pub struct DxeRuntimeDriver;
+
+/// MM_STANDALONE, see [`MmStandaloneComponent`].
+pub struct MmStandalone;
+
+/// Implemented for every component that can be built as module type `M`.
+pub trait BuildAs<M> {
+    type Component: ?Sized;
+}
+
+impl<C: Component + ?Sized> BuildAs<DxeDriver> for C {
+    type Component = C;
+}
+
+impl<C: DriverBindingComponent + ?Sized> BuildAs<UefiDriver> for C {
+    type Component = C;
+}
+
+impl<C: ApplicationComponent + ?Sized> BuildAs<UefiApplication> for C {
+    type Component = C;
+}
+
+impl<C: RuntimeComponent + ?Sized> BuildAs<DxeRuntimeDriver> for C {
+    type Component = C;
+}
+
+impl<C: MmStandaloneComponent + ?Sized> BuildAs<MmStandalone> for C {
+    type Component = C;
+}
@@ -0,0 +1,240 @@
+//! A panic handler shared by all UEFI binaries.
+//!
+//! The handler reports the panic through the `log` backend installed by the component's DebugLib,
+//! flushes it, and then applies a [`PanicPolicy`]. Binaries install it with [`panic_handler!`]:
+//!
+//! ```ignore
+//! mu_core::panic_handler!(mu_core::panic::PanicPolicy::Exit);
+//! ```
+use core::{
+    fmt,
+    panic::PanicInfo,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use r_efi::efi;
+
+use crate::{ImageHandle, SystemTable};
+
+/// What to do once a panic has been reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Spin forever, so that a debugger can be attached.
+    Deadloop,
+    /// Trigger a CPU breakpoint, then spin forever.
+    Breakpoint,
+    /// Cold reset the system through the ResetSystem runtime service.
+    Reset,
+    /// Exit the image with `EFI_ABORTED`, handing control back to whoever started it.
+    Exit,
+}
+
+static IMAGE_HANDLE: AtomicPtr<core::ffi::c_void> = AtomicPtr::new(ptr::null_mut());
+static SYSTEM_TABLE: AtomicPtr<efi::SystemTable> = AtomicPtr::new(ptr::null_mut());
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Records the image handle and system table the `Reset` and `Exit` policies need.
+pub(crate) fn register(image_handle: ImageHandle, system_table: SystemTable) {
+    IMAGE_HANDLE.store(image_handle.as_raw(), Ordering::SeqCst);
+    SYSTEM_TABLE.store(system_table.as_ptr(), Ordering::SeqCst);
+}
+
+/// Reports `info` through the installed logger and applies `policy`.
+///
+/// A panic raised while handling a panic, e.g. by the logger or by the policy itself, skips straight to
+/// `Deadloop`. `Reset` and `Exit` fall back to `Deadloop` if the services they need are not available.
+pub fn handle(info: &PanicInfo, policy: PanicPolicy) -> ! {
+    let image_handle = ImageHandle::new(IMAGE_HANDLE.load(Ordering::SeqCst)).ok();
+    // SAFETY: the system table was validated by the entry point before it was registered.
+    let system_table = unsafe { SystemTable::from_validated(SYSTEM_TABLE.load(Ordering::SeqCst)) };
+    respond(&PANICKING, info, policy, image_handle, system_table);
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Reports `message` and applies `policy`, unless `panicking` shows a panic is already being handled.
+/// Returns if the policy does.
+fn respond(
+    panicking: &AtomicBool,
+    message: &dyn fmt::Display,
+    policy: PanicPolicy,
+    image_handle: Option<ImageHandle>,
+    system_table: Option<SystemTable>,
+) {
+    if panicking.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    log::error!("{}", message);
+    log::logger().flush();
+    apply(policy, image_handle, system_table);
+}
+
+fn apply(policy: PanicPolicy, image_handle: Option<ImageHandle>, system_table: Option<SystemTable>) {
+    match policy {
+        PanicPolicy::Deadloop => {}
+        PanicPolicy::Breakpoint => breakpoint(),
+        PanicPolicy::Reset => {
+            if let Some(rt) = system_table.and_then(|st| st.runtime_services()) {
+                rt.reset_system(efi::RESET_COLD, efi::Status::ABORTED);
+            }
+        }
+        PanicPolicy::Exit => {
+            if let (Some(bs), Some(image_handle)) = (system_table.and_then(|st| st.boot_services()), image_handle) {
+                let _ = bs.exit(image_handle, efi::Status::ABORTED);
+            }
+        }
+    }
+}
+
+fn breakpoint() {
+    // SAFETY: a breakpoint instruction has no effect on memory.
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!("int3")
+    };
+    // SAFETY: a breakpoint instruction has no effect on memory.
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("brk #0")
+    };
+}
+
+/// Defines the `#[panic_handler]` of a UEFI binary, using [`handle`] with the given [`PanicPolicy`].
+#[macro_export]
+macro_rules! panic_handler {
+    ($policy:expr) => {
+        #[panic_handler]
+        fn panic(info: &core::panic::PanicInfo) -> ! {
+            $crate::panic::handle(info, $policy)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{
+        calculate_crc32,
+        tests::{mock_boot_services, mock_runtime_services, mock_system_table},
+    };
+    use core::ffi::c_void;
+
+    // The crate is `no_std` without the `std` feature, but its tests always run on the host.
+    extern crate std;
+    use std::{
+        string::{String, ToString},
+        sync::{Mutex, Once},
+        vec::Vec,
+    };
+
+    /// Every error logged by this module, so that a test can find the messages it reported.
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct MockLogger;
+
+    impl log::Log for MockLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if record.level() == log::Level::Error && record.target() == "mu_core::panic" {
+                LOGGED.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn logged(message: &str) -> usize {
+        static LOGGER: Once = Once::new();
+        LOGGER.call_once(|| {
+            log::set_logger(&MockLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+        LOGGED.lock().unwrap().iter().filter(|logged| *logged == message).count()
+    }
+
+    /// Every image exited through the mocked `Exit` service, with its status. Each test exits its own image
+    /// handle, so that tests running in parallel do not see each other's calls.
+    static EXITED: Mutex<Vec<(usize, efi::Status)>> = Mutex::new(Vec::new());
+    static RESET: Mutex<Option<(efi::ResetType, efi::Status)>> = Mutex::new(None);
+
+    extern "efiapi" fn mock_exit(image_handle: efi::Handle, status: efi::Status, _: usize, _: *mut efi::Char16) -> efi::Status {
+        EXITED.lock().unwrap().push((image_handle as usize, status));
+        efi::Status::SUCCESS
+    }
+
+    fn exited(image_handle: ImageHandle) -> Option<efi::Status> {
+        let exited = EXITED.lock().unwrap();
+        exited.iter().find(|(handle, _)| *handle == image_handle.as_raw() as usize).map(|(_, status)| *status)
+    }
+
+    extern "efiapi" fn mock_reset_system(reset_type: efi::ResetType, status: efi::Status, _: usize, _: *mut c_void) {
+        *RESET.lock().unwrap() = Some((reset_type, status));
+    }
+
+    #[test]
+    fn test_exit_policy() {
+        let mut bs = mock_boot_services();
+        bs.exit = mock_exit;
+        bs.hdr.crc32 = unsafe { calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let system_table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+        let mut image = 0u8;
+        let image_handle = ImageHandle::new(&mut image as *mut u8 as efi::Handle).unwrap();
+
+        logged("");
+        respond(&AtomicBool::new(false), &"exit policy", PanicPolicy::Exit, Some(image_handle), Some(system_table));
+        assert_eq!(logged("exit policy"), 1);
+        assert_eq!(exited(image_handle), Some(efi::Status::ABORTED));
+    }
+
+    #[test]
+    fn test_reset_policy() {
+        let mut bs = mock_boot_services();
+        let mut rt = mock_runtime_services();
+        rt.reset_system = mock_reset_system;
+        rt.hdr.crc32 = unsafe { calculate_crc32(&rt.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        st.runtime_services = &mut rt;
+        st.hdr.crc32 = unsafe { calculate_crc32(&st.hdr) };
+        let system_table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+
+        logged("");
+        respond(&AtomicBool::new(false), &"reset policy", PanicPolicy::Reset, None, Some(system_table));
+        assert_eq!(logged("reset policy"), 1);
+        assert_eq!(*RESET.lock().unwrap(), Some((efi::RESET_COLD, efi::Status::ABORTED)));
+    }
+
+    #[test]
+    fn test_nested_panic_skips_policy() {
+        let mut bs = mock_boot_services();
+        bs.exit = mock_exit;
+        bs.hdr.crc32 = unsafe { calculate_crc32(&bs.hdr) };
+        let mut st = mock_system_table(&mut bs);
+        let system_table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+        let mut image = 0u8;
+        let image_handle = ImageHandle::new(&mut image as *mut u8 as efi::Handle).unwrap();
+
+        logged("");
+        let panicking = AtomicBool::new(false);
+        respond(&panicking, &"first panic", PanicPolicy::Deadloop, Some(image_handle), Some(system_table));
+        respond(&panicking, &"second panic", PanicPolicy::Exit, Some(image_handle), Some(system_table));
+        assert_eq!(logged("first panic"), 1);
+        assert_eq!(logged("second panic"), 0);
+        assert_eq!(exited(image_handle), None);
+    }
+
+    #[test]
+    fn test_missing_services_fall_back_to_deadloop() {
+        let mut st = mock_system_table(ptr::null_mut());
+        let system_table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+        apply(PanicPolicy::Exit, None, Some(system_table));
+        apply(PanicPolicy::Reset, None, Some(system_table));
+        apply(PanicPolicy::Exit, None, None);
+    }
+}
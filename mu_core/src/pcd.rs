@@ -0,0 +1,67 @@
+//! Platform Configuration Database (PCD) values.
+//!
+//! `pcds!` declares the PCDs a crate reads, with their type and default value, and sets each one from the
+//! `[[pcds]]` section of the platform config file. A PCD listed under `kind = "FixedAtBuild"` (the default)
+//! becomes a [`FixedAtBuild`] constant, and one listed under `kind = "PatchableInModule"` a
+//! [`PatchableInModule`] static. Both are read with `get()`, so code reading a PCD does not change with
+//! its kind, and a fixed PCD can also be read in const contexts, such as an array length. A PCD read in a
+//! const context is declared `const`, and `pcds!` rejects a config file that makes it patchable.
+use core::cell::UnsafeCell;
+
+/// A PCD whose value is a constant of the image.
+pub struct FixedAtBuild<T>(T);
+
+impl<T: Copy> FixedAtBuild<T> {
+    pub const fn new(value: T) -> Self {
+        FixedAtBuild(value)
+    }
+
+    pub const fn get(&self) -> T {
+        self.0
+    }
+}
+
+/// A PCD whose value is kept in the image's data, so that it can be patched in the binary after the
+/// build, or set while the image runs.
+pub struct PatchableInModule<T>(UnsafeCell<T>);
+
+// SAFETY: the value is only written through `set`, whose caller guarantees there are no concurrent reads.
+unsafe impl<T: Copy + Send> Sync for PatchableInModule<T> {}
+
+impl<T: Copy> PatchableInModule<T> {
+    pub const fn new(value: T) -> Self {
+        PatchableInModule(UnsafeCell::new(value))
+    }
+
+    pub fn get(&self) -> T {
+        // The read is volatile so the compiler does not fold the build-time value into the code.
+        unsafe { self.0.get().read_volatile() }
+    }
+
+    /// Sets the value of the PCD.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may read or write the PCD at the same time, such as an event callback or another processor.
+    pub unsafe fn set(&self, value: T) {
+        self.0.get().write_volatile(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXED: FixedAtBuild<usize> = FixedAtBuild::new(4);
+    static PATCHABLE: PatchableInModule<u16> = PatchableInModule::new(0x402);
+
+    #[test]
+    fn test_pcds() {
+        let buffer = [0u8; FIXED.get()];
+        assert_eq!(buffer.len(), 4);
+
+        assert_eq!(PATCHABLE.get(), 0x402);
+        unsafe { PATCHABLE.set(0x3F8) };
+        assert_eq!(PATCHABLE.get(), 0x3F8);
+    }
+}
@@ -0,0 +1,185 @@
+//! Components built as DXE runtime drivers.
+//!
+//! A runtime driver stays resident after the OS has taken over. It is told when boot services go away,
+//! and when the OS switches the firmware to virtual addressing so that it can convert any pointers it
+//! keeps.
+use core::ffi::c_void;
+
+use r_efi::efi;
+
+use crate::{
+    error::{EfiError, EntryResult, Result},
+    ImageHandle, RuntimeServices, SystemTable,
+};
+
+pub trait RuntimeComponent {
+    fn main(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> EntryResult;
+
+    fn init(
+        image_handle: ImageHandle,
+        system_table: SystemTable,
+    ) -> Result<()>;
+
+    /// Called when ExitBootServices is signaled. Boot services, including the global allocator, must not
+    /// be used once this returns.
+    fn exit_boot_services() {}
+
+    /// Called when SetVirtualAddressMap is signaled. Every pointer the driver uses at runtime must be
+    /// converted with [`RuntimeServices::convert_pointer`].
+    fn virtual_address_change(_runtime_services: RuntimeServices) {}
+
+    /// Validates the arguments passed to the image entry point, registers the ExitBootServices and
+    /// SetVirtualAddressMap notifications and runs the component.
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or point to a system table that stays valid while the image is loaded.
+    /// See [`SystemTable::from_ptr`].
+    unsafe fn entry_point(
+        image_handle: efi::Handle,
+        system_table: *mut efi::SystemTable,
+    ) -> EntryResult {
+        let image_handle = ImageHandle::new(image_handle)?;
+        let system_table = SystemTable::from_ptr(system_table)?;
+        crate::init_image(image_handle, system_table);
+
+        Self::init(image_handle, system_table)?;
+        register_events::<Self>(system_table)?;
+        Self::main(image_handle, system_table)
+    }
+}
+
+/// Creates the ExitBootServices and SetVirtualAddressMap event group notifications for `C`.
+fn register_events<C: RuntimeComponent + ?Sized>(system_table: SystemTable) -> Result<()> {
+    let boot_services = system_table.boot_services().ok_or(EfiError::Unsupported)?;
+    let runtime_services = system_table.runtime_services().ok_or(EfiError::Unsupported)?;
+
+    boot_services.create_event_ex(
+        efi::EVT_NOTIFY_SIGNAL,
+        efi::TPL_NOTIFY,
+        on_exit_boot_services::<C>,
+        None::<&()>,
+        &efi::EVENT_GROUP_EXIT_BOOT_SERVICES,
+    )?;
+
+    boot_services.create_event_ex(
+        efi::EVT_NOTIFY_SIGNAL,
+        efi::TPL_NOTIFY,
+        on_virtual_address_change::<C>,
+        Some(runtime_services.table()),
+        &efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE,
+    )?;
+    Ok(())
+}
+
+extern "efiapi" fn on_exit_boot_services<C: RuntimeComponent + ?Sized>(_event: efi::Event, _context: *mut c_void) {
+    C::exit_boot_services();
+
+    // Allocations are made from boot services memory, which the OS is about to reclaim.
+    #[cfg(not(feature = "std"))]
+    rust_boot_services_allocator_dxe::GLOBAL_ALLOCATOR.init(core::ptr::null_mut());
+}
+
+extern "efiapi" fn on_virtual_address_change<C: RuntimeComponent + ?Sized>(_event: efi::Event, context: *mut c_void) {
+    // SAFETY: the context is the Runtime Services table validated in `entry_point`.
+    if let Some(runtime_services) = unsafe { RuntimeServices::from_validated(context as *mut efi::RuntimeServices) } {
+        C::virtual_address_change(runtime_services);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{
+        calculate_crc32,
+        tests::{mock_boot_services, mock_runtime_services, mock_system_table},
+    };
+    use core::{
+        mem, ptr,
+        sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    };
+
+    static EXIT_BOOT_SERVICES: AtomicUsize = AtomicUsize::new(0);
+    static VIRTUAL_ADDRESS_CHANGE: AtomicUsize = AtomicUsize::new(0);
+    static CONTEXT: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    static EXITED: AtomicBool = AtomicBool::new(false);
+    static CONVERTED: AtomicPtr<efi::RuntimeServices> = AtomicPtr::new(ptr::null_mut());
+
+    extern "efiapi" fn mock_create_event_ex(
+        event_type: u32,
+        notify_tpl: efi::Tpl,
+        notify_function: Option<efi::EventNotify>,
+        notify_context: *const c_void,
+        event_group: *const efi::Guid,
+        _event: *mut efi::Event,
+    ) -> efi::Status {
+        assert_eq!(event_type, efi::EVT_NOTIFY_SIGNAL);
+        assert_eq!(notify_tpl, efi::TPL_NOTIFY);
+        let notify_function = notify_function.unwrap() as usize;
+        match unsafe { *event_group } {
+            efi::EVENT_GROUP_EXIT_BOOT_SERVICES => EXIT_BOOT_SERVICES.store(notify_function, Ordering::SeqCst),
+            efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE => {
+                VIRTUAL_ADDRESS_CHANGE.store(notify_function, Ordering::SeqCst);
+                CONTEXT.store(notify_context as *mut c_void, Ordering::SeqCst);
+            }
+            _ => return efi::Status::INVALID_PARAMETER,
+        }
+        efi::Status::SUCCESS
+    }
+
+    struct MockRuntimeDriver;
+
+    impl RuntimeComponent for MockRuntimeDriver {
+        fn main(_: ImageHandle, _: SystemTable) -> EntryResult {
+            Ok(None)
+        }
+
+        fn init(_: ImageHandle, _: SystemTable) -> Result<()> {
+            Ok(())
+        }
+
+        fn exit_boot_services() {
+            EXITED.store(true, Ordering::SeqCst);
+        }
+
+        fn virtual_address_change(runtime_services: RuntimeServices) {
+            CONVERTED.store(runtime_services.as_ptr(), Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_runtime_events_are_registered() {
+        let mut bs = mock_boot_services();
+        bs.create_event_ex = mock_create_event_ex;
+        bs.hdr.crc32 = unsafe { calculate_crc32(&bs.hdr) };
+        let mut rt = mock_runtime_services();
+        let mut st = mock_system_table(&mut bs);
+        st.runtime_services = &mut rt;
+        st.hdr.crc32 = unsafe { calculate_crc32(&st.hdr) };
+        let mut image = 0u8;
+
+        assert_eq!(unsafe { MockRuntimeDriver::entry_point(&mut image as *mut u8 as efi::Handle, &mut st) }, Ok(None));
+
+        let notify: efi::EventNotify = unsafe { mem::transmute(EXIT_BOOT_SERVICES.load(Ordering::SeqCst)) };
+        notify(ptr::null_mut(), ptr::null_mut());
+        assert!(EXITED.load(Ordering::SeqCst));
+
+        let notify: efi::EventNotify = unsafe { mem::transmute(VIRTUAL_ADDRESS_CHANGE.load(Ordering::SeqCst)) };
+        notify(ptr::null_mut(), CONTEXT.load(Ordering::SeqCst));
+        assert_eq!(CONVERTED.load(Ordering::SeqCst), &mut rt as *mut efi::RuntimeServices);
+    }
+
+    #[test]
+    fn test_runtime_driver_requires_runtime_services() {
+        let mut bs = mock_boot_services();
+        let mut st = mock_system_table(&mut bs);
+        let mut image = 0u8;
+
+        let result = unsafe { MockRuntimeDriver::entry_point(&mut image as *mut u8 as efi::Handle, &mut st) };
+        assert_eq!(result, Err(EfiError::Unsupported));
+    }
+}
@@ -0,0 +1,457 @@
+//! Safe wrappers around the handle and tables the firmware passes to an image's entry point.
+//!
+//! The raw `efi::Handle` and `*mut efi::SystemTable` are checked once, when the component is entered,
+//! so that components and libraries only ever see validated, typed references.
+use core::{ffi::c_void, fmt, mem, ptr, slice};
+
+use r_efi::{
+    efi,
+    protocols::{loaded_image, simple_text_input, simple_text_output},
+};
+
+use crate::error::{self, EfiError, Result};
+
+/// Byte offset of `crc32` inside an `efi::TableHeader` (signature: u64, revision: u32, header_size: u32).
+const CRC32_OFFSET: usize = 16;
+
+/// The handle of the image currently being executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHandle(efi::Handle);
+
+impl ImageHandle {
+    /// Wraps the image handle passed to the entry point.
+    ///
+    /// Returns [`EfiError::InvalidParameter`] if the handle is null.
+    pub fn new(handle: efi::Handle) -> Result<Self> {
+        if handle.is_null() {
+            return Err(EfiError::InvalidParameter);
+        }
+        Ok(ImageHandle(handle))
+    }
+
+    /// Returns the raw handle, for passing back to firmware services.
+    pub fn as_raw(&self) -> efi::Handle {
+        self.0
+    }
+
+    /// The handle of the image running on the host, which carries a Loaded Image Protocol.
+    #[cfg(feature = "std")]
+    pub fn host() -> Self {
+        ImageHandle(mu_host::image_handle())
+    }
+}
+
+impl From<ImageHandle> for efi::Handle {
+    fn from(handle: ImageHandle) -> Self {
+        handle.0
+    }
+}
+
+/// A protocol interface, found by its GUID.
+///
+/// # Safety
+///
+/// An interface installed under `GUID` must be a `Self`. A `#[repr(C)]` type that starts with the
+/// protocol, and carries the driver's own data after it, may be used to install the protocol.
+pub unsafe trait Protocol {
+    const GUID: efi::Guid;
+}
+
+// SAFETY: the Loaded Image Protocol is installed under its GUID.
+unsafe impl Protocol for loaded_image::Protocol {
+    const GUID: efi::Guid = loaded_image::PROTOCOL_GUID;
+}
+
+/// A validated reference to the UEFI System Table.
+#[derive(Clone, Copy)]
+pub struct SystemTable<'a> {
+    table: &'a efi::SystemTable,
+}
+
+impl<'a> SystemTable<'a> {
+    /// Wraps a system table that has already been validated with [`SystemTable::from_ptr`].
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or a system table that `from_ptr` accepted, and that is still valid.
+    pub(crate) unsafe fn from_validated(system_table: *mut efi::SystemTable) -> Option<Self> {
+        system_table.as_ref().map(|table| SystemTable { table })
+    }
+
+    /// Validates and wraps the system table passed to the entry point.
+    ///
+    /// The pointer must not be null, and the system table as well as any Boot Services or Runtime
+    /// Services table it references must carry the expected signature and a matching CRC32.
+    ///
+    /// # Safety
+    ///
+    /// `system_table` must be null or point to memory that is readable for the size given in its header,
+    /// and that stays valid for `'a`. The same applies to the tables it references.
+    pub unsafe fn from_ptr(system_table: *mut efi::SystemTable) -> Result<Self> {
+        let table = system_table.as_ref().ok_or(EfiError::InvalidParameter)?;
+        validate_header(&table.hdr, efi::SYSTEM_TABLE_SIGNATURE)?;
+
+        if let Some(bs) = table.boot_services.as_ref() {
+            validate_header(&bs.hdr, efi::BOOT_SERVICES_SIGNATURE)?;
+        }
+        if let Some(rt) = table.runtime_services.as_ref() {
+            validate_header(&rt.hdr, efi::RUNTIME_SERVICES_SIGNATURE)?;
+        }
+        Ok(SystemTable { table })
+    }
+
+    /// Returns the raw system table, for passing back to firmware services.
+    pub fn as_ptr(&self) -> *mut efi::SystemTable {
+        self.table as *const efi::SystemTable as *mut efi::SystemTable
+    }
+
+    /// The revision of the UEFI specification the system table conforms to.
+    pub fn revision(&self) -> u32 {
+        self.table.hdr.revision
+    }
+
+    /// The vendor specific revision of the firmware.
+    pub fn firmware_revision(&self) -> u32 {
+        self.table.firmware_revision
+    }
+
+    /// The Boot Services table, or `None` once boot services have been exited.
+    pub fn boot_services(&self) -> Option<BootServices<'a>> {
+        // SAFETY: the pointer was validated in `from_ptr`.
+        unsafe { self.table.boot_services.as_ref() }.map(|table| BootServices { table })
+    }
+
+    /// The Runtime Services table, if one is installed.
+    pub fn runtime_services(&self) -> Option<RuntimeServices<'a>> {
+        // SAFETY: the pointer was validated in `from_ptr`.
+        unsafe { self.table.runtime_services.as_ref() }.map(|table| RuntimeServices { table })
+    }
+
+    /// The protocol interface of the active console input device.
+    pub fn con_in(&self) -> Option<&'a simple_text_input::Protocol> {
+        // SAFETY: the caller of `from_ptr` guarantees referenced pointers are valid or null.
+        unsafe { self.table.con_in.as_ref() }
+    }
+
+    /// The protocol interface of the active console output device.
+    pub fn con_out(&self) -> Option<&'a simple_text_output::Protocol> {
+        // SAFETY: the caller of `from_ptr` guarantees referenced pointers are valid or null.
+        unsafe { self.table.con_out.as_ref() }
+    }
+
+    /// The protocol interface of the active standard error console device.
+    pub fn std_err(&self) -> Option<&'a simple_text_output::Protocol> {
+        // SAFETY: the caller of `from_ptr` guarantees referenced pointers are valid or null.
+        unsafe { self.table.std_err.as_ref() }
+    }
+
+    /// All configuration tables installed in the system table.
+    pub fn configuration_tables(&self) -> &'a [efi::ConfigurationTable] {
+        if self.table.configuration_table.is_null() {
+            return &[];
+        }
+        // SAFETY: the caller of `from_ptr` guarantees referenced pointers are valid or null.
+        unsafe { slice::from_raw_parts(self.table.configuration_table, self.table.number_of_table_entries) }
+    }
+
+    /// Looks up the configuration table registered under `guid`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the table registered under `guid` is a valid `T`.
+    pub unsafe fn configuration_table<T>(&self, guid: &efi::Guid) -> Option<&'a T> {
+        self.configuration_tables()
+            .iter()
+            .find(|entry| entry.vendor_guid == *guid)
+            .and_then(|entry| (entry.vendor_table as *const T).as_ref())
+    }
+}
+
+#[cfg(feature = "std")]
+impl SystemTable<'static> {
+    /// The system table of the in-process UEFI environment provided by `mu_host`.
+    ///
+    /// Lets components run on the host with working Boot and Runtime Services.
+    pub fn host() -> Self {
+        // SAFETY: the host system table is valid and lives until the process exits.
+        unsafe { Self::from_ptr(mu_host::system_table()) }.expect("the host system table is valid")
+    }
+}
+
+impl fmt::Debug for SystemTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SystemTable")
+            .field("revision", &self.revision())
+            .field("firmware_revision", &self.firmware_revision())
+            .field("boot_services", &!self.table.boot_services.is_null())
+            .field("runtime_services", &!self.table.runtime_services.is_null())
+            .field("configuration_tables", &self.table.number_of_table_entries)
+            .finish()
+    }
+}
+
+/// A validated reference to the UEFI Boot Services table.
+#[derive(Clone, Copy)]
+pub struct BootServices<'a> {
+    table: &'a efi::BootServices,
+}
+
+impl<'a> BootServices<'a> {
+    /// Returns the raw Boot Services table, e.g. to initialize the global allocator.
+    pub fn as_ptr(&self) -> *mut efi::BootServices {
+        self.table as *const efi::BootServices as *mut efi::BootServices
+    }
+
+    /// Looks up the `P` interface installed on `handle`.
+    ///
+    /// The interface is owned by whoever installed it, and stays valid for as long as it is installed.
+    ///
+    /// # Safety
+    ///
+    /// The firmware hands out the same interface to every caller, so nothing else may access it while the
+    /// returned reference is in use: not another reference from this function, nor the firmware or another
+    /// image. The interface must also stay installed for as long as the reference is used.
+    pub unsafe fn handle_protocol<P: Protocol>(&self, handle: impl Into<efi::Handle>) -> Result<&'a mut P> {
+        let mut guid = P::GUID;
+        let mut interface: *mut c_void = ptr::null_mut();
+        error::from_status((self.table.handle_protocol)(handle.into(), &mut guid, &mut interface))?;
+        // SAFETY: an interface installed under `P::GUID` is a `P`, see `Protocol`, and the caller guarantees
+        // the reference is the only access to it.
+        (interface as *mut P).as_mut().ok_or(EfiError::NotFound)
+    }
+
+    /// Installs `interface` as the `P` protocol on `handle`. The interface stays installed until the
+    /// image is unloaded, so it is never freed.
+    pub fn install_protocol_interface<P: Protocol>(&self, handle: impl Into<efi::Handle>, interface: &'static mut P) -> Result<()> {
+        let mut handle = handle.into();
+        let mut guid = P::GUID;
+        error::from_status((self.table.install_protocol_interface)(
+            &mut handle,
+            &mut guid,
+            efi::NATIVE_INTERFACE,
+            interface as *mut P as *mut c_void,
+        ))?;
+        Ok(())
+    }
+
+    /// Creates an event of `event_type` in `event_group`. `notify_function` is called with `notify_context`
+    /// when the group is signaled.
+    pub fn create_event_ex<T>(
+        &self,
+        event_type: u32,
+        notify_tpl: efi::Tpl,
+        notify_function: efi::EventNotify,
+        notify_context: Option<&'a T>,
+        event_group: &efi::Guid,
+    ) -> Result<efi::Event> {
+        let context = notify_context.map_or(ptr::null(), |context| context as *const T as *const c_void);
+        let mut event: efi::Event = ptr::null_mut();
+        error::from_status((self.table.create_event_ex)(
+            event_type,
+            notify_tpl,
+            Some(notify_function),
+            context,
+            event_group,
+            &mut event,
+        ))?;
+        Ok(event)
+    }
+
+    /// Exits `image_handle` with `status`. Only returns if the firmware fails to exit the image.
+    pub fn exit(&self, image_handle: ImageHandle, status: efi::Status) -> Result<()> {
+        error::from_status((self.table.exit)(image_handle.as_raw(), status, 0, ptr::null_mut()))?;
+        Ok(())
+    }
+}
+
+/// A validated reference to the UEFI Runtime Services table.
+#[derive(Clone, Copy)]
+pub struct RuntimeServices<'a> {
+    table: &'a efi::RuntimeServices,
+}
+
+impl<'a> RuntimeServices<'a> {
+    /// Wraps a Runtime Services table that has already been validated.
+    ///
+    /// # Safety
+    ///
+    /// `table` must be null or point to a valid Runtime Services table.
+    pub(crate) unsafe fn from_validated(table: *mut efi::RuntimeServices) -> Option<Self> {
+        table.as_ref().map(|table| RuntimeServices { table })
+    }
+
+    /// Returns the raw Runtime Services table.
+    pub fn as_ptr(&self) -> *mut efi::RuntimeServices {
+        self.table as *const efi::RuntimeServices as *mut efi::RuntimeServices
+    }
+
+    /// The table, to pass as the context of an event notification.
+    pub(crate) fn table(&self) -> &'a efi::RuntimeServices {
+        self.table
+    }
+
+    /// Resets the system with `reset_type`, reporting `status` as the reason. Does not return on success.
+    pub fn reset_system(&self, reset_type: efi::ResetType, status: efi::Status) {
+        (self.table.reset_system)(reset_type, status, 0, ptr::null_mut());
+    }
+
+    /// Converts `pointer` from its physical address to the virtual address the OS has assigned to it.
+    ///
+    /// # Safety
+    ///
+    /// May only be called while the virtual address change event is being signaled, and `pointer` must
+    /// point into memory that is part of the runtime memory map.
+    pub unsafe fn convert_pointer<T>(&self, pointer: &mut *mut T) -> Result<()> {
+        let pointer = pointer as *mut *mut T as *mut *mut core::ffi::c_void;
+        error::from_status((self.table.convert_pointer)(0, pointer))?;
+        Ok(())
+    }
+}
+
+/// Checks the signature and CRC32 of a table header.
+///
+/// # Safety
+///
+/// `hdr` must be the start of a table that is readable for `hdr.header_size` bytes.
+unsafe fn validate_header(hdr: &efi::TableHeader, signature: u64) -> Result<()> {
+    if hdr.signature != signature || (hdr.header_size as usize) < mem::size_of::<efi::TableHeader>() {
+        return Err(EfiError::InvalidParameter);
+    }
+    if calculate_crc32(hdr) != hdr.crc32 {
+        return Err(EfiError::CrcError);
+    }
+    Ok(())
+}
+
+/// Calculates the CRC32 of a table, treating its `crc32` field as zero as the specification requires.
+///
+/// # Safety
+///
+/// `hdr` must be the start of a table that is readable for `hdr.header_size` bytes.
+pub(crate) unsafe fn calculate_crc32(hdr: &efi::TableHeader) -> u32 {
+    let bytes = slice::from_raw_parts(hdr as *const efi::TableHeader as *const u8, hdr.header_size as usize);
+    crc32(bytes.iter().enumerate().map(|(i, byte)| if (CRC32_OFFSET..CRC32_OFFSET + 4).contains(&i) { 0 } else { *byte }))
+}
+
+/// The CRC32 (IEEE 802.3) used by UEFI table headers.
+fn crc32(bytes: impl Iterator<Item = u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A copy of the Boot Services table of `mu_host`, so that every service is a working function. Tests
+    /// replace the services they mock, and then update the CRC32.
+    pub(crate) fn mock_boot_services() -> efi::BootServices {
+        // SAFETY: the host tables are valid until the process exits, and hold no data owned by the table.
+        unsafe { ptr::read((*mu_host::system_table()).boot_services) }
+    }
+
+    /// A copy of the Runtime Services table of `mu_host`, see [`mock_boot_services`].
+    pub(crate) fn mock_runtime_services() -> efi::RuntimeServices {
+        // SAFETY: as above.
+        unsafe { ptr::read((*mu_host::system_table()).runtime_services) }
+    }
+
+    pub(crate) fn mock_system_table(boot_services: *mut efi::BootServices) -> efi::SystemTable {
+        let mut system_table = efi::SystemTable {
+            hdr: efi::TableHeader {
+                signature: efi::SYSTEM_TABLE_SIGNATURE,
+                revision: efi::SYSTEM_TABLE_REVISION,
+                header_size: mem::size_of::<efi::SystemTable>() as u32,
+                crc32: 0,
+                reserved: 0,
+            },
+            firmware_vendor: ptr::null_mut(),
+            firmware_revision: 0,
+            console_in_handle: ptr::null_mut(),
+            con_in: ptr::null_mut(),
+            console_out_handle: ptr::null_mut(),
+            con_out: ptr::null_mut(),
+            standard_error_handle: ptr::null_mut(),
+            std_err: ptr::null_mut(),
+            runtime_services: ptr::null_mut(),
+            boot_services,
+            number_of_table_entries: 0,
+            configuration_table: ptr::null_mut(),
+        };
+        system_table.hdr.crc32 = unsafe { calculate_crc32(&system_table.hdr) };
+        system_table
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789".iter().copied()), 0xCBF4_3926);
+
+        // The crc32 field itself does not contribute to the checksum.
+        let mut hdr = efi::TableHeader { signature: 0, revision: 0, header_size: 24, crc32: 0xFFFF_FFFF, reserved: 0 };
+        let crc = unsafe { calculate_crc32(&hdr) };
+        hdr.crc32 = 0;
+        assert_eq!(crc, unsafe { calculate_crc32(&hdr) });
+    }
+
+    #[test]
+    fn test_null_is_rejected() {
+        assert_eq!(ImageHandle::new(core::ptr::null_mut()), Err(EfiError::InvalidParameter));
+        assert_eq!(unsafe { SystemTable::from_ptr(core::ptr::null_mut()) }.err(), Some(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_valid_table() {
+        let mut bs = mock_boot_services();
+        let mut st = mock_system_table(&mut bs);
+
+        let table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+        assert_eq!(table.as_ptr(), &mut st as *mut efi::SystemTable);
+        assert_eq!(table.boot_services().unwrap().as_ptr(), &mut bs as *mut efi::BootServices);
+        assert!(table.runtime_services().is_none());
+        assert!(table.con_out().is_none());
+        assert!(table.configuration_tables().is_empty());
+    }
+
+    #[test]
+    fn test_bad_signature_is_rejected() {
+        let mut st = mock_system_table(core::ptr::null_mut());
+        st.hdr.signature = efi::BOOT_SERVICES_SIGNATURE;
+        assert_eq!(unsafe { SystemTable::from_ptr(&mut st) }.err(), Some(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_bad_crc_is_rejected() {
+        let mut st = mock_system_table(core::ptr::null_mut());
+        st.firmware_revision = 1;
+        assert_eq!(unsafe { SystemTable::from_ptr(&mut st) }.err(), Some(EfiError::CrcError));
+
+        // A corrupted Boot Services table invalidates the whole system table.
+        let mut bs = mock_boot_services();
+        bs.hdr.revision = 1;
+        let mut st = mock_system_table(&mut bs);
+        assert_eq!(unsafe { SystemTable::from_ptr(&mut st) }.err(), Some(EfiError::CrcError));
+    }
+
+    #[test]
+    fn test_configuration_table_lookup() {
+        const GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6, 7, 8, 9, 10, 11]);
+        let mut value = 42u32;
+        let mut entries = [efi::ConfigurationTable { vendor_guid: GUID, vendor_table: &mut value as *mut u32 as *mut c_void }];
+
+        let mut st = mock_system_table(core::ptr::null_mut());
+        st.number_of_table_entries = entries.len();
+        st.configuration_table = entries.as_mut_ptr();
+        st.hdr.crc32 = unsafe { calculate_crc32(&st.hdr) };
+
+        let table = unsafe { SystemTable::from_ptr(&mut st) }.unwrap();
+        assert_eq!(table.configuration_tables().len(), 1);
+        assert_eq!(unsafe { table.configuration_table::<u32>(&GUID) }, Some(&42));
+        assert!(unsafe { table.configuration_table::<u32>(&efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6])) }.is_none());
+    }
+}
@@ -0,0 +1,247 @@
+//! The Boot Services table, and the services that do not belong to a larger group.
+use std::{
+    ffi::c_void,
+    mem, process, ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use r_efi::{efi, protocols::loaded_image};
+
+use crate::{event, lock, memory, protocol};
+
+static MONOTONIC_COUNT: AtomicU64 = AtomicU64::new(0);
+/// The installed configuration tables, as (GUID, table address) pairs.
+static CONFIGURATION_TABLES: Mutex<Vec<(efi::Guid, usize)>> = Mutex::new(Vec::new());
+
+pub(crate) fn boot_services() -> *mut efi::BootServices {
+    let table = Box::leak(Box::new(efi::BootServices {
+        hdr: crate::table_header(
+            efi::BOOT_SERVICES_SIGNATURE,
+            efi::BOOT_SERVICES_REVISION,
+            mem::size_of::<efi::BootServices>(),
+        ),
+        raise_tpl: event::raise_tpl,
+        restore_tpl: event::restore_tpl,
+        allocate_pages: memory::allocate_pages,
+        free_pages: memory::free_pages,
+        get_memory_map: memory::get_memory_map,
+        allocate_pool: memory::allocate_pool,
+        free_pool: memory::free_pool,
+        create_event: event::create_event,
+        set_timer: event::set_timer,
+        wait_for_event: event::wait_for_event,
+        signal_event: event::signal_event,
+        close_event: event::close_event,
+        check_event: event::check_event,
+        install_protocol_interface: protocol::install_protocol_interface,
+        reinstall_protocol_interface: protocol::reinstall_protocol_interface,
+        uninstall_protocol_interface: protocol::uninstall_protocol_interface,
+        handle_protocol: protocol::handle_protocol,
+        reserved: ptr::null_mut(),
+        register_protocol_notify: protocol::register_protocol_notify,
+        locate_handle: protocol::locate_handle,
+        locate_device_path: protocol::locate_device_path,
+        install_configuration_table,
+        load_image,
+        start_image,
+        exit,
+        unload_image,
+        exit_boot_services,
+        get_next_monotonic_count,
+        stall: event::stall,
+        set_watchdog_timer,
+        connect_controller: protocol::connect_controller,
+        disconnect_controller: protocol::disconnect_controller,
+        open_protocol: protocol::open_protocol,
+        close_protocol: protocol::close_protocol,
+        open_protocol_information: protocol::open_protocol_information,
+        protocols_per_handle: protocol::protocols_per_handle,
+        locate_handle_buffer: protocol::locate_handle_buffer,
+        locate_protocol: protocol::locate_protocol,
+        install_multiple_protocol_interfaces: protocol::install_multiple_protocol_interfaces,
+        uninstall_multiple_protocol_interfaces: protocol::uninstall_multiple_protocol_interfaces,
+        calculate_crc32,
+        copy_mem,
+        set_mem,
+        create_event_ex: event::create_event_ex,
+    }));
+    crate::update_crc32(&mut table.hdr);
+    table
+}
+
+/// Adds, replaces or removes (when `table` is null) a configuration table, then rebuilds the array the
+/// system table points at.
+extern "efiapi" fn install_configuration_table(guid: *mut efi::Guid, table: *mut c_void) -> efi::Status {
+    // SAFETY: the GUID is either null or points to a GUID.
+    let Some(guid) = (unsafe { guid.as_ref() }).copied() else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let mut tables = lock(&CONFIGURATION_TABLES);
+    let existing = tables.iter().position(|(g, _)| *g == guid);
+    match (existing, table.is_null()) {
+        (Some(index), true) => {
+            tables.remove(index);
+        }
+        (None, true) => return efi::Status::NOT_FOUND,
+        (Some(index), false) => tables[index].1 = table as usize,
+        (None, false) => tables.push((guid, table as usize)),
+    }
+
+    let array: Box<[efi::ConfigurationTable]> = tables
+        .iter()
+        .map(|(vendor_guid, vendor_table)| efi::ConfigurationTable {
+            vendor_guid: *vendor_guid,
+            vendor_table: *vendor_table as *mut c_void,
+        })
+        .collect();
+    let entries = array.len();
+
+    // SAFETY: the system table was built by this crate, and the previous array, if any, was leaked by a
+    // previous call with the length recorded in the table.
+    unsafe {
+        let system_table = &mut *crate::system_table();
+        let previous = system_table.configuration_table;
+        if !previous.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(previous, system_table.number_of_table_entries)));
+        }
+        system_table.configuration_table = Box::leak(array).as_mut_ptr();
+        system_table.number_of_table_entries = entries;
+        crate::update_crc32(&mut system_table.hdr);
+    }
+    efi::Status::SUCCESS
+}
+
+/// There are no PE images to load on the host.
+extern "efiapi" fn load_image(
+    _boot_policy: efi::Boolean,
+    _parent_image_handle: efi::Handle,
+    _device_path: *mut r_efi::protocols::device_path::Protocol,
+    _source_buffer: *mut c_void,
+    _source_size: usize,
+    _image_handle: *mut efi::Handle,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn start_image(
+    _image_handle: efi::Handle,
+    _exit_data_size: *mut usize,
+    _exit_data: *mut *mut efi::Char16,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+/// Ends the process, successfully if `exit_status` is not an error.
+extern "efiapi" fn exit(
+    _image_handle: efi::Handle,
+    exit_status: efi::Status,
+    _exit_data_size: usize,
+    _exit_data: *mut efi::Char16,
+) -> efi::Status {
+    process::exit(match exit_status.is_error() {
+        true => 1,
+        false => 0,
+    })
+}
+
+/// Calls the unload handler of the image's Loaded Image Protocol.
+extern "efiapi" fn unload_image(image_handle: efi::Handle) -> efi::Status {
+    let mut interface = ptr::null_mut();
+    let status = protocol::handle_protocol(
+        image_handle,
+        &loaded_image::PROTOCOL_GUID as *const _ as *mut efi::Guid,
+        &mut interface,
+    );
+    if status != efi::Status::SUCCESS {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: the interface is a Loaded Image Protocol.
+    let unload = unsafe { (*(interface as *mut loaded_image::Protocol)).unload };
+    unload(image_handle)
+}
+
+/// The unload handler of images that cannot be unloaded.
+pub(crate) extern "efiapi" fn unload_unsupported(_image_handle: efi::Handle) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+/// Signals the ExitBootServices event group. The services keep working afterwards.
+extern "efiapi" fn exit_boot_services(_image_handle: efi::Handle, _map_key: usize) -> efi::Status {
+    event::signal_group(efi::EVENT_GROUP_EXIT_BOOT_SERVICES);
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn get_next_monotonic_count(count: *mut u64) -> efi::Status {
+    if count.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: checked for null above.
+    unsafe { *count = MONOTONIC_COUNT.fetch_add(1, Ordering::SeqCst) };
+    efi::Status::SUCCESS
+}
+
+/// There is no watchdog on the host.
+extern "efiapi" fn set_watchdog_timer(
+    _timeout: usize,
+    _watchdog_code: u64,
+    _data_size: usize,
+    _watchdog_data: *mut efi::Char16,
+) -> efi::Status {
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn calculate_crc32(data: *mut c_void, data_size: usize, crc32: *mut u32) -> efi::Status {
+    if data.is_null() || data_size == 0 || crc32.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: checked for null above, and the caller passes `data_size` bytes.
+    unsafe { *crc32 = crate::crc32(std::slice::from_raw_parts(data as *const u8, data_size)) };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn copy_mem(destination: *mut c_void, source: *mut c_void, length: usize) {
+    // SAFETY: the caller passes buffers of `length` bytes, which may overlap.
+    unsafe { ptr::copy(source as *const u8, destination as *mut u8, length) };
+}
+
+extern "efiapi" fn set_mem(buffer: *mut c_void, size: usize, value: u8) {
+    // SAFETY: the caller passes a buffer of `size` bytes.
+    unsafe { ptr::write_bytes(buffer as *mut u8, value, size) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::crc32_is_valid;
+
+    #[test]
+    fn test_install_configuration_table() {
+        let mut guid =
+            efi::Guid::from_fields(0x01234567, 0x89ab, 0xcdef, 0x01, 0x23, &[0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        let mut table = 0u64;
+        let table = &mut table as *mut u64 as *mut c_void;
+
+        assert_eq!(install_configuration_table(&mut guid, table), efi::Status::SUCCESS);
+        let system_table = unsafe { &*crate::system_table() };
+        assert!(crc32_is_valid(&system_table.hdr));
+        let tables = unsafe {
+            std::slice::from_raw_parts(system_table.configuration_table, system_table.number_of_table_entries)
+        };
+        assert!(tables.iter().any(|t| t.vendor_guid == guid && t.vendor_table == table));
+
+        assert_eq!(install_configuration_table(&mut guid, ptr::null_mut()), efi::Status::SUCCESS);
+        assert_eq!(install_configuration_table(&mut guid, ptr::null_mut()), efi::Status::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_calculate_crc32() {
+        let mut data = *b"123456789";
+        let mut crc = 0;
+        assert_eq!(calculate_crc32(data.as_mut_ptr() as *mut c_void, data.len(), &mut crc), efi::Status::SUCCESS);
+        assert_eq!(crc, 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,179 @@
+//! Text output consoles writing to stdout and stderr.
+use std::{
+    ffi::c_void,
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use r_efi::{efi, protocols::simple_text_output};
+
+use crate::protocol;
+
+const COLUMNS: usize = 80;
+const ROWS: usize = 25;
+
+static STDOUT: OnceLock<(usize, usize)> = OnceLock::new();
+static STDERR: OnceLock<(usize, usize)> = OnceLock::new();
+
+/// The handle and protocol of the console writing to stdout.
+pub(crate) fn stdout() -> (efi::Handle, *mut simple_text_output::Protocol) {
+    let (handle, protocol) = *STDOUT.get_or_init(new_console);
+    (handle as efi::Handle, protocol as *mut simple_text_output::Protocol)
+}
+
+/// The handle and protocol of the console writing to stderr.
+pub(crate) fn stderr() -> (efi::Handle, *mut simple_text_output::Protocol) {
+    let (handle, protocol) = *STDERR.get_or_init(new_console);
+    (handle as efi::Handle, protocol as *mut simple_text_output::Protocol)
+}
+
+fn new_console() -> (usize, usize) {
+    let mode = Box::leak(Box::new(simple_text_output::Mode {
+        max_mode: 1,
+        mode: 0,
+        attribute: 0x07,
+        cursor_column: 0,
+        cursor_row: 0,
+        cursor_visible: efi::Boolean::TRUE,
+    }));
+    let console = Box::leak(Box::new(simple_text_output::Protocol {
+        reset,
+        output_string,
+        test_string,
+        query_mode,
+        set_mode,
+        set_attribute,
+        clear_screen,
+        set_cursor_position,
+        enable_cursor,
+        mode,
+    }));
+
+    let interface = console as *mut simple_text_output::Protocol;
+    let handle = protocol::install(0, simple_text_output::PROTOCOL_GUID, interface as *mut c_void)
+        .expect("a new handle accepts any protocol");
+    (handle, interface as usize)
+}
+
+/// Reads a null terminated UCS-2 string.
+///
+/// # Safety
+///
+/// `string` must be null or point to a null terminated string.
+unsafe fn read_string(string: *const efi::Char16) -> Option<Vec<u16>> {
+    if string.is_null() {
+        return None;
+    }
+    let length = (0..).take_while(|i| *string.add(*i) != 0).count();
+    Some(std::slice::from_raw_parts(string, length).to_vec())
+}
+
+extern "efiapi" fn reset(_this: *mut simple_text_output::Protocol, _extended_verification: efi::Boolean) -> efi::Status {
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn output_string(this: *mut simple_text_output::Protocol, string: *mut efi::Char16) -> efi::Status {
+    // SAFETY: the caller passes a null terminated string.
+    let Some(string) = (unsafe { read_string(string) }) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    // Consoles end lines with CR LF, terminals only need the LF.
+    let text = String::from_utf16_lossy(&string).replace('\r', "");
+
+    let result = match this == stderr().1 {
+        true => io::stderr().write_all(text.as_bytes()),
+        false => io::stdout().write_all(text.as_bytes()).and_then(|_| io::stdout().flush()),
+    };
+    match result {
+        Ok(()) => efi::Status::SUCCESS,
+        Err(_) => efi::Status::DEVICE_ERROR,
+    }
+}
+
+extern "efiapi" fn test_string(_this: *mut simple_text_output::Protocol, string: *mut efi::Char16) -> efi::Status {
+    // SAFETY: the caller passes a null terminated string.
+    match unsafe { read_string(string) } {
+        Some(string) if char::decode_utf16(string.iter().copied()).all(|c| c.is_ok()) => efi::Status::SUCCESS,
+        Some(_) => efi::Status::UNSUPPORTED,
+        None => efi::Status::INVALID_PARAMETER,
+    }
+}
+
+extern "efiapi" fn query_mode(
+    _this: *mut simple_text_output::Protocol,
+    mode_number: usize,
+    columns: *mut usize,
+    rows: *mut usize,
+) -> efi::Status {
+    if columns.is_null() || rows.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if mode_number != 0 {
+        return efi::Status::UNSUPPORTED;
+    }
+    // SAFETY: checked for null above.
+    unsafe {
+        *columns = COLUMNS;
+        *rows = ROWS;
+    }
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn set_mode(_this: *mut simple_text_output::Protocol, mode_number: usize) -> efi::Status {
+    match mode_number {
+        0 => efi::Status::SUCCESS,
+        _ => efi::Status::UNSUPPORTED,
+    }
+}
+
+extern "efiapi" fn set_attribute(this: *mut simple_text_output::Protocol, attribute: usize) -> efi::Status {
+    // SAFETY: `this` is one of the consoles built by `new_console`.
+    unsafe { (*(*this).mode).attribute = attribute as i32 };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn clear_screen(this: *mut simple_text_output::Protocol) -> efi::Status {
+    set_cursor_position(this, 0, 0)
+}
+
+extern "efiapi" fn set_cursor_position(
+    this: *mut simple_text_output::Protocol,
+    column: usize,
+    row: usize,
+) -> efi::Status {
+    if column >= COLUMNS || row >= ROWS {
+        return efi::Status::UNSUPPORTED;
+    }
+    // SAFETY: `this` is one of the consoles built by `new_console`.
+    unsafe {
+        (*(*this).mode).cursor_column = column as i32;
+        (*(*this).mode).cursor_row = row as i32;
+    }
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn enable_cursor(this: *mut simple_text_output::Protocol, visible: efi::Boolean) -> efi::Status {
+    // SAFETY: `this` is one of the consoles built by `new_console`.
+    unsafe { (*(*this).mode).cursor_visible = visible };
+    efi::Status::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_string() {
+        let (_, console) = stdout();
+        let mut text: Vec<u16> = "mu_host console test\r\n".encode_utf16().chain([0]).collect();
+        assert_eq!(output_string(console, text.as_mut_ptr()), efi::Status::SUCCESS);
+        assert_eq!(output_string(console, std::ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+
+        let mut unpaired = [0xD800u16, 0];
+        assert_eq!(test_string(console, unpaired.as_mut_ptr()), efi::Status::UNSUPPORTED);
+
+        let (mut columns, mut rows) = (0, 0);
+        assert_eq!(query_mode(console, 0, &mut columns, &mut rows), efi::Status::SUCCESS);
+        assert_eq!((columns, rows), (COLUMNS, ROWS));
+    }
+}
@@ -0,0 +1,328 @@
+//! Events, timers and task priority levels.
+//!
+//! There is no timer interrupt on the host. Timers are checked whenever a component stalls, waits for or
+//! checks an event, and the notification functions of expired timers run at that point.
+use std::{
+    collections::BTreeMap,
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use r_efi::efi;
+
+use crate::lock;
+
+struct Event {
+    event_type: u32,
+    notify: Option<efi::EventNotify>,
+    context: usize,
+    group: Option<efi::Guid>,
+    signaled: bool,
+    timer: Option<Timer>,
+}
+
+struct Timer {
+    deadline: Instant,
+    period: Option<Duration>,
+}
+
+static EVENTS: Mutex<BTreeMap<usize, Event>> = Mutex::new(BTreeMap::new());
+static NEXT_EVENT: AtomicUsize = AtomicUsize::new(1);
+static TPL: AtomicUsize = AtomicUsize::new(efi::TPL_APPLICATION);
+
+/// How long `wait_for_event` sleeps at most before checking its events again.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+pub(crate) extern "efiapi" fn raise_tpl(new_tpl: efi::Tpl) -> efi::Tpl {
+    TPL.swap(new_tpl, Ordering::SeqCst)
+}
+
+pub(crate) extern "efiapi" fn restore_tpl(old_tpl: efi::Tpl) {
+    TPL.store(old_tpl, Ordering::SeqCst);
+}
+
+pub(crate) extern "efiapi" fn create_event(
+    event_type: u32,
+    notify_tpl: efi::Tpl,
+    notify_function: Option<efi::EventNotify>,
+    notify_context: *mut c_void,
+    event: *mut efi::Event,
+) -> efi::Status {
+    let group = match event_type {
+        efi::EVT_SIGNAL_EXIT_BOOT_SERVICES => Some(efi::EVENT_GROUP_EXIT_BOOT_SERVICES),
+        efi::EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE => Some(efi::EVENT_GROUP_VIRTUAL_ADDRESS_CHANGE),
+        _ => None,
+    };
+    create(event_type, notify_tpl, notify_function, notify_context, group, event)
+}
+
+pub(crate) extern "efiapi" fn create_event_ex(
+    event_type: u32,
+    notify_tpl: efi::Tpl,
+    notify_function: Option<efi::EventNotify>,
+    notify_context: *const c_void,
+    event_group: *const efi::Guid,
+    event: *mut efi::Event,
+) -> efi::Status {
+    if matches!(event_type, efi::EVT_SIGNAL_EXIT_BOOT_SERVICES | efi::EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: the group is either null or points to a GUID.
+    let group = unsafe { event_group.as_ref() }.copied();
+    create(event_type, notify_tpl, notify_function, notify_context as *mut c_void, group, event)
+}
+
+fn create(
+    event_type: u32,
+    notify_tpl: efi::Tpl,
+    notify: Option<efi::EventNotify>,
+    context: *mut c_void,
+    group: Option<efi::Guid>,
+    event: *mut efi::Event,
+) -> efi::Status {
+    if event.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let notify_type = event_type & (efi::EVT_NOTIFY_SIGNAL | efi::EVT_NOTIFY_WAIT);
+    if notify_type == efi::EVT_NOTIFY_SIGNAL | efi::EVT_NOTIFY_WAIT {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if notify_type != 0
+        && (notify.is_none() || notify_tpl <= efi::TPL_APPLICATION || notify_tpl > efi::TPL_HIGH_LEVEL)
+    {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let id = NEXT_EVENT.fetch_add(1, Ordering::SeqCst);
+    lock(&EVENTS).insert(
+        id,
+        Event { event_type, notify, context: context as usize, group, signaled: false, timer: None },
+    );
+    // SAFETY: checked for null above.
+    unsafe { *event = id as efi::Event };
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn set_timer(event: efi::Event, timer_type: efi::TimerDelay, trigger_time: u64) -> efi::Status {
+    let mut events = lock(&EVENTS);
+    let Some(event) = events.get_mut(&(event as usize)) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if event.event_type & efi::EVT_TIMER == 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // The trigger time is in units of 100ns.
+    let delay = Duration::from_nanos(trigger_time.saturating_mul(100));
+    event.timer = match timer_type {
+        efi::TIMER_CANCEL => None,
+        efi::TIMER_RELATIVE => Some(Timer { deadline: Instant::now() + delay, period: None }),
+        efi::TIMER_PERIODIC => Some(Timer { deadline: Instant::now() + delay, period: Some(delay) }),
+        _ => return efi::Status::INVALID_PARAMETER,
+    };
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn signal_event(event: efi::Event) -> efi::Status {
+    if !lock(&EVENTS).contains_key(&(event as usize)) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    signal(event as usize);
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn close_event(event: efi::Event) -> efi::Status {
+    match lock(&EVENTS).remove(&(event as usize)) {
+        Some(_) => efi::Status::SUCCESS,
+        None => efi::Status::INVALID_PARAMETER,
+    }
+}
+
+pub(crate) extern "efiapi" fn check_event(event: efi::Event) -> efi::Status {
+    let id = event as usize;
+    let notify = match lock(&EVENTS).get(&id) {
+        Some(event) if event.event_type & efi::EVT_NOTIFY_SIGNAL != 0 => return efi::Status::INVALID_PARAMETER,
+        Some(event) if event.event_type & efi::EVT_NOTIFY_WAIT != 0 => event.notify.map(|f| (f, event.context)),
+        Some(_) => None,
+        None => return efi::Status::INVALID_PARAMETER,
+    };
+
+    fire_timers();
+    if take_signal(id) {
+        return efi::Status::SUCCESS;
+    }
+
+    // A wait event's notification function is what signals it.
+    if let Some((notify, context)) = notify {
+        notify(event, context as *mut c_void);
+        if take_signal(id) {
+            return efi::Status::SUCCESS;
+        }
+    }
+    efi::Status::NOT_READY
+}
+
+pub(crate) extern "efiapi" fn wait_for_event(
+    number_of_events: usize,
+    event: *mut efi::Event,
+    index: *mut usize,
+) -> efi::Status {
+    if number_of_events == 0 || event.is_null() || index.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: the caller passes an array of `number_of_events` events.
+    let events = unsafe { std::slice::from_raw_parts(event, number_of_events) };
+
+    loop {
+        for (i, event) in events.iter().enumerate() {
+            let status = check_event(*event);
+            if status != efi::Status::NOT_READY {
+                // SAFETY: checked for null above.
+                unsafe { *index = i };
+                return status;
+            }
+        }
+
+        let sleep = next_deadline()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()).min(POLL_INTERVAL))
+            .unwrap_or(POLL_INTERVAL);
+        thread::sleep(sleep);
+    }
+}
+
+/// Sleeps for `microseconds`, then runs the notifications of any timer that expired meanwhile.
+pub(crate) extern "efiapi" fn stall(microseconds: usize) -> efi::Status {
+    thread::sleep(Duration::from_micros(microseconds as u64));
+    fire_timers();
+    efi::Status::SUCCESS
+}
+
+/// Signals every event in `group`.
+pub(crate) fn signal_group(group: efi::Guid) {
+    let members: Vec<usize> =
+        lock(&EVENTS).iter().filter(|(_, event)| event.group == Some(group)).map(|(id, _)| *id).collect();
+    for id in members {
+        signal_one(id);
+    }
+}
+
+/// Signals `id`, or every event in its group if it is part of one.
+pub(crate) fn signal(id: usize) {
+    let group = lock(&EVENTS).get(&id).and_then(|event| event.group);
+    match group {
+        Some(group) => signal_group(group),
+        None => signal_one(id),
+    }
+}
+
+fn signal_one(id: usize) {
+    let notify = {
+        let mut events = lock(&EVENTS);
+        let Some(event) = events.get_mut(&id) else {
+            return;
+        };
+        if event.event_type & efi::EVT_NOTIFY_SIGNAL != 0 {
+            event.notify.map(|f| (f, event.context))
+        } else {
+            event.signaled = true;
+            None
+        }
+    };
+
+    // The lock is released first, as notification functions call back into the services.
+    if let Some((notify, context)) = notify {
+        notify(id as efi::Event, context as *mut c_void);
+    }
+}
+
+fn take_signal(id: usize) -> bool {
+    lock(&EVENTS).get_mut(&id).map(|event| std::mem::take(&mut event.signaled)).unwrap_or(false)
+}
+
+fn next_deadline() -> Option<Instant> {
+    lock(&EVENTS).values().filter_map(|event| event.timer.as_ref()).map(|timer| timer.deadline).min()
+}
+
+/// Signals every timer event whose deadline has passed, rearming periodic ones.
+fn fire_timers() {
+    let now = Instant::now();
+    let mut expired = Vec::new();
+    for (id, event) in lock(&EVENTS).iter_mut() {
+        let Some(timer) = event.timer.as_mut().filter(|timer| timer.deadline <= now) else {
+            continue;
+        };
+        match timer.period {
+            Some(period) => timer.deadline = now + period,
+            None => event.timer = None,
+        }
+        expired.push(*id);
+    }
+
+    for id in expired {
+        signal_one(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+
+    extern "efiapi" fn count_notify(_event: efi::Event, context: *mut c_void) {
+        NOTIFIED.fetch_add(context as usize, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_timer_signals_wait() {
+        let mut event = ptr::null_mut();
+        assert_eq!(create_event(efi::EVT_TIMER, 0, None, ptr::null_mut(), &mut event), efi::Status::SUCCESS);
+        assert_eq!(check_event(event), efi::Status::NOT_READY);
+
+        // 1ms
+        assert_eq!(set_timer(event, efi::TIMER_RELATIVE, 10_000), efi::Status::SUCCESS);
+        let mut index = usize::MAX;
+        assert_eq!(wait_for_event(1, &mut event, &mut index), efi::Status::SUCCESS);
+        assert_eq!(index, 0);
+        assert_eq!(check_event(event), efi::Status::NOT_READY);
+
+        assert_eq!(close_event(event), efi::Status::SUCCESS);
+        assert_eq!(check_event(event), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_group_notifications() {
+        const GROUP: efi::Guid =
+            efi::Guid::from_fields(0x1e2d3c4b, 0x5a69, 0x7887, 0x96, 0xa5, &[0xb4, 0xc3, 0xd2, 0xe1, 0xf0, 0x01]);
+        let mut events = [ptr::null_mut(); 2];
+        for (event, context) in events.iter_mut().zip([1usize, 2]) {
+            let status = create_event_ex(
+                efi::EVT_NOTIFY_SIGNAL,
+                efi::TPL_CALLBACK,
+                Some(count_notify),
+                context as *const c_void,
+                &GROUP,
+                event,
+            );
+            assert_eq!(status, efi::Status::SUCCESS);
+        }
+
+        assert_eq!(signal_event(events[0]), efi::Status::SUCCESS);
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 3);
+        assert_eq!(check_event(events[1]), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_notify_requires_function() {
+        let mut event = ptr::null_mut();
+        let status = create_event(efi::EVT_NOTIFY_SIGNAL, efi::TPL_CALLBACK, None, ptr::null_mut(), &mut event);
+        assert_eq!(status, efi::Status::INVALID_PARAMETER);
+    }
+}
@@ -0,0 +1,170 @@
+//! An in-process UEFI environment for running components on the host.
+//!
+//! [`system_table`] builds a real `efi::SystemTable`. Its Boot Services allocate pool and pages from the
+//! host heap and keep a handle and protocol database, events and timers. Its Runtime Services keep
+//! variables in memory. ConOut writes to stdout and StdErr to stderr. [`image_handle`] is a handle
+//! carrying a Loaded Image Protocol that points at that system table, so components can be run and
+//! debugged end-to-end with their regular entry point:
+//!
+//! ```ignore
+//! let status = efi_main(mu_host::image_handle(), mu_host::system_table());
+//! ```
+//!
+//! The services are reentrant, as component code runs from event notifications and driver binding
+//! calls, but they do not model TPLs: notification functions run as soon as their event is signaled.
+mod boot;
+mod console;
+mod event;
+mod memory;
+mod protocol;
+mod runtime;
+mod variable;
+
+use std::{
+    ffi::c_void,
+    mem,
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+use r_efi::{efi, protocols::loaded_image};
+
+/// The address of the system table, built on first use.
+static SYSTEM_TABLE: OnceLock<usize> = OnceLock::new();
+static IMAGE_HANDLE: OnceLock<usize> = OnceLock::new();
+
+/// Returns the host system table, building it on first use.
+///
+/// The table, and everything it references, lives until the process exits.
+pub fn system_table() -> *mut efi::SystemTable {
+    *SYSTEM_TABLE.get_or_init(build_system_table) as *mut efi::SystemTable
+}
+
+/// Returns the handle of the image running on the host.
+///
+/// The handle carries a Loaded Image Protocol whose system table is [`system_table`].
+pub fn image_handle() -> efi::Handle {
+    *IMAGE_HANDLE.get_or_init(|| {
+        let loaded_image = Box::leak(Box::new(loaded_image::Protocol {
+            revision: loaded_image::REVISION,
+            parent_handle: std::ptr::null_mut(),
+            system_table: system_table(),
+            device_handle: std::ptr::null_mut(),
+            file_path: std::ptr::null_mut(),
+            reserved: std::ptr::null_mut(),
+            load_options_size: 0,
+            load_options: std::ptr::null_mut(),
+            image_base: std::ptr::null_mut(),
+            image_size: 0,
+            image_code_type: efi::LOADER_CODE,
+            image_data_type: efi::LOADER_DATA,
+            unload: boot::unload_unsupported,
+        }));
+        protocol::install(0, loaded_image::PROTOCOL_GUID, loaded_image as *mut _ as *mut c_void)
+            .expect("a new handle accepts any protocol")
+    }) as efi::Handle
+}
+
+fn build_system_table() -> usize {
+    let firmware_vendor: Vec<u16> = "mu_host".encode_utf16().chain([0]).collect();
+    let (console_out_handle, con_out) = console::stdout();
+    let (standard_error_handle, std_err) = console::stderr();
+
+    let table = Box::leak(Box::new(efi::SystemTable {
+        hdr: table_header(efi::SYSTEM_TABLE_SIGNATURE, efi::SYSTEM_TABLE_REVISION, mem::size_of::<efi::SystemTable>()),
+        firmware_vendor: Box::leak(firmware_vendor.into_boxed_slice()).as_mut_ptr(),
+        firmware_revision: 0,
+        console_in_handle: std::ptr::null_mut(),
+        con_in: std::ptr::null_mut(),
+        console_out_handle,
+        con_out,
+        standard_error_handle,
+        std_err,
+        runtime_services: runtime::runtime_services(),
+        boot_services: boot::boot_services(),
+        number_of_table_entries: 0,
+        configuration_table: std::ptr::null_mut(),
+    }));
+    update_crc32(&mut table.hdr);
+    table as *mut efi::SystemTable as usize
+}
+
+/// A table header whose CRC32 still has to be filled in with [`update_crc32`].
+fn table_header(signature: u64, revision: u32, header_size: usize) -> efi::TableHeader {
+    efi::TableHeader { signature, revision, header_size: header_size as u32, crc32: 0, reserved: 0 }
+}
+
+/// Recomputes the CRC32 of the table `hdr` is embedded in.
+fn update_crc32(hdr: &mut efi::TableHeader) {
+    hdr.crc32 = 0;
+    // SAFETY: every header built by this crate is embedded in a table of `header_size` bytes.
+    let table = unsafe { std::slice::from_raw_parts(hdr as *const _ as *const u8, hdr.header_size as usize) };
+    hdr.crc32 = crc32(table);
+}
+
+/// The CRC32 used by UEFI tables and the CalculateCrc32 service.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Locks `mutex`, ignoring poisoning: a panicking component must not take the services down with it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    /// Checks the CRC32 of the table `hdr` is embedded in.
+    pub(crate) fn crc32_is_valid(hdr: &efi::TableHeader) -> bool {
+        let mut table = unsafe { std::slice::from_raw_parts(hdr as *const _ as *const u8, hdr.header_size as usize) }.to_vec();
+        table[16..20].fill(0);
+        crc32(&table) == hdr.crc32
+    }
+
+    #[test]
+    fn test_system_table_is_valid() {
+        let st = unsafe { &*system_table() };
+        assert_eq!(st.hdr.signature, efi::SYSTEM_TABLE_SIGNATURE);
+        assert!(crc32_is_valid(&st.hdr));
+
+        let bs = unsafe { &*st.boot_services };
+        assert_eq!(bs.hdr.signature, efi::BOOT_SERVICES_SIGNATURE);
+        assert!(crc32_is_valid(&bs.hdr));
+
+        let rt = unsafe { &*st.runtime_services };
+        assert_eq!(rt.hdr.signature, efi::RUNTIME_SERVICES_SIGNATURE);
+        assert!(crc32_is_valid(&rt.hdr));
+
+        assert!(!st.con_out.is_null());
+        assert!(!st.std_err.is_null());
+    }
+
+    #[test]
+    fn test_image_handle_has_loaded_image() {
+        let st = unsafe { &*system_table() };
+        let mut interface: *mut c_void = std::ptr::null_mut();
+        let status = unsafe {
+            ((*st.boot_services).handle_protocol)(
+                image_handle(),
+                &loaded_image::PROTOCOL_GUID as *const _ as *mut _,
+                &mut interface,
+            )
+        };
+        assert_eq!(status, efi::Status::SUCCESS);
+        let loaded_image = unsafe { &*(interface as *const loaded_image::Protocol) };
+        assert_eq!(loaded_image.system_table, system_table());
+    }
+}
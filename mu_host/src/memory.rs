@@ -0,0 +1,220 @@
+//! Pool and page allocations, backed by the host heap.
+use std::{
+    alloc::{self, Layout},
+    collections::BTreeMap,
+    ffi::c_void,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use r_efi::efi;
+
+use crate::lock;
+
+const PAGE_SIZE: usize = 0x1000;
+/// Pool allocations are 8-byte aligned, like the firmware's.
+const POOL_ALIGNMENT: usize = 8;
+
+/// Base address of each pool allocation, mapped to its layout.
+static POOL: Mutex<BTreeMap<usize, Layout>> = Mutex::new(BTreeMap::new());
+/// Base address of each page allocation, mapped to its memory type and number of pages.
+static PAGES: Mutex<BTreeMap<usize, (efi::MemoryType, usize)>> = Mutex::new(BTreeMap::new());
+/// Changes whenever the memory map does.
+static MAP_KEY: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocates `size` bytes of pool, for services that hand buffers back to the caller.
+pub(crate) fn allocate(size: usize) -> Option<*mut c_void> {
+    let layout = Layout::from_size_align(size.max(1), POOL_ALIGNMENT).ok()?;
+    // SAFETY: the layout has a non-zero size.
+    let buffer = unsafe { alloc::alloc(layout) };
+    if buffer.is_null() {
+        return None;
+    }
+    lock(&POOL).insert(buffer as usize, layout);
+    Some(buffer as *mut c_void)
+}
+
+pub(crate) extern "efiapi" fn allocate_pool(
+    memory_type: efi::MemoryType,
+    size: usize,
+    buffer: *mut *mut c_void,
+) -> efi::Status {
+    if buffer.is_null() || memory_type == efi::CONVENTIONAL_MEMORY {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    match allocate(size) {
+        Some(allocation) => {
+            // SAFETY: checked for null above.
+            unsafe { *buffer = allocation };
+            efi::Status::SUCCESS
+        }
+        None => efi::Status::OUT_OF_RESOURCES,
+    }
+}
+
+pub(crate) extern "efiapi" fn free_pool(buffer: *mut c_void) -> efi::Status {
+    match lock(&POOL).remove(&(buffer as usize)) {
+        // SAFETY: the buffer was allocated with this layout by `allocate`.
+        Some(layout) => unsafe { alloc::dealloc(buffer as *mut u8, layout) },
+        None => return efi::Status::INVALID_PARAMETER,
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn allocate_pages(
+    allocate_type: efi::AllocateType,
+    memory_type: efi::MemoryType,
+    pages: usize,
+    memory: *mut efi::PhysicalAddress,
+) -> efi::Status {
+    if memory.is_null() || pages == 0 || memory_type == efi::CONVENTIONAL_MEMORY {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let Some(layout) = pages.checked_mul(PAGE_SIZE).and_then(|size| Layout::from_size_align(size, PAGE_SIZE).ok())
+    else {
+        return efi::Status::OUT_OF_RESOURCES;
+    };
+    // SAFETY: checked for null above.
+    let max_address = unsafe { *memory };
+
+    let base = match allocate_type {
+        efi::ALLOCATE_ANY_PAGES | efi::ALLOCATE_MAX_ADDRESS => {
+            // SAFETY: the layout has a non-zero size.
+            unsafe { alloc::alloc(layout) }
+        }
+        // The host heap cannot be asked for memory at a given address.
+        efi::ALLOCATE_ADDRESS => return efi::Status::NOT_FOUND,
+        _ => return efi::Status::INVALID_PARAMETER,
+    };
+    if base.is_null() {
+        return efi::Status::OUT_OF_RESOURCES;
+    }
+    if allocate_type == efi::ALLOCATE_MAX_ADDRESS && (base as u64 + layout.size() as u64 - 1) > max_address {
+        // SAFETY: allocated with this layout just above.
+        unsafe { alloc::dealloc(base, layout) };
+        return efi::Status::NOT_FOUND;
+    }
+
+    lock(&PAGES).insert(base as usize, (memory_type, pages));
+    MAP_KEY.fetch_add(1, Ordering::SeqCst);
+    // SAFETY: checked for null above.
+    unsafe { *memory = base as efi::PhysicalAddress };
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn free_pages(memory: efi::PhysicalAddress, pages: usize) -> efi::Status {
+    if memory as usize % PAGE_SIZE != 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let mut allocations = lock(&PAGES);
+    match allocations.get(&(memory as usize)) {
+        Some((_, allocated)) if *allocated == pages => {}
+        Some(_) => return efi::Status::INVALID_PARAMETER,
+        None => return efi::Status::NOT_FOUND,
+    }
+    allocations.remove(&(memory as usize));
+    MAP_KEY.fetch_add(1, Ordering::SeqCst);
+
+    // SAFETY: the pages were allocated with this layout by `allocate_pages`.
+    unsafe { alloc::dealloc(memory as *mut u8, Layout::from_size_align_unchecked(pages * PAGE_SIZE, PAGE_SIZE)) };
+    efi::Status::SUCCESS
+}
+
+/// Describes the pages allocated through AllocatePages. Host memory that was not handed out by these
+/// services is not part of the map.
+pub(crate) extern "efiapi" fn get_memory_map(
+    memory_map_size: *mut usize,
+    memory_map: *mut efi::MemoryDescriptor,
+    map_key: *mut usize,
+    descriptor_size: *mut usize,
+    descriptor_version: *mut u32,
+) -> efi::Status {
+    if memory_map_size.is_null() || map_key.is_null() || descriptor_size.is_null() || descriptor_version.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let allocations = lock(&PAGES);
+    let required = allocations.len() * mem::size_of::<efi::MemoryDescriptor>();
+    // SAFETY: checked for null above.
+    unsafe {
+        *descriptor_size = mem::size_of::<efi::MemoryDescriptor>();
+        *descriptor_version = efi::MEMORY_DESCRIPTOR_VERSION;
+        if *memory_map_size < required {
+            *memory_map_size = required;
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+    }
+    if memory_map.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    for (index, (base, (memory_type, pages))) in allocations.iter().enumerate() {
+        let descriptor = efi::MemoryDescriptor {
+            r#type: *memory_type,
+            physical_start: *base as u64,
+            virtual_start: *base as u64,
+            number_of_pages: *pages as u64,
+            attribute: 0,
+        };
+        // SAFETY: the caller's buffer holds at least `required` bytes.
+        unsafe { memory_map.add(index).write(descriptor) };
+    }
+    // SAFETY: checked for null above.
+    unsafe {
+        *memory_map_size = required;
+        *map_key = MAP_KEY.load(Ordering::SeqCst);
+    }
+    efi::Status::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_pool_round_trip() {
+        let mut buffer = ptr::null_mut();
+        assert_eq!(allocate_pool(efi::BOOT_SERVICES_DATA, 24, &mut buffer), efi::Status::SUCCESS);
+        assert_eq!(buffer as usize % POOL_ALIGNMENT, 0);
+        unsafe { ptr::write_bytes(buffer as *mut u8, 0xAA, 24) };
+
+        assert_eq!(free_pool(buffer), efi::Status::SUCCESS);
+        assert_eq!(free_pool(buffer), efi::Status::INVALID_PARAMETER);
+        assert_eq!(allocate_pool(efi::CONVENTIONAL_MEMORY, 24, &mut buffer), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_pages_round_trip() {
+        let mut memory = 0;
+        assert_eq!(allocate_pages(efi::ALLOCATE_ANY_PAGES, efi::BOOT_SERVICES_DATA, 2, &mut memory), efi::Status::SUCCESS);
+        assert_eq!(memory as usize % PAGE_SIZE, 0);
+
+        let mut size = 0;
+        let (mut key, mut descriptor_size, mut version) = (0, 0, 0);
+        let status = get_memory_map(&mut size, ptr::null_mut(), &mut key, &mut descriptor_size, &mut version);
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        let mut map = vec![unsafe { mem::zeroed::<efi::MemoryDescriptor>() }; size / descriptor_size];
+        let status = get_memory_map(&mut size, map.as_mut_ptr(), &mut key, &mut descriptor_size, &mut version);
+        assert_eq!(status, efi::Status::SUCCESS);
+        assert!(map.iter().any(|d| d.physical_start == memory && d.number_of_pages == 2));
+
+        assert_eq!(free_pages(memory, 1), efi::Status::INVALID_PARAMETER);
+        assert_eq!(free_pages(memory, 2), efi::Status::SUCCESS);
+        assert_eq!(free_pages(memory, 2), efi::Status::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_pages_below_max_address() {
+        let mut memory = 0xFFF;
+        let status = allocate_pages(efi::ALLOCATE_MAX_ADDRESS, efi::BOOT_SERVICES_DATA, 1, &mut memory);
+        assert_eq!(status, efi::Status::NOT_FOUND);
+        assert_eq!(allocate_pages(efi::ALLOCATE_ADDRESS, efi::BOOT_SERVICES_DATA, 1, &mut memory), efi::Status::NOT_FOUND);
+    }
+}
@@ -0,0 +1,719 @@
+//! The handle and protocol database, and the UEFI Driver Model services built on it.
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ffi::c_void,
+    mem, ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use r_efi::{efi, protocols::driver_binding};
+
+use crate::{event, lock, memory};
+
+struct Interface {
+    guid: efi::Guid,
+    interface: usize,
+    opens: Vec<Open>,
+}
+
+#[derive(Clone, Copy)]
+struct Open {
+    agent: usize,
+    controller: usize,
+    attributes: u32,
+    count: u32,
+}
+
+/// A RegisterProtocolNotify registration.
+struct Notify {
+    guid: efi::Guid,
+    event: usize,
+    /// Handles the protocol was installed on, not yet returned by a locate service.
+    pending: VecDeque<usize>,
+}
+
+/// The protocols of each handle, in the order they were installed.
+static HANDLES: Mutex<BTreeMap<usize, Vec<Interface>>> = Mutex::new(BTreeMap::new());
+static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(1);
+/// Registrations, keyed by their position plus one.
+static NOTIFIES: Mutex<Vec<Notify>> = Mutex::new(Vec::new());
+
+const DRIVER_OPEN: u32 = efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE;
+
+/// Installs `interface` on `handle`, or on a new handle if `handle` is zero, and returns the handle.
+pub(crate) fn install(handle: usize, guid: efi::Guid, interface: *mut c_void) -> Result<usize, efi::Status> {
+    let mut handles = lock(&HANDLES);
+    let handle = match handle {
+        0 => NEXT_HANDLE.fetch_add(1, Ordering::SeqCst),
+        handle if handles.contains_key(&handle) => handle,
+        _ => return Err(efi::Status::INVALID_PARAMETER),
+    };
+
+    let interfaces = handles.entry(handle).or_default();
+    if interfaces.iter().any(|i| i.guid == guid) {
+        return Err(efi::Status::INVALID_PARAMETER);
+    }
+    interfaces.push(Interface { guid, interface: interface as usize, opens: Vec::new() });
+    drop(handles);
+
+    notify(guid, handle);
+    Ok(handle)
+}
+
+/// Queues `handle` for every registration on `guid`, then signals their events.
+fn notify(guid: efi::Guid, handle: usize) {
+    let events: Vec<usize> = lock(&NOTIFIES)
+        .iter_mut()
+        .filter(|notify| notify.guid == guid)
+        .map(|notify| {
+            notify.pending.push_back(handle);
+            notify.event
+        })
+        .collect();
+    for id in events {
+        event::signal(id);
+    }
+}
+
+pub(crate) extern "efiapi" fn install_protocol_interface(
+    handle: *mut efi::Handle,
+    protocol: *mut efi::Guid,
+    interface_type: efi::InterfaceType,
+    interface: *mut c_void,
+) -> efi::Status {
+    if handle.is_null() || protocol.is_null() || interface_type != efi::NATIVE_INTERFACE {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: checked for null above.
+    match install(unsafe { *handle } as usize, unsafe { *protocol }, interface) {
+        Ok(installed) => {
+            // SAFETY: checked for null above.
+            unsafe { *handle = installed as efi::Handle };
+            efi::Status::SUCCESS
+        }
+        Err(status) => status,
+    }
+}
+
+/// Installs a single protocol. The services are variadic in C, but can only be called with one
+/// protocol and interface pair through the `r-efi` definition.
+pub(crate) extern "efiapi" fn install_multiple_protocol_interfaces(
+    handle: *mut efi::Handle,
+    protocol: *mut c_void,
+    interface: *mut c_void,
+) -> efi::Status {
+    install_protocol_interface(handle, protocol as *mut efi::Guid, efi::NATIVE_INTERFACE, interface)
+}
+
+pub(crate) extern "efiapi" fn reinstall_protocol_interface(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    old_interface: *mut c_void,
+    new_interface: *mut c_void,
+) -> efi::Status {
+    // SAFETY: the protocol is either null or points to a GUID.
+    let Some(guid) = (unsafe { protocol.as_ref() }).copied() else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    {
+        let mut handles = lock(&HANDLES);
+        let Some(interfaces) = handles.get_mut(&(handle as usize)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(installed) = interfaces.iter_mut().find(|i| i.guid == guid && i.interface == old_interface as usize)
+        else {
+            return efi::Status::NOT_FOUND;
+        };
+        installed.interface = new_interface as usize;
+    }
+
+    notify(guid, handle as usize);
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn uninstall_protocol_interface(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    interface: *mut c_void,
+) -> efi::Status {
+    // SAFETY: the protocol is either null or points to a GUID.
+    let Some(guid) = (unsafe { protocol.as_ref() }).copied() else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let mut handles = lock(&HANDLES);
+    let Some(interfaces) = handles.get_mut(&(handle as usize)) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    let Some(index) = interfaces.iter().position(|i| i.guid == guid && i.interface == interface as usize) else {
+        return efi::Status::NOT_FOUND;
+    };
+    // Drivers have to be stopped with DisconnectController first.
+    if interfaces[index].opens.iter().any(|open| open.attributes & DRIVER_OPEN != 0) {
+        return efi::Status::ACCESS_DENIED;
+    }
+
+    interfaces.remove(index);
+    if interfaces.is_empty() {
+        handles.remove(&(handle as usize));
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn uninstall_multiple_protocol_interfaces(
+    handle: efi::Handle,
+    protocol: *mut c_void,
+    interface: *mut c_void,
+) -> efi::Status {
+    uninstall_protocol_interface(handle, protocol as *mut efi::Guid, interface)
+}
+
+pub(crate) extern "efiapi" fn handle_protocol(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    interface: *mut *mut c_void,
+) -> efi::Status {
+    open_protocol(
+        handle,
+        protocol,
+        interface,
+        crate::image_handle(),
+        ptr::null_mut(),
+        efi::OPEN_PROTOCOL_BY_HANDLE_PROTOCOL,
+    )
+}
+
+pub(crate) extern "efiapi" fn open_protocol(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    interface: *mut *mut c_void,
+    agent_handle: efi::Handle,
+    controller_handle: efi::Handle,
+    attributes: u32,
+) -> efi::Status {
+    let test = attributes == efi::OPEN_PROTOCOL_TEST_PROTOCOL;
+    if protocol.is_null() || (!test && interface.is_null()) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let needs_controller = efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER | efi::OPEN_PROTOCOL_BY_DRIVER;
+    if (attributes & (needs_controller | efi::OPEN_PROTOCOL_EXCLUSIVE) != 0 && agent_handle.is_null())
+        || (attributes & needs_controller != 0 && controller_handle.is_null())
+    {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if !test {
+        // SAFETY: checked for null above.
+        unsafe { *interface = ptr::null_mut() };
+    }
+
+    // SAFETY: checked for null above.
+    let guid = unsafe { *protocol };
+    let mut handles = lock(&HANDLES);
+    let Some(interfaces) = handles.get_mut(&(handle as usize)) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    let Some(installed) = interfaces.iter_mut().find(|i| i.guid == guid) else {
+        return efi::Status::UNSUPPORTED;
+    };
+    if test {
+        return efi::Status::SUCCESS;
+    }
+
+    let (agent, controller) = (agent_handle as usize, controller_handle as usize);
+    if attributes & DRIVER_OPEN != 0 {
+        if let Some(open) = installed.opens.iter().find(|open| open.attributes & DRIVER_OPEN != 0) {
+            if open.agent != agent || open.attributes != attributes {
+                return efi::Status::ACCESS_DENIED;
+            }
+            // SAFETY: checked for null above.
+            unsafe { *interface = installed.interface as *mut c_void };
+            return efi::Status::ALREADY_STARTED;
+        }
+    }
+
+    match installed
+        .opens
+        .iter_mut()
+        .find(|open| open.agent == agent && open.controller == controller && open.attributes == attributes)
+    {
+        Some(open) => open.count += 1,
+        None => installed.opens.push(Open { agent, controller, attributes, count: 1 }),
+    }
+    // SAFETY: checked for null above.
+    unsafe { *interface = installed.interface as *mut c_void };
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn close_protocol(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    agent_handle: efi::Handle,
+    controller_handle: efi::Handle,
+) -> efi::Status {
+    // SAFETY: the protocol is either null or points to a GUID.
+    let Some(guid) = (unsafe { protocol.as_ref() }).copied() else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let mut handles = lock(&HANDLES);
+    let Some(interfaces) = handles.get_mut(&(handle as usize)) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    let Some(installed) = interfaces.iter_mut().find(|i| i.guid == guid) else {
+        return efi::Status::NOT_FOUND;
+    };
+
+    let opened = installed.opens.len();
+    installed
+        .opens
+        .retain(|open| open.agent != agent_handle as usize || open.controller != controller_handle as usize);
+    if installed.opens.len() == opened {
+        return efi::Status::NOT_FOUND;
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn open_protocol_information(
+    handle: efi::Handle,
+    protocol: *mut efi::Guid,
+    entry_buffer: *mut *mut efi::OpenProtocolInformationEntry,
+    entry_count: *mut usize,
+) -> efi::Status {
+    if protocol.is_null() || entry_buffer.is_null() || entry_count.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: checked for null above.
+    let guid = unsafe { *protocol };
+    let handles = lock(&HANDLES);
+    let Some(installed) = handles.get(&(handle as usize)).and_then(|i| i.iter().find(|i| i.guid == guid)) else {
+        return efi::Status::NOT_FOUND;
+    };
+
+    let entries: Vec<efi::OpenProtocolInformationEntry> = installed
+        .opens
+        .iter()
+        .map(|open| efi::OpenProtocolInformationEntry {
+            agent_handle: open.agent as efi::Handle,
+            controller_handle: open.controller as efi::Handle,
+            attributes: open.attributes,
+            open_count: open.count,
+        })
+        .collect();
+    // SAFETY: checked for null above.
+    unsafe { copy_to_pool(&entries, entry_buffer, entry_count) }
+}
+
+pub(crate) extern "efiapi" fn protocols_per_handle(
+    handle: efi::Handle,
+    protocol_buffer: *mut *mut *mut efi::Guid,
+    protocol_buffer_count: *mut usize,
+) -> efi::Status {
+    if protocol_buffer.is_null() || protocol_buffer_count.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let handles = lock(&HANDLES);
+    let Some(interfaces) = handles.get(&(handle as usize)) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    // The GUIDs are stored right after the array pointing at them, so a single FreePool releases both.
+    let count = interfaces.len();
+    let pointers_size = count * mem::size_of::<*mut efi::Guid>();
+    let Some(buffer) = memory::allocate(pointers_size + count * mem::size_of::<efi::Guid>()) else {
+        return efi::Status::OUT_OF_RESOURCES;
+    };
+    let pointers = buffer as *mut *mut efi::Guid;
+    // SAFETY: the buffer holds `count` pointers followed by `count` GUIDs, and both are 8-byte aligned at most.
+    unsafe {
+        let guids = (buffer as *mut u8).add(pointers_size) as *mut efi::Guid;
+        for (index, interface) in interfaces.iter().enumerate() {
+            guids.add(index).write(interface.guid);
+            pointers.add(index).write(guids.add(index));
+        }
+        *protocol_buffer = pointers;
+        *protocol_buffer_count = count;
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn register_protocol_notify(
+    protocol: *mut efi::Guid,
+    event: efi::Event,
+    registration: *mut *mut c_void,
+) -> efi::Status {
+    if protocol.is_null() || registration.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let mut notifies = lock(&NOTIFIES);
+    // SAFETY: checked for null above.
+    notifies.push(Notify { guid: unsafe { *protocol }, event: event as usize, pending: VecDeque::new() });
+    // SAFETY: checked for null above.
+    unsafe { *registration = notifies.len() as *mut c_void };
+    efi::Status::SUCCESS
+}
+
+/// Returns the handles matching a LocateHandle search.
+///
+/// A `BY_REGISTER_NOTIFY` search returns the next handle the registration has not seen yet, which is
+/// only consumed by [`consume_notify`].
+fn search(
+    search_type: efi::LocateSearchType,
+    protocol: *mut efi::Guid,
+    search_key: *mut c_void,
+) -> Result<Vec<usize>, efi::Status> {
+    let handles: Vec<usize> = match search_type {
+        efi::ALL_HANDLES => lock(&HANDLES).keys().copied().collect(),
+        efi::BY_PROTOCOL => {
+            // SAFETY: the protocol is either null or points to a GUID.
+            let guid = unsafe { protocol.as_ref() }.ok_or(efi::Status::INVALID_PARAMETER)?;
+            lock(&HANDLES)
+                .iter()
+                .filter(|(_, interfaces)| interfaces.iter().any(|i| i.guid == *guid))
+                .map(|(handle, _)| *handle)
+                .collect()
+        }
+        efi::BY_REGISTER_NOTIFY => {
+            let notifies = lock(&NOTIFIES);
+            let notify = (search_key as usize)
+                .checked_sub(1)
+                .and_then(|index| notifies.get(index))
+                .ok_or(efi::Status::INVALID_PARAMETER)?;
+            notify.pending.front().copied().into_iter().collect()
+        }
+        _ => return Err(efi::Status::INVALID_PARAMETER),
+    };
+
+    if handles.is_empty() {
+        return Err(efi::Status::NOT_FOUND);
+    }
+    Ok(handles)
+}
+
+/// Marks the next pending handle of a registration as seen.
+fn consume_notify(search_key: *mut c_void) -> Option<usize> {
+    let index = (search_key as usize).checked_sub(1)?;
+    lock(&NOTIFIES).get_mut(index)?.pending.pop_front()
+}
+
+pub(crate) extern "efiapi" fn locate_handle(
+    search_type: efi::LocateSearchType,
+    protocol: *mut efi::Guid,
+    search_key: *mut c_void,
+    buffer_size: *mut usize,
+    buffer: *mut efi::Handle,
+) -> efi::Status {
+    if buffer_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let handles = match search(search_type, protocol, search_key) {
+        Ok(handles) => handles,
+        Err(status) => return status,
+    };
+
+    let required = handles.len() * mem::size_of::<efi::Handle>();
+    // SAFETY: checked for null above.
+    unsafe {
+        if *buffer_size < required {
+            *buffer_size = required;
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        *buffer_size = required;
+    }
+    if buffer.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    for (index, handle) in handles.iter().enumerate() {
+        // SAFETY: the caller's buffer holds at least `required` bytes.
+        unsafe { buffer.add(index).write(*handle as efi::Handle) };
+    }
+    if search_type == efi::BY_REGISTER_NOTIFY {
+        consume_notify(search_key);
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn locate_handle_buffer(
+    search_type: efi::LocateSearchType,
+    protocol: *mut efi::Guid,
+    search_key: *mut c_void,
+    no_handles: *mut usize,
+    buffer: *mut *mut efi::Handle,
+) -> efi::Status {
+    if no_handles.is_null() || buffer.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let handles: Vec<efi::Handle> = match search(search_type, protocol, search_key) {
+        Ok(handles) => handles.into_iter().map(|handle| handle as efi::Handle).collect(),
+        Err(status) => return status,
+    };
+
+    // SAFETY: checked for null above.
+    let status = unsafe { copy_to_pool(&handles, buffer, no_handles) };
+    if status == efi::Status::SUCCESS && search_type == efi::BY_REGISTER_NOTIFY {
+        consume_notify(search_key);
+    }
+    status
+}
+
+pub(crate) extern "efiapi" fn locate_protocol(
+    protocol: *mut efi::Guid,
+    registration: *mut c_void,
+    interface: *mut *mut c_void,
+) -> efi::Status {
+    if protocol.is_null() || interface.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: checked for null above.
+    let guid = unsafe { *protocol };
+    // SAFETY: checked for null above.
+    unsafe { *interface = ptr::null_mut() };
+
+    let handle = match registration.is_null() {
+        true => None,
+        false => match consume_notify(registration) {
+            Some(handle) => Some(handle),
+            None => return efi::Status::NOT_FOUND,
+        },
+    };
+
+    let handles = lock(&HANDLES);
+    let found = handles
+        .iter()
+        .filter(|(h, _)| handle.map_or(true, |handle| handle == **h))
+        .find_map(|(_, interfaces)| interfaces.iter().find(|i| i.guid == guid));
+    match found {
+        Some(installed) => {
+            // SAFETY: checked for null above.
+            unsafe { *interface = installed.interface as *mut c_void };
+            efi::Status::SUCCESS
+        }
+        None => efi::Status::NOT_FOUND,
+    }
+}
+
+/// There are no device paths on the host.
+pub(crate) extern "efiapi" fn locate_device_path(
+    _protocol: *mut efi::Guid,
+    _device_path: *mut *mut r_efi::protocols::device_path::Protocol,
+    _device: *mut efi::Handle,
+) -> efi::Status {
+    efi::Status::NOT_FOUND
+}
+
+/// Runs `supported` and then `start` of every driver binding on `controller_handle`, highest version
+/// first. Child controllers are not connected recursively.
+pub(crate) extern "efiapi" fn connect_controller(
+    controller_handle: efi::Handle,
+    driver_image_handle: *mut efi::Handle,
+    remaining_device_path: *mut r_efi::protocols::device_path::Protocol,
+    _recursive: efi::Boolean,
+) -> efi::Status {
+    if !lock(&HANDLES).contains_key(&(controller_handle as usize)) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let mut images = Vec::new();
+    let mut next = driver_image_handle;
+    // SAFETY: the list of driver images is either null or terminated by a null handle.
+    while let Some(image) = unsafe { next.as_ref() }.filter(|image| !image.is_null()) {
+        images.push(*image);
+        // SAFETY: as above.
+        next = unsafe { next.add(1) };
+    }
+
+    let mut bindings = driver_bindings();
+    bindings.retain(|binding| images.is_empty() || images.contains(&binding.image_handle));
+    bindings.sort_by(|a, b| b.version.cmp(&a.version));
+
+    let mut started = false;
+    for binding in bindings {
+        let this = binding as *const _ as *mut driver_binding::Protocol;
+        if (binding.supported)(this, controller_handle, remaining_device_path) == efi::Status::SUCCESS
+            && (binding.start)(this, controller_handle, remaining_device_path) == efi::Status::SUCCESS
+        {
+            started = true;
+        }
+    }
+
+    match started {
+        true => efi::Status::SUCCESS,
+        false => efi::Status::NOT_FOUND,
+    }
+}
+
+/// Runs `stop` of every driver that opened a protocol on `controller_handle` by driver.
+pub(crate) extern "efiapi" fn disconnect_controller(
+    controller_handle: efi::Handle,
+    driver_image_handle: efi::Handle,
+    _child_handle: efi::Handle,
+) -> efi::Status {
+    let agents: Vec<usize> = {
+        let handles = lock(&HANDLES);
+        let Some(interfaces) = handles.get(&(controller_handle as usize)) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let mut agents: Vec<usize> = interfaces
+            .iter()
+            .flat_map(|i| i.opens.iter())
+            .filter(|open| open.attributes & efi::OPEN_PROTOCOL_BY_DRIVER != 0)
+            .map(|open| open.agent)
+            .collect();
+        agents.dedup();
+        agents
+    };
+
+    for binding in driver_bindings() {
+        if !agents.contains(&(binding.driver_binding_handle as usize))
+            || (!driver_image_handle.is_null() && binding.image_handle != driver_image_handle)
+        {
+            continue;
+        }
+        let this = binding as *const _ as *mut driver_binding::Protocol;
+        let status = (binding.stop)(this, controller_handle, 0, ptr::null_mut());
+        if status != efi::Status::SUCCESS {
+            return status;
+        }
+    }
+    efi::Status::SUCCESS
+}
+
+/// Every installed Driver Binding Protocol.
+fn driver_bindings() -> Vec<&'static driver_binding::Protocol> {
+    lock(&HANDLES)
+        .values()
+        .flat_map(|interfaces| interfaces.iter())
+        .filter(|i| i.guid == driver_binding::PROTOCOL_GUID)
+        // SAFETY: installed interfaces stay valid until they are uninstalled.
+        .filter_map(|i| unsafe { (i.interface as *const driver_binding::Protocol).as_ref() })
+        .collect()
+}
+
+/// Copies `items` to a new pool buffer, handing its address and length back to the caller.
+///
+/// # Safety
+///
+/// `buffer` and `count` must be valid for writes.
+unsafe fn copy_to_pool<T: Copy>(items: &[T], buffer: *mut *mut T, count: *mut usize) -> efi::Status {
+    let Some(allocation) = memory::allocate(mem::size_of_val(items)) else {
+        return efi::Status::OUT_OF_RESOURCES;
+    };
+    ptr::copy_nonoverlapping(items.as_ptr(), allocation as *mut T, items.len());
+    *buffer = allocation as *mut T;
+    *count = items.len();
+    efi::Status::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUID: efi::Guid =
+        efi::Guid::from_fields(0x0f1e2d3c, 0x4b5a, 0x6978, 0x87, 0x96, &[0xa5, 0xb4, 0xc3, 0xd2, 0xe1, 0xf0]);
+    const NOTIFY_GUID: efi::Guid =
+        efi::Guid::from_fields(0x0f1e2d3c, 0x4b5a, 0x6978, 0x87, 0x96, &[0xa5, 0xb4, 0xc3, 0xd2, 0xe1, 0xf1]);
+
+    #[test]
+    fn test_install_locate_uninstall() {
+        let mut interface = 0u32;
+        let interface = &mut interface as *mut u32 as *mut c_void;
+        let mut guid = GUID;
+        let mut handle = ptr::null_mut();
+        assert_eq!(
+            install_protocol_interface(&mut handle, &mut guid, efi::NATIVE_INTERFACE, interface),
+            efi::Status::SUCCESS
+        );
+        assert_eq!(
+            install_protocol_interface(&mut handle, &mut guid, efi::NATIVE_INTERFACE, interface),
+            efi::Status::INVALID_PARAMETER
+        );
+
+        let mut found = ptr::null_mut();
+        assert_eq!(handle_protocol(handle, &mut guid, &mut found), efi::Status::SUCCESS);
+        assert_eq!(found, interface);
+        assert_eq!(locate_protocol(&mut guid, ptr::null_mut(), &mut found), efi::Status::SUCCESS);
+        assert_eq!(found, interface);
+
+        let mut size = 0;
+        assert_eq!(
+            locate_handle(efi::BY_PROTOCOL, &mut guid, ptr::null_mut(), &mut size, ptr::null_mut()),
+            efi::Status::BUFFER_TOO_SMALL
+        );
+        let mut handles = vec![ptr::null_mut(); size / mem::size_of::<efi::Handle>()];
+        assert_eq!(
+            locate_handle(efi::BY_PROTOCOL, &mut guid, ptr::null_mut(), &mut size, handles.as_mut_ptr()),
+            efi::Status::SUCCESS
+        );
+        assert_eq!(handles, [handle]);
+
+        let (mut guids, mut count) = (ptr::null_mut(), 0);
+        assert_eq!(protocols_per_handle(handle, &mut guids, &mut count), efi::Status::SUCCESS);
+        assert_eq!(count, 1);
+        assert_eq!(unsafe { **guids }, GUID);
+        assert_eq!(memory::free_pool(guids as *mut c_void), efi::Status::SUCCESS);
+
+        assert_eq!(uninstall_protocol_interface(handle, &mut guid, interface), efi::Status::SUCCESS);
+        assert_eq!(handle_protocol(handle, &mut guid, &mut found), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_open_by_driver() {
+        let mut interface = 0u32;
+        let interface = &mut interface as *mut u32 as *mut c_void;
+        let controller = install(0, GUID, interface).unwrap() as efi::Handle;
+        let (agent, other_agent) = (0x10 as efi::Handle, 0x20 as efi::Handle);
+        let mut guid = GUID;
+        let mut found = ptr::null_mut();
+
+        let open = |agent, found: &mut *mut c_void| {
+            let mut guid = GUID;
+            open_protocol(controller, &mut guid, found, agent, controller, efi::OPEN_PROTOCOL_BY_DRIVER)
+        };
+        assert_eq!(open(agent, &mut found), efi::Status::SUCCESS);
+        assert_eq!(open(agent, &mut found), efi::Status::ALREADY_STARTED);
+        assert_eq!(open(other_agent, &mut found), efi::Status::ACCESS_DENIED);
+        assert_eq!(uninstall_protocol_interface(controller, &mut guid, interface), efi::Status::ACCESS_DENIED);
+
+        let (mut entries, mut count) = (ptr::null_mut(), 0);
+        assert_eq!(open_protocol_information(controller, &mut guid, &mut entries, &mut count), efi::Status::SUCCESS);
+        assert_eq!(count, 1);
+        assert_eq!(unsafe { (*entries).agent_handle }, agent);
+        assert_eq!(memory::free_pool(entries as *mut c_void), efi::Status::SUCCESS);
+
+        assert_eq!(close_protocol(controller, &mut guid, agent, controller), efi::Status::SUCCESS);
+        assert_eq!(close_protocol(controller, &mut guid, agent, controller), efi::Status::NOT_FOUND);
+        assert_eq!(open(other_agent, &mut found), efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn test_register_protocol_notify() {
+        let mut event = ptr::null_mut();
+        assert_eq!(event::create_event(0, 0, None, ptr::null_mut(), &mut event), efi::Status::SUCCESS);
+        let mut guid = NOTIFY_GUID;
+        let mut registration = ptr::null_mut();
+        assert_eq!(register_protocol_notify(&mut guid, event, &mut registration), efi::Status::SUCCESS);
+
+        let handle = install(0, NOTIFY_GUID, ptr::null_mut()).unwrap() as efi::Handle;
+        assert_eq!(event::check_event(event), efi::Status::SUCCESS);
+
+        let (mut count, mut handles) = (0, ptr::null_mut());
+        assert_eq!(
+            locate_handle_buffer(efi::BY_REGISTER_NOTIFY, ptr::null_mut(), registration, &mut count, &mut handles),
+            efi::Status::SUCCESS
+        );
+        assert_eq!(unsafe { *handles }, handle);
+        assert_eq!(memory::free_pool(handles as *mut c_void), efi::Status::SUCCESS);
+        assert_eq!(
+            locate_handle_buffer(efi::BY_REGISTER_NOTIFY, ptr::null_mut(), registration, &mut count, &mut handles),
+            efi::Status::NOT_FOUND
+        );
+    }
+}
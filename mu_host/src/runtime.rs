@@ -0,0 +1,181 @@
+//! The Runtime Services table.
+use std::{
+    ffi::c_void,
+    mem, process,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use r_efi::efi;
+
+use crate::variable;
+
+static HIGH_MONOTONIC_COUNT: AtomicU32 = AtomicU32::new(0);
+
+pub(crate) fn runtime_services() -> *mut efi::RuntimeServices {
+    let table = Box::leak(Box::new(efi::RuntimeServices {
+        hdr: crate::table_header(
+            efi::RUNTIME_SERVICES_SIGNATURE,
+            efi::RUNTIME_SERVICES_REVISION,
+            mem::size_of::<efi::RuntimeServices>(),
+        ),
+        get_time,
+        set_time,
+        get_wakeup_time,
+        set_wakeup_time,
+        set_virtual_address_map,
+        convert_pointer,
+        get_variable: variable::get_variable,
+        get_next_variable_name: variable::get_next_variable_name,
+        set_variable: variable::set_variable,
+        get_next_high_mono_count,
+        reset_system,
+        update_capsule,
+        query_capsule_capabilities,
+        query_variable_info: variable::query_variable_info,
+    }));
+    crate::update_crc32(&mut table.hdr);
+    table
+}
+
+/// Returns the host time, in UTC.
+extern "efiapi" fn get_time(time: *mut efi::Time, capabilities: *mut efi::TimeCapabilities) -> efi::Status {
+    if time.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+        return efi::Status::DEVICE_ERROR;
+    };
+
+    let (year, month, day) = civil_from_days((now.as_secs() / 86400) as i64);
+    let seconds = now.as_secs() % 86400;
+    // SAFETY: checked for null above, the capabilities are optional.
+    unsafe {
+        *time = efi::Time {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+            pad1: 0,
+            nanosecond: now.subsec_nanos(),
+            timezone: efi::UNSPECIFIED_TIMEZONE,
+            daylight: 0,
+            pad2: 0,
+        };
+        if let Some(capabilities) = capabilities.as_mut() {
+            *capabilities = efi::TimeCapabilities { resolution: 1, accuracy: 0, sets_to_zero: efi::Boolean::FALSE };
+        }
+    }
+    efi::Status::SUCCESS
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The host clock belongs to the host.
+extern "efiapi" fn set_time(_time: *mut efi::Time) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn get_wakeup_time(
+    _enabled: *mut efi::Boolean,
+    _pending: *mut efi::Boolean,
+    _time: *mut efi::Time,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn set_wakeup_time(_enable: efi::Boolean, _time: *mut efi::Time) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+/// Components keep running with the host's addressing.
+extern "efiapi" fn set_virtual_address_map(
+    _memory_map_size: usize,
+    _descriptor_size: usize,
+    _descriptor_version: u32,
+    _virtual_map: *mut efi::MemoryDescriptor,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+/// Physical and virtual addresses are the same on the host.
+extern "efiapi" fn convert_pointer(_debug_disposition: usize, address: *mut *mut c_void) -> efi::Status {
+    match address.is_null() {
+        true => efi::Status::INVALID_PARAMETER,
+        false => efi::Status::SUCCESS,
+    }
+}
+
+extern "efiapi" fn get_next_high_mono_count(high_count: *mut u32) -> efi::Status {
+    if high_count.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: checked for null above.
+    unsafe { *high_count = HIGH_MONOTONIC_COUNT.fetch_add(1, Ordering::SeqCst) + 1 };
+    efi::Status::SUCCESS
+}
+
+/// Ends the process, successfully if `reset_status` is `EFI_SUCCESS`.
+extern "efiapi" fn reset_system(
+    _reset_type: efi::ResetType,
+    reset_status: efi::Status,
+    _data_size: usize,
+    _reset_data: *mut c_void,
+) {
+    process::exit(match reset_status {
+        efi::Status::SUCCESS => 0,
+        _ => 1,
+    })
+}
+
+extern "efiapi" fn update_capsule(
+    _capsule_header_array: *mut *mut efi::CapsuleHeader,
+    _capsule_count: usize,
+    _scatter_gather_list: efi::PhysicalAddress,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn query_capsule_capabilities(
+    _capsule_header_array: *mut *mut efi::CapsuleHeader,
+    _capsule_count: usize,
+    _maximum_capsule_size: *mut u64,
+    _reset_type: *mut efi::ResetType,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn test_get_time() {
+        let mut time = unsafe { mem::zeroed::<efi::Time>() };
+        assert_eq!(get_time(&mut time, std::ptr::null_mut()), efi::Status::SUCCESS);
+        assert!(time.year >= 2024);
+        assert!((1..=12).contains(&time.month));
+        assert_eq!(get_time(std::ptr::null_mut(), std::ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+    }
+}
@@ -0,0 +1,274 @@
+//! An in-memory variable store.
+use std::{collections::BTreeMap, ffi::c_void, mem, sync::Mutex};
+
+use r_efi::efi;
+
+use crate::lock;
+
+/// The space variables may take up, names included.
+const STORAGE_SIZE: usize = 0x10000;
+/// The largest name and data a single variable may take up.
+const MAX_VARIABLE_SIZE: usize = 0x8000;
+
+/// Variables keyed by vendor GUID and name, the name without its null terminator.
+static VARIABLES: Mutex<BTreeMap<(efi::Guid, Vec<u16>), Variable>> = Mutex::new(BTreeMap::new());
+
+struct Variable {
+    attributes: u32,
+    data: Vec<u8>,
+}
+
+/// Reads a null terminated variable name, without the terminator.
+///
+/// # Safety
+///
+/// `name` must be null or point to a null terminated string.
+unsafe fn read_name(name: *const efi::Char16) -> Option<Vec<u16>> {
+    if name.is_null() {
+        return None;
+    }
+    let length = (0..).take_while(|i| *name.add(*i) != 0).count();
+    Some(std::slice::from_raw_parts(name, length).to_vec())
+}
+
+/// The space a variable takes up in the store.
+fn variable_size(name: &[u16], data: &[u8]) -> usize {
+    (name.len() + 1) * mem::size_of::<u16>() + data.len()
+}
+
+pub(crate) extern "efiapi" fn get_variable(
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+    attributes: *mut u32,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> efi::Status {
+    // SAFETY: the caller passes a null terminated name.
+    let (Some(name), false, false) = (unsafe { read_name(variable_name) }, vendor_guid.is_null(), data_size.is_null())
+    else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let variables = lock(&VARIABLES);
+    // SAFETY: checked for null above.
+    let Some(variable) = variables.get(&(unsafe { *vendor_guid }, name)) else {
+        return efi::Status::NOT_FOUND;
+    };
+
+    // SAFETY: checked for null above, the attributes are optional.
+    unsafe {
+        if !attributes.is_null() {
+            *attributes = variable.attributes;
+        }
+        if *data_size < variable.data.len() {
+            *data_size = variable.data.len();
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        *data_size = variable.data.len();
+    }
+    if data.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: the caller's buffer holds at least `data_size` bytes.
+    unsafe { std::ptr::copy_nonoverlapping(variable.data.as_ptr(), data as *mut u8, variable.data.len()) };
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn get_next_variable_name(
+    variable_name_size: *mut usize,
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+) -> efi::Status {
+    // SAFETY: the caller passes a null terminated name.
+    let (Some(name), false, false) = (unsafe { read_name(variable_name) }, vendor_guid.is_null(), variable_name_size.is_null())
+    else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let variables = lock(&VARIABLES);
+    let next = match name.is_empty() {
+        true => variables.keys().next(),
+        false => {
+            // SAFETY: checked for null above.
+            let current = (unsafe { *vendor_guid }, name);
+            if !variables.contains_key(&current) {
+                return efi::Status::INVALID_PARAMETER;
+            }
+            variables.range(current..).nth(1).map(|(key, _)| key)
+        }
+    };
+    let Some((guid, name)) = next else {
+        return efi::Status::NOT_FOUND;
+    };
+
+    let required = (name.len() + 1) * mem::size_of::<u16>();
+    // SAFETY: checked for null above, and the caller's buffer holds `variable_name_size` bytes.
+    unsafe {
+        if *variable_name_size < required {
+            *variable_name_size = required;
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+        *variable_name_size = required;
+        std::ptr::copy_nonoverlapping(name.as_ptr(), variable_name, name.len());
+        *variable_name.add(name.len()) = 0;
+        *vendor_guid = *guid;
+    }
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn set_variable(
+    variable_name: *mut efi::Char16,
+    vendor_guid: *mut efi::Guid,
+    attributes: u32,
+    data_size: usize,
+    data: *mut c_void,
+) -> efi::Status {
+    // SAFETY: the caller passes a null terminated name.
+    let (Some(name), false) = (unsafe { read_name(variable_name) }, vendor_guid.is_null()) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if name.is_empty() || (data_size != 0 && data.is_null()) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if attributes & efi::VARIABLE_RUNTIME_ACCESS != 0 && attributes & efi::VARIABLE_BOOTSERVICE_ACCESS == 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let authenticated = efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+    if attributes & authenticated != 0 {
+        return efi::Status::UNSUPPORTED;
+    }
+
+    // SAFETY: checked for null above.
+    let key = (unsafe { *vendor_guid }, name);
+    let data = match data_size {
+        0 => &[][..],
+        // SAFETY: the caller passes `data_size` bytes of data.
+        _ => unsafe { std::slice::from_raw_parts(data as *const u8, data_size) },
+    };
+    let append = attributes & efi::VARIABLE_APPEND_WRITE != 0;
+    let attributes = attributes & !efi::VARIABLE_APPEND_WRITE;
+
+    let mut variables = lock(&VARIABLES);
+    if !append && (data.is_empty() || attributes == 0) {
+        return match variables.remove(&key) {
+            Some(_) => efi::Status::SUCCESS,
+            None => efi::Status::NOT_FOUND,
+        };
+    }
+
+    let mut new_data = match (append, variables.get(&key)) {
+        (true, Some(existing)) => existing.data.clone(),
+        _ => Vec::new(),
+    };
+    if let Some(existing) = variables.get(&key) {
+        if existing.attributes != attributes {
+            return efi::Status::INVALID_PARAMETER;
+        }
+    }
+    new_data.extend_from_slice(data);
+
+    let size = variable_size(&key.1, &new_data);
+    let used: usize = variables.iter().filter(|(k, _)| **k != key).map(|(k, v)| variable_size(&k.1, &v.data)).sum();
+    if size > MAX_VARIABLE_SIZE || used + size > STORAGE_SIZE {
+        return efi::Status::OUT_OF_RESOURCES;
+    }
+
+    variables.insert(key, Variable { attributes, data: new_data });
+    efi::Status::SUCCESS
+}
+
+pub(crate) extern "efiapi" fn query_variable_info(
+    _attributes: u32,
+    maximum_variable_storage_size: *mut u64,
+    remaining_variable_storage_size: *mut u64,
+    maximum_variable_size: *mut u64,
+) -> efi::Status {
+    if maximum_variable_storage_size.is_null() || remaining_variable_storage_size.is_null() || maximum_variable_size.is_null()
+    {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    let used: usize = lock(&VARIABLES).iter().map(|(key, variable)| variable_size(&key.1, &variable.data)).sum();
+    // SAFETY: checked for null above.
+    unsafe {
+        *maximum_variable_storage_size = STORAGE_SIZE as u64;
+        *remaining_variable_storage_size = (STORAGE_SIZE - used) as u64;
+        *maximum_variable_size = MAX_VARIABLE_SIZE as u64;
+    }
+    efi::Status::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    const VENDOR: efi::Guid =
+        efi::Guid::from_fields(0x5e4d3c2b, 0x1a09, 0xf8e7, 0xd6, 0xc5, &[0xb4, 0xa3, 0x92, 0x81, 0x70, 0x6f]);
+    const ATTRIBUTES: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+
+    fn name(name: &str) -> Vec<u16> {
+        name.encode_utf16().chain([0]).collect()
+    }
+
+    fn get(variable: &str) -> Result<Vec<u8>, efi::Status> {
+        let mut guid = VENDOR;
+        let mut data = [0u8; 16];
+        let mut size = data.len();
+        let status = get_variable(name(variable).as_mut_ptr(), &mut guid, ptr::null_mut(), &mut size, data.as_mut_ptr() as *mut c_void);
+        match status {
+            efi::Status::SUCCESS => Ok(data[..size].to_vec()),
+            status => Err(status),
+        }
+    }
+
+    fn set(variable: &str, attributes: u32, data: &[u8]) -> efi::Status {
+        let mut guid = VENDOR;
+        set_variable(name(variable).as_mut_ptr(), &mut guid, attributes, data.len(), data.as_ptr() as *mut c_void)
+    }
+
+    #[test]
+    fn test_set_get_delete() {
+        assert_eq!(get("TestSetGet"), Err(efi::Status::NOT_FOUND));
+        assert_eq!(set("TestSetGet", ATTRIBUTES, &[1, 2]), efi::Status::SUCCESS);
+        assert_eq!(get("TestSetGet"), Ok(vec![1, 2]));
+
+        assert_eq!(set("TestSetGet", ATTRIBUTES | efi::VARIABLE_APPEND_WRITE, &[3]), efi::Status::SUCCESS);
+        assert_eq!(get("TestSetGet"), Ok(vec![1, 2, 3]));
+        assert_eq!(set("TestSetGet", efi::VARIABLE_BOOTSERVICE_ACCESS, &[4]), efi::Status::INVALID_PARAMETER);
+
+        assert_eq!(set("TestSetGet", ATTRIBUTES, &[]), efi::Status::SUCCESS);
+        assert_eq!(get("TestSetGet"), Err(efi::Status::NOT_FOUND));
+        assert_eq!(set("TestSetGet", efi::VARIABLE_RUNTIME_ACCESS, &[1]), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        assert_eq!(set("TestTooSmall", ATTRIBUTES, &[1, 2, 3, 4]), efi::Status::SUCCESS);
+        let mut guid = VENDOR;
+        let mut size = 2;
+        let mut data = [0u8; 2];
+        let status =
+            get_variable(name("TestTooSmall").as_mut_ptr(), &mut guid, ptr::null_mut(), &mut size, data.as_mut_ptr() as *mut c_void);
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(size, 4);
+    }
+
+    #[test]
+    fn test_next_variable_name() {
+        assert_eq!(set("TestNextA", ATTRIBUTES, &[1]), efi::Status::SUCCESS);
+        assert_eq!(set("TestNextB", ATTRIBUTES, &[1]), efi::Status::SUCCESS);
+
+        let mut buffer = [0u16; 64];
+        buffer[..10].copy_from_slice(&name("TestNextA"));
+        let mut guid = VENDOR;
+        let mut size = 4;
+        assert_eq!(get_next_variable_name(&mut size, buffer.as_mut_ptr(), &mut guid), efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(size, name("TestNextB").len() * 2);
+
+        size = mem::size_of_val(&buffer);
+        assert_eq!(get_next_variable_name(&mut size, buffer.as_mut_ptr(), &mut guid), efi::Status::SUCCESS);
+        assert_eq!(buffer[..10], name("TestNextB"));
+        assert_eq!(guid, VENDOR);
+    }
+}
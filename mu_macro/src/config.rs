@@ -0,0 +1,250 @@
+//! Finding and reading the config file named by `component_from_path!` and `pcds!`.
+//!
+//! A relative path is resolved against the directory of the crate being compiled (`CARGO_MANIFEST_DIR`),
+//! and then against the workspace root. The generated code includes the file with `include_bytes!`, and
+//! reads the variable with `option_env!` for `Env`, so that cargo rebuilds the crate when either changes.
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, LitStr, Token};
+
+use mu_config::{Config, ConfigError};
+
+mod kw {
+    syn::custom_keyword!(path);
+    syn::custom_keyword!(Path);
+    syn::custom_keyword!(config);
+    syn::custom_keyword!(Config);
+    syn::custom_keyword!(env);
+    syn::custom_keyword!(Env);
+}
+
+/// Where a macro finds its config file: `Config = "path"` (or `Path = "path"`), or `Env = "VARIABLE"`.
+pub(crate) enum Source {
+    Path(LitStr),
+    Env(LitStr),
+}
+
+impl Source {
+    pub(crate) fn peek(input: syn::parse::ParseStream) -> bool {
+        input.peek(kw::path) || input.peek(kw::Path) || input.peek(kw::config) || input.peek(kw::Config)
+            || input.peek(kw::env) || input.peek(kw::Env)
+    }
+
+    /// The path of the config file as written, or `None` when the `Env` variable is not set.
+    pub(crate) fn path(&self) -> Option<String> {
+        match self {
+            Source::Path(path) => Some(path.value()),
+            Source::Env(env) => std::env::var(env.value()).ok(),
+        }
+    }
+
+    /// The path of the config file, failing when the `Env` variable is not set.
+    pub(crate) fn required_path(&self) -> syn::Result<String> {
+        match self {
+            Source::Path(path) => Ok(path.value()),
+            Source::Env(env) => std::env::var(env.value()).map_err(|e| {
+                syn::Error::new(env.span(), format!("Environment variable {}: {}", env.value(), e))
+            }),
+        }
+    }
+
+    /// Reads and parses the config file at `path`, and the files it includes, reporting errors at the source.
+    /// Returns the config, and every file it was read from.
+    pub(crate) fn read(&self, path: &str) -> syn::Result<(Config, Vec<PathBuf>)> {
+        let span = match self {
+            Source::Path(path) | Source::Env(path) => path.span(),
+        };
+
+        let resolved = resolve(path);
+        std::fs::metadata(&resolved).map_err(
+            |e| syn::Error::new(span, format!("Failed to read {}: {}", path, e.kind()))
+        )?;
+
+        // Every problem in the files is reported, each as its own error, with the files named as written.
+        let config = Config::from_path(&resolved).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|e| {
+                    let file = e.path.as_deref().map(|file| as_written(file, &resolved, path));
+                    syn::Error::new(span, ConfigError { path: file, ..e })
+                })
+                .reduce(|mut combined, e| {
+                    combined.combine(e);
+                    combined
+                })
+                .unwrap()
+        })?;
+        let files = config.files.clone();
+        Ok((config, files))
+    }
+
+    /// Items that make the crate depend on the config `files`, and on the `Env` variable.
+    pub(crate) fn track_items(&self, files: &[PathBuf]) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        if let Source::Env(env) = self {
+            tokens.extend(quote!(const _: Option<&str> = option_env!(#env);));
+        }
+        for file in files {
+            let file = file.to_string_lossy().into_owned();
+            tokens.extend(quote!(const _: &[u8] = include_bytes!(#file);));
+        }
+        tokens
+    }
+
+    /// The type `ty`, made to depend on the config `files`, and on the `Env` variable.
+    pub(crate) fn track_type(&self, ty: TokenStream, files: &[PathBuf]) -> TokenStream {
+        let items = self.track_items(&[]);
+        let files = files.iter().map(|file| file.to_string_lossy().into_owned());
+        quote! {
+            <#ty as ::mu_core::__private::DependsOn<{ #items #(include_bytes!(#files).len())+* }>>::Type
+        }
+    }
+}
+
+/// Names `file`, one of the files read for the config file `path`, which was found at `resolved`, relative
+/// to `path` as it was written.
+fn as_written(file: &Path, resolved: &Path, path: &str) -> PathBuf {
+    if file == resolved {
+        return PathBuf::from(path);
+    }
+    let written_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    match resolved.parent().and_then(|dir| file.strip_prefix(dir).ok()) {
+        Some(relative) => written_dir.join(relative),
+        None => file.to_path_buf(),
+    }
+}
+
+impl syn::parse::Parse for Source {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let env = input.peek(kw::env) || input.peek(kw::Env);
+        if !Source::peek(input) {
+            return Err(input.error("Expected 'Config', 'Path' or 'Env' keyword"));
+        }
+        input.parse::<Ident>()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse::<LitStr>()?;
+
+        Ok(if env { Source::Env(value) } else { Source::Path(value) })
+    }
+}
+
+/// The target of the crate being compiled, when the macro is run by rustc: the triple given with `--target`,
+/// the `arch` of a custom target's JSON spec, or else the architecture rustc runs on, which it builds for
+/// when no target is given. `None` when the macro is run by something else, such as rust-analyzer, which
+/// does not say what the crate is built for.
+#[cfg(not(test))]
+pub(crate) fn target() -> Option<String> {
+    target_from_args(std::env::args())
+}
+
+/// The unit tests run the macros in the test binary, which is built for the host.
+#[cfg(test)]
+pub(crate) fn target() -> Option<String> {
+    Some(std::env::consts::ARCH.to_string())
+}
+
+fn target_from_args(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let mut args = args.into_iter();
+    let mut rustc = false;
+    while let Some(arg) = args.next() {
+        rustc |= arg == "--crate-name";
+        let target = match arg.strip_prefix("--target") {
+            Some("") => args.next()?,
+            Some(target) => match target.strip_prefix('=') {
+                Some(target) => target.to_string(),
+                None => continue,
+            },
+            None => continue,
+        };
+        if target.ends_with(".json") {
+            return spec_arch(Path::new(&target));
+        }
+        return Some(target);
+    }
+    rustc.then(|| std::env::consts::ARCH.to_string())
+}
+
+/// The `arch` of the custom target spec at `path`.
+fn spec_arch(path: &Path) -> Option<String> {
+    let spec: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    spec.get("arch")?.as_str().map(str::to_string)
+}
+
+/// Resolves `path` against the crate being compiled, and then against the workspace root. A path found in
+/// neither is returned relative to the crate, so that errors name the first place it was looked for.
+pub(crate) fn resolve(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    let manifest_dir = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(dir) if path.is_relative() => PathBuf::from(dir),
+        _ => return canonical(path.to_path_buf()),
+    };
+
+    let in_crate = manifest_dir.join(path);
+    if in_crate.exists() {
+        return canonical(in_crate);
+    }
+
+    let workspace_root = manifest_dir.ancestors().find(|dir| {
+        std::fs::read_to_string(dir.join("Cargo.toml"))
+            .map(|manifest| manifest.lines().any(|line| line.trim() == "[workspace]"))
+            .unwrap_or(false)
+    });
+    match workspace_root.map(|root| root.join(path)) {
+        Some(in_workspace) if in_workspace.exists() => canonical(in_workspace),
+        _ => in_crate,
+    }
+}
+
+fn canonical(path: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+
+        // Relative to the crate.
+        assert_eq!(
+            resolve("tests/data/test_config1.toml"),
+            manifest_dir.join("tests/data/test_config1.toml").canonicalize().unwrap()
+        );
+
+        // Relative to the workspace root.
+        assert_eq!(
+            resolve("mu_macro/tests/data/test_config1.toml"),
+            manifest_dir.join("tests/data/test_config1.toml").canonicalize().unwrap()
+        );
+
+        // Absolute.
+        let absolute = manifest_dir.join("tests/data/test_config1.toml").canonicalize().unwrap();
+        assert_eq!(resolve(absolute.to_str().unwrap()), absolute);
+
+        // Missing, reported relative to the crate.
+        assert_eq!(resolve("tests/data/missing.toml"), manifest_dir.join("tests/data/missing.toml"));
+    }
+
+    #[test]
+    fn test_target_from_args() {
+        let args = |args: &[&str]| target_from_args(args.iter().map(ToString::to_string));
+        let host = Some(std::env::consts::ARCH.to_string());
+
+        assert_eq!(args(&["rustc", "--crate-name", "driver", "--target", "x86_64-unknown-uefi"]).as_deref(), Some("x86_64-unknown-uefi"));
+        assert_eq!(args(&["rustc", "--crate-name", "driver", "--target=aarch64-unknown-uefi"]).as_deref(), Some("aarch64-unknown-uefi"));
+
+        // A custom target is named after its spec file, which says nothing about its arch.
+        let spec = resolve("tests/data/my_board.json");
+        assert_eq!(args(&["rustc", "--target", spec.to_str().unwrap()]).as_deref(), Some("aarch64"));
+        assert_eq!(args(&["rustc", "--target", "tests/data/missing.json"]), None);
+
+        // rustc without a target builds for the host, and anything else does not say.
+        assert_eq!(args(&["rustc", "--crate-name", "driver", "--edition=2021"]), host);
+        assert_eq!(args(&["rust-analyzer-proc-macro-srv"]), None);
+        assert_eq!(args(&["rustc", "--targetx"]), None);
+    }
+}
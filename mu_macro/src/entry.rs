@@ -0,0 +1,193 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{punctuated::Punctuated, Ident, Token};
+
+/// Expands `#[entry(..)]` on a component type alias.
+pub fn parse(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match syn::parse2::<EntryArgs>(attr) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let item = match syn::parse2::<syn::ItemType>(item) {
+        Ok(item) => item,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    Entry { args, item }.to_token_stream()
+}
+
+/// The arguments of the attribute: an optional module type, then an optional `panic = Policy`.
+struct EntryArgs {
+    module: Ident,
+    panic: Ident,
+}
+
+impl syn::parse::Parse for EntryArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut module = Ident::new("DxeDriver", Span::call_site());
+        let mut panic = Ident::new("Exit", Span::call_site());
+
+        let args: Punctuated<EntryArg, Token![,]> = input.parse_terminated(EntryArg::parse, Token![,])?;
+        for arg in args {
+            match arg {
+                EntryArg::Module(ident) => {
+                    if !MODULE_TYPES.contains(&ident.to_string().as_str()) {
+                        return Err(syn::Error::new(
+                            ident.span(),
+                            format!("Unknown module type {}, expected one of {}", ident, MODULE_TYPES.join(", ")),
+                        ));
+                    }
+                    module = ident;
+                }
+                EntryArg::Panic(ident) => panic = ident,
+            }
+        }
+
+        Ok(EntryArgs { module, panic })
+    }
+}
+
+const MODULE_TYPES: [&str; 5] = ["DxeDriver", "UefiDriver", "UefiApplication", "DxeRuntimeDriver", "MmStandalone"];
+
+enum EntryArg {
+    Module(Ident),
+    Panic(Ident),
+}
+
+impl syn::parse::Parse for EntryArg {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        if !input.peek(Token![=]) {
+            return Ok(EntryArg::Module(name));
+        }
+
+        input.parse::<Token![=]>()?;
+        if name != "panic" {
+            return Err(syn::Error::new(name.span(), format!("Unknown argument {}, expected panic", name)));
+        }
+        Ok(EntryArg::Panic(input.parse()?))
+    }
+}
+
+struct Entry {
+    args: EntryArgs,
+    item: syn::ItemType,
+}
+
+impl ToTokens for Entry {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let item = &self.item;
+        let name = &item.ident;
+        let policy = &self.args.panic;
+
+        let (component, entry_point, host_main) = match self.args.module.to_string().as_str() {
+            "MmStandalone" => (
+                quote!(<#name as ::mu_core::MmStandaloneComponent>),
+                quote!(efi_main(image_handle: efi::Handle, system_table: *mut ::core::ffi::c_void)),
+                quote!(::core::result::Result::Err(::mu_core::error::EfiError::Unsupported)),
+            ),
+            module => {
+                let component = match module {
+                    "DxeDriver" => quote!(<#name as ::mu_core::Component>),
+                    "UefiDriver" => quote!(<#name as ::mu_core::DriverBindingComponent>),
+                    "UefiApplication" => quote!(<#name as ::mu_core::ApplicationComponent>),
+                    _ => quote!(<#name as ::mu_core::RuntimeComponent>),
+                };
+                let host_main = quote!(unsafe {
+                    #component::entry_point(
+                        ::mu_core::ImageHandle::host().as_raw(),
+                        ::mu_core::SystemTable::host().as_ptr(),
+                    )
+                });
+                (
+                    component,
+                    quote!(efi_main(image_handle: efi::Handle, system_table: *mut efi::SystemTable)),
+                    host_main,
+                )
+            }
+        };
+
+        tokens.extend(quote! {
+            #item
+
+            #[cfg(target_os = "uefi")]
+            ::mu_core::panic_handler!(::mu_core::panic::PanicPolicy::#policy);
+
+            #[cfg(target_os = "uefi")]
+            const _: () = {
+                use ::mu_core::__private::efi;
+
+                #[no_mangle]
+                pub extern "efiapi" fn #entry_point -> efi::Status {
+                    ::mu_core::error::to_status(unsafe { #component::entry_point(image_handle, system_table) })
+                }
+            };
+
+            #[cfg(not(target_os = "uefi"))]
+            fn main() -> ::mu_core::error::Result<()> {
+                #host_main?;
+                Ok(())
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_dxe_driver() {
+        let expected = quote! {
+            type Driver = MyDriver<DebugLibBase>;
+
+            #[cfg(target_os = "uefi")]
+            ::mu_core::panic_handler!(::mu_core::panic::PanicPolicy::Exit);
+
+            #[cfg(target_os = "uefi")]
+            const _: () = {
+                use ::mu_core::__private::efi;
+
+                #[no_mangle]
+                pub extern "efiapi" fn efi_main(image_handle: efi::Handle, system_table: *mut efi::SystemTable) -> efi::Status {
+                    ::mu_core::error::to_status(unsafe { <Driver as ::mu_core::Component>::entry_point(image_handle, system_table) })
+                }
+            };
+
+            #[cfg(not(target_os = "uefi"))]
+            fn main() -> ::mu_core::error::Result<()> {
+                unsafe {
+                    <Driver as ::mu_core::Component>::entry_point(
+                        ::mu_core::ImageHandle::host().as_raw(),
+                        ::mu_core::SystemTable::host().as_ptr(),
+                    )
+                }?;
+                Ok(())
+            }
+        };
+
+        let actual = parse(quote!(), quote!(type Driver = MyDriver<DebugLibBase>;));
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_entry_arguments() {
+        let actual = parse(quote!(UefiDriver, panic = Deadloop), quote!(type Driver = MyDriver<DebugLibBase>;));
+        let actual = actual.to_string();
+        assert!(actual.contains(&quote!(::mu_core::panic::PanicPolicy::Deadloop).to_string()));
+        assert!(actual.contains(&quote!(<Driver as ::mu_core::DriverBindingComponent>::entry_point).to_string()));
+    }
+
+    #[test]
+    fn test_entry_unknown_module() {
+        let actual = parse(quote!(PeiCore), quote!(type Driver = MyDriver<DebugLibBase>;));
+        assert!(actual.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn test_entry_requires_type_alias() {
+        let actual = parse(quote!(), quote!(struct Driver;));
+        assert!(actual.to_string().contains("compile_error"));
+    }
+}
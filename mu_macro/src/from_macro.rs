@@ -1,23 +1,20 @@
-use super::{Component, Library};
+use super::{constructed, Component, Library, LibraryGraph};
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
 use syn::Token;
 
 use std::collections::HashMap;
 
 
 pub fn parse(tokens: TokenStream) -> TokenStream {
-    let mut parsed = match syn::parse2::<FullyDescribed>(tokens) {
+    let parsed = match syn::parse2::<FullyDescribed>(tokens) {
         Ok(component) => component,
-        Err(e) => return e.to_compile_error().into(),
+        Err(e) => return e.to_compile_error(),
     };
 
-    match parsed.resolve() {
-        Ok(_) => (),
-        Err(e) => return e.to_compile_error().into(),
+    match parsed.expand() {
+        Ok(tokens) => tokens,
+        Err(e) => e.to_compile_error(),
     }
-
-    parsed.to_token_stream()
 }
 
 struct FullyDescribed {
@@ -26,20 +23,9 @@ struct FullyDescribed {
 }
 
 impl FullyDescribed {
-    fn resolve(&mut self) -> syn::Result<()> {
-        while !self.impl_map.values().all(|lib| lib.is_resolved()) {
-            let temp_map = self.impl_map.clone();
-        
-            for lib in self.impl_map.values_mut() {
-                lib.resolve(&temp_map).unwrap();
-            }
-      
-            // If the map hasn't changed, we have a circular dependency
-            if temp_map == self.impl_map {
-                panic!("Circular dependency detected");
-            }
-        }
-        Ok(())
+    fn expand(&self) -> syn::Result<TokenStream> {
+        let graph = LibraryGraph::new(&self.component.library_list, &self.impl_map)?;
+        Ok(constructed(&self.component, &graph))
     }
 }
 
@@ -57,30 +43,18 @@ impl syn::parse::Parse for FullyDescribed {
     }
 }
 
-impl ToTokens for FullyDescribed {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        let name = &self.component.name;
-
-        let mut library_list = vec![];
-        for library in &self.component.library_list {
-          let lib = self.impl_map.get(&library.to_string().to_lowercase()).unwrap();
-          library_list.push(lib);
-        }
-    
-        tokens.extend(quote! {
-          #name<#(#library_list),*>
-        });
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quote::quote;
 
     #[test]
     fn test_full_parse1() {
         let expected = quote! {
-            MyDriver<DebugLibBase>
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase>,
+                (DebugLibBase, ())
+            >
         };
       
         let input = quote! {
@@ -94,7 +68,10 @@ mod tests {
     #[test]
     fn test_full_parse2() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, ()))
+            >
         };
       
         let input = quote! {
@@ -110,7 +87,10 @@ mod tests {
     #[test]
     fn test_full_parse3() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase >, AdvLibBase < PrintLibBase> >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase>, AdvLibBase<PrintLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, (AdvLibBase<PrintLibBase>, ())))
+            >
         };
 
         let input = quote! {
@@ -127,7 +107,10 @@ mod tests {
     #[test]
     fn test_full_parse4() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase >, AdvLibBase < PrintLibBase, WriteLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase>, AdvLibBase<PrintLibBase, WriteLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, (WriteLibBase, (AdvLibBase<PrintLibBase, WriteLibBase>, ()))))
+            >
         };
 
         let input = quote! {
@@ -145,7 +128,10 @@ mod tests {
     #[test]
     fn test_full_parse5() {
         let expected_output = quote! {
-            MyDriver < pk1::library::DebugLibBase, pk1::library::AdvLibSpecial < pk2::library::WriteLibBase, pk1::library::DebugLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<pk1::library::DebugLibBase, pk1::library::AdvLibSpecial<pk2::library::WriteLibBase, pk1::library::DebugLibBase> >,
+                (pk1::library::DebugLibBase, (pk2::library::WriteLibBase, (pk1::library::AdvLibSpecial<pk2::library::WriteLibBase, pk1::library::DebugLibBase>, ())))
+            >
         };
 
         let input = quote! {
@@ -158,4 +144,23 @@ mod tests {
         let actual = parse(input);
         assert_eq!(actual.to_string(), expected_output.to_string());
     }
+
+    #[test]
+    fn test_full_parse6() {
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<8, pk2::library::RingBufferDebugLib<1024, pk2::library::WriteLibBase>, crate::Serial>,
+                (pk2::library::WriteLibBase, (pk2::library::RingBufferDebugLib<1024, pk2::library::WriteLibBase>, ()))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<8, DebugLib, crate::Serial>;
+            DebugLib=pk2::library::RingBufferDebugLib<1024, WriteLib>;
+            WriteLib=pk2::library::WriteLibBase;
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), expected_output.to_string());
+    }
 }
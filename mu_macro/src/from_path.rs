@@ -1,83 +1,149 @@
-use super::{Component, Library};
-use proc_macro2::TokenStream;
-use syn::{Ident, Token};
-use quote::{quote, ToTokens};
+use super::{config::{target, Source}, constructed, Component, Library, LibraryGraph};
+use proc_macro2::{Span, TokenStream};
+use syn::Token;
+use quote::quote;
 
-use mu_config::{Architecture, Config, LibraryInstance, Module};
+use mu_config::{ComponentInstance, Config, LibraryInstance, Module};
 
 use std::collections::HashMap;
-
-mod kw {
-    syn::custom_keyword!(path);
-    syn::custom_keyword!(Path);
-    syn::custom_keyword!(env);
-    syn::custom_keyword!(Env);
-}
+use std::path::PathBuf;
 
 pub fn parse(tokens: TokenStream) -> TokenStream {
     let mut parsed = match syn::parse2::<PathDescribed>(tokens) {
         Ok(component) => component,
-        Err(e) => return e.to_compile_error().into(),
+        Err(e) => return type_error(e),
     };
 
-    match parsed.resolve() {
-        Ok(_) => (),
-        Err(e) => return e.to_compile_error().into(),
+    match parsed.resolve().and_then(|_| parsed.expand()) {
+        Ok(tokens) => tokens,
+        Err(e) => type_error(e),
     }
+}
 
-    parsed.to_token_stream()
+/// The errors of `e` in type position, where only one `compile_error!` fits on its own.
+fn type_error(e: syn::Error) -> TokenStream {
+    if e.clone().into_iter().count() == 1 {
+        return e.to_compile_error();
+    }
+    let errors = e.into_iter().map(|e| e.to_compile_error());
+    quote!([(); { #(#errors;)* 0 }])
 }
 
 struct PathDescribed {
     component: Component,
     impl_map: HashMap<String, Library>,
+    /// The instances given after the config file, by lowercase library name, which take priority over the
+    /// config file for the component and every library it requires.
+    overrides: HashMap<String, Library>,
     config: Config,
+    config_path: String,
+    /// Where the config file was found, and the file itself, which the crate is rebuilt on.
+    source: Source,
+    files: Vec<PathBuf>,
+    module: Module,
 }
 
 impl PathDescribed {
+    /// Looks up the instances of the component's libraries, and of the libraries they require, in the config.
     fn resolve(&mut self) -> syn::Result<()> {
-        let component_name = self.component.name.to_string().to_lowercase();
-        let component = self.config.components.get(&component_name).unwrap();
+        let name = &self.component.name;
+        let target = target().ok_or_else(|| syn::Error::new(
+            name.span(),
+            format!("Cannot tell which target {} is built for, as the macro is not run by rustc", name),
+        ))?;
+        let component = self.config.components.get_for_target(&name.to_string(), &target).ok_or_else(|| {
+            let message = match self.config.components.get(&name.to_string()) {
+                Some(_) => format!(
+                    "Component {} has no build target for {} in {}",
+                    name,
+                    mu_config::Architecture::from_target(&target),
+                    self.config_path
+                ),
+                None => format!("Component {} not found in {}", name, self.config_path),
+            };
+            syn::Error::new(name.span(), message)
+        })?;
+        self.module = component.module.clone();
+
+        for library in self.component.library_list.clone() {
+            let instance = self.instance(&library.to_string(), None, &component, library.span())?;
+            self.register_dependencies(&instance, &component, library.span())?;
+        }
 
-        let library_list: Vec<LibraryInstance> = self.component.library_list
-            .iter()
-            .map(|lib| lib.to_string().to_lowercase())
-            .map(|lib| self.config.libraries.get(&lib, &component.arch, &component.module).unwrap())
-            .collect();
+        Ok(())
+    }
 
-        for library in library_list {
-            let instance = syn::parse_str::<Library>(&format!("{}={}", &library.name, &library.path)).unwrap();
-            self.register_dependencies(&instance, &component.arch, &component.module);
+    fn expand(&self) -> syn::Result<TokenStream> {
+        // The libraries come from the config file, so a cycle is reported at the component.
+        let graph = LibraryGraph::new(&self.component.library_list, &self.impl_map)
+            .map_err(|e| syn::Error::new(self.component.name.span(), e))?;
+        let component = constructed(&self.component, &graph);
+
+        // Check that the component implements the trait for the module type it is built as.
+        let module_type = match self.module {
+            Module::DxeDriver => quote!(DxeDriver),
+            Module::DxeRuntimeDriver => quote!(DxeRuntimeDriver),
+            Module::UefiDriver => quote!(UefiDriver),
+            Module::UefiApplication => quote!(UefiApplication),
+            Module::MmStandalone => quote!(MmStandalone),
+            // The other module types have no component trait to check.
+            _ => return Ok(self.source.track_type(component, &self.files)),
+        };
+        let component = quote! {
+          <#component as ::mu_core::module_type::BuildAs<::mu_core::module_type::#module_type>>::Component
+        };
+        Ok(self.source.track_type(component, &self.files))
+    }
+
+    /// Looks up the instance of library `name`, in the overrides and then for `component` in the config,
+    /// reporting any error at `span`.
+    fn instance(&self, name: &str, required_by: Option<&Library>, component: &ComponentInstance, span: Span) -> syn::Result<Library> {
+        if let Some(library) = self.overrides.get(&name.to_lowercase()) {
+            return Ok(library.clone());
         }
 
-        while !self.impl_map.values().all(|lib| lib.is_resolved()) {
-            let temp_map = self.impl_map.clone();
-        
-            for lib in self.impl_map.values_mut() {
-                lib.resolve(&temp_map).unwrap();
-            }
-      
-            // If the map hasn't changed, we have a circular dependency
-            if temp_map == self.impl_map {
-                panic!("Circular dependency detected");
-            }
+        let required_by = match required_by {
+            Some(library) => format!(", required by {}", library.name),
+            None => String::new(),
+        };
+
+        let library: LibraryInstance = self.config.libraries.get_for_component(name, component).ok_or_else(|| {
+            syn::Error::new(span, format!(
+                "No instance of library {}{} for arch {:?} and module {:?} in {}",
+                name, required_by, component.arch, component.module, self.config_path
+            ))
+        })?;
+
+        if !library.supports(&component.module) {
+            let modules: Vec<String> = library.modules.iter().map(ToString::to_string).collect();
+            let comma = if required_by.is_empty() { "" } else { "," };
+            return Err(syn::Error::new(span, format!(
+                "Instance {} of library {}{}{} supports {}, but {} is a {} in {}",
+                library.path, name, required_by, comma, modules.join(" "), self.component.name, component.module,
+                self.config_path
+            )));
         }
 
-        Ok(())
+        syn::parse_str::<Library>(&format!("{}={}", name, &library.path)).map_err(|e| {
+            syn::Error::new(span, format!(
+                "Invalid instance \"{}\" of library {}{} in {}: {}",
+                library.path, name, required_by, self.config_path, e
+            ))
+        })
     }
 
-    fn register_dependencies(&mut self, library: &Library, arch: &Architecture, module: &Module) {
+    fn register_dependencies(&mut self, library: &Library, component: &ComponentInstance, span: Span) -> syn::Result<()> {
         let key = library.name.to_string().to_lowercase();
         if self.impl_map.contains_key(&key) {
-            return;
+            return Ok(());
         }
         self.impl_map.insert(key, library.clone());
 
-        for library in &library.required {
-            let library = self.config.libraries.get(&library.to_string().to_lowercase(), arch, module).unwrap();
-            let instance = syn::parse_str::<Library>(&format!("{}={}", &library.name, &library.path)).unwrap();
-            self.register_dependencies(&instance, arch, module);
+        for required in &library.required {
+            let instance = self.instance(&required.to_string(), Some(library), component, span)?;
+            self.register_dependencies(&instance, component, span)?;
         }
+        Ok(())
     }
 }
 
@@ -85,62 +151,57 @@ impl syn::parse::Parse for PathDescribed {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let component = input.parse::<Component>()?;
         
-        let mut path: Option<String> = None;
-        if input.peek(kw::path) || input.peek(kw::Path) {
-            input.parse::<Ident>()?;
-            input.parse::<Token![=]>()?;
-            path = Some(input.parse::<syn::LitStr>()?.value());
+        let source = input.parse::<Source>()?;
+        if !input.is_empty() {
             input.parse::<Token![;]>()?;
         }
-        else if input.peek(kw::env) || input.peek(kw::Env) {
-            input.parse::<Ident>()?;
-            input.parse::<Token![=]>()?;
-            let env = input.parse::<syn::LitStr>()?;
-            input.parse::<Token![;]>()?;
-            path = Some(std::env::var(env.value()).unwrap());
-        }
 
-        if path.is_none() {
-            return Err(input.error("Expected 'Path' or 'Env' keyword"));
-        }
+        let overrides = input.parse_terminated(Library::parse, Token![;])?;
+        let overrides: HashMap<String, Library> = overrides
+            .into_iter()
+            .map(|lib| (lib.name.to_string().to_lowercase(), lib))
+            .collect();
 
-        let toml_content = std::fs::read_to_string(path.unwrap()).map_err(
-            |e| syn::Error::new(input.span(), e)
-        )?;
-        let config = toml::from_str::<Config>(&toml_content).unwrap();
+        let path = source.required_path()?;
+        let (config, files) = source.read(&path)?;
 
         Ok(PathDescribed {
             component,
             impl_map: HashMap::new(),
+            overrides,
             config,
+            config_path: path,
+            source,
+            files,
+            module: Module::Common,
         })
     }
 }
 
-impl ToTokens for PathDescribed {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        let name = &self.component.name;
-
-        let mut library_list = vec![];
-        for library in &self.component.library_list {
-          let lib = self.impl_map.get(&library.to_string().to_lowercase()).unwrap();
-          library_list.push(lib);
-        }
-    
-        tokens.extend(quote! {
-          #name<#(#library_list),*>
-        });
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// The `expected` type of a component, made to depend on the config file at `path`.
+    fn tracked(path: &str, expected: TokenStream) -> TokenStream {
+        tracked_files(&[path], expected)
+    }
+
+    /// The `expected` type of a component, made to depend on the config file at `paths[0]`, and the files
+    /// it includes.
+    fn tracked_files(paths: &[&str], expected: TokenStream) -> TokenStream {
+        let source = Source::Path(syn::LitStr::new(paths[0], Span::call_site()));
+        let files: Vec<PathBuf> = paths.iter().map(|path| crate::config::resolve(path)).collect();
+        source.track_type(expected, &files)
+    }
+
     #[test]
     fn test_full_parse1() {
         let expected = quote! {
-            MyDriver<DebugLibBase>
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase>,
+                (DebugLibBase, ())
+            >
         };
       
         let input = quote! {
@@ -149,13 +210,16 @@ mod tests {
         };
       
         let actual = parse(input);
-        assert_eq!(actual.to_string(), expected.to_string());
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config1.toml", expected).to_string());
     }
 
     #[test]
     fn test_full_parse2() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, ()))
+            >
         };
       
         let input = quote! {
@@ -164,13 +228,16 @@ mod tests {
         };
 
         let actual = parse(input);
-        assert_eq!(actual.to_string(), expected_output.to_string());
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config2.toml", expected_output).to_string());
     }
 
     #[test]
     fn test_full_parse3() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase >, AdvLibBase < PrintLibBase> >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase>, AdvLibBase<PrintLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, (AdvLibBase<PrintLibBase>, ())))
+            >
         };
 
         let input = quote! {
@@ -179,13 +246,16 @@ mod tests {
         };
 
         let actual = parse(input);
-        assert_eq!(actual.to_string(), expected_output.to_string());
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config2.toml", expected_output).to_string());
     }
 
     #[test]
     fn test_full_parse4() {
         let expected_output = quote! {
-            MyDriver < DebugLibBase < PrintLibBase >, AdvLibBase < PrintLibBase, WriteLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibBase>, AdvLibBase<PrintLibBase, WriteLibBase> >,
+                (PrintLibBase, (DebugLibBase<PrintLibBase>, (WriteLibBase, (AdvLibBase<PrintLibBase, WriteLibBase>, ()))))
+            >
         };
 
         let input = quote! {
@@ -194,13 +264,32 @@ mod tests {
         };
         
         let actual = parse(input);
-        assert_eq!(actual.to_string(), expected_output.to_string());
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config3.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_module_type_check() {
+        let expected_output = quote! {
+            < ::mu_core::library::Constructed<MyDriver<DebugLibBase>, (DebugLibBase, ())>
+                as ::mu_core::module_type::BuildAs<::mu_core::module_type::DxeRuntimeDriver>>::Component
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib>;
+            Path = "tests/data/test_config5.toml";
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config5.toml", expected_output).to_string());
     }
 
     #[test]
     fn test_full_parse5() {
         let expected_output = quote! {
-            MyDriver < pk1::library::DebugLibBase, pk1::library::AdvLibSpecial < pk2::library::WriteLibBase, pk1::library::DebugLibBase > >
+            ::mu_core::library::Constructed<
+                MyDriver<pk1::library::DebugLibBase, pk1::library::AdvLibSpecial<pk2::library::WriteLibBase, pk1::library::DebugLibBase> >,
+                (pk1::library::DebugLibBase, (pk2::library::WriteLibBase, (pk1::library::AdvLibSpecial<pk2::library::WriteLibBase, pk1::library::DebugLibBase>, ())))
+            >
         };
 
         let input = quote! {
@@ -209,6 +298,180 @@ mod tests {
         };
 
         let actual = parse(input);
-        assert_eq!(actual.to_string(), expected_output.to_string());
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config4.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_full_parse6() {
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<
+                    pk2::library::RingBufferDebugLib<4096, pk2::library::WriteLibBase>,
+                    pk1::library::AdvLibBase<u32, pk1::Serial<u8>, { pk1::SIZE }, pk2::library::RingBufferDebugLib<4096, pk2::library::WriteLibBase> >
+                >,
+                (pk2::library::WriteLibBase, (pk2::library::RingBufferDebugLib<4096, pk2::library::WriteLibBase>, (pk1::library::AdvLibBase<u32, pk1::Serial<u8>, { pk1::SIZE }, pk2::library::RingBufferDebugLib<4096, pk2::library::WriteLibBase> >, ())))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib, AdvLib>;
+            Path = "tests/data/test_config6.toml";
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config6.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_config_keyword() {
+        // `Config` is the same as `Path`, and a path that is not in the crate is looked up in the workspace.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase>,
+                (DebugLibBase, ())
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib>;
+            Config = "mu_macro/tests/data/test_config1.toml";
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config1.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_overrides() {
+        // PrintLib is overridden for DebugLib and AdvLib, which require it, and brings in WriteLib, which is
+        // not in the config file.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibDebug<WriteLibBase> >, AdvLibBase<PrintLibDebug<WriteLibBase> > >,
+                (WriteLibBase, (PrintLibDebug<WriteLibBase>, (DebugLibBase<PrintLibDebug<WriteLibBase> >, (AdvLibBase<PrintLibDebug<WriteLibBase> >, ()))))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib, AdvLib>;
+            Path = "tests/data/test_config2.toml";
+            PrintLib=PrintLibDebug<WriteLib>;
+            WriteLib=WriteLibBase;
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config2.toml", expected_output).to_string());
+
+        // An override of a library the component uses directly.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibStd>,
+                (DebugLibStd, ())
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib>;
+            Path = "tests/data/test_config2.toml";
+            DebugLib=DebugLibStd
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config2.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_component_overrides() {
+        // The component's PrintLib in the config file replaces the platform's, for the libraries that require it.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibSerial>, AdvLibBase<PrintLibSerial> >,
+                (PrintLibSerial, (DebugLibBase<PrintLibSerial>, (AdvLibBase<PrintLibSerial>, ())))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib, AdvLib>;
+            Path = "tests/data/test_config7.toml";
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config7.toml", expected_output).to_string());
+
+        // The overrides given to the macro come before the component's.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibDebug> >,
+                (PrintLibDebug, (DebugLibBase<PrintLibDebug>, ()))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib>;
+            Path = "tests/data/test_config7.toml";
+            PrintLib=PrintLibDebug;
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config7.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_include() {
+        // The including file replaces PrintLib of the file it includes, and the crate depends on both.
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<DebugLibBase<PrintLibSerial>, AdvLibBase<PrintLibSerial> >,
+                (PrintLibSerial, (DebugLibBase<PrintLibSerial>, (AdvLibBase<PrintLibSerial>, ())))
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib, AdvLib>;
+            Path = "tests/data/test_config_include.toml";
+        };
+
+        let actual = parse(input);
+        let files = ["tests/data/test_config_include.toml", "tests/data/test_config2.toml"];
+        assert_eq!(actual.to_string(), tracked_files(&files, expected_output).to_string());
+    }
+
+    #[test]
+    fn test_target_arch() {
+        // The component is built for the arch of the target, which picks the instance for that arch.
+        let instance = match mu_config::Architecture::from_target(&target().unwrap()) {
+            mu_config::Architecture::Aarch64 => quote!(DebugLibPl011),
+            _ => quote!(DebugLibBase),
+        };
+        let expected_output = quote! {
+            ::mu_core::library::Constructed<
+                MyDriver<#instance>,
+                (#instance, ())
+            >
+        };
+
+        let input = quote! {
+            MyDriver<DebugLib>;
+            Path = "tests/data/test_config8.toml";
+        };
+
+        let actual = parse(input);
+        assert_eq!(actual.to_string(), tracked("tests/data/test_config8.toml", expected_output).to_string());
+    }
+
+    #[test]
+    fn test_missing_target_arch() {
+        // A component listed only for another arch is not built with that arch's libraries.
+        let input = quote! {
+            LoongDriver<DebugLib>;
+            Path = "tests/data/test_config8.toml";
+        };
+
+        let actual = parse(input).to_string();
+        assert!(actual.contains("compile_error"));
+        assert!(actual.contains(&format!(
+            "Component LoongDriver has no build target for {}",
+            mu_config::Architecture::from_target(&target().unwrap())
+        )));
     }
 }
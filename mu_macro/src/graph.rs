@@ -0,0 +1,277 @@
+//! The library dependency graph of a component.
+//!
+//! The graph is built once from the libraries the component uses, walking each library's required
+//! libraries depth first. Each library is finished after everything it requires, which gives a topological
+//! order of the graph, and reaching a library that is still on the walk's path is a dependency cycle. Each
+//! library's type is built once, from the types of the libraries it requires, no matter how many other
+//! libraries require it.
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use super::{Argument, Library};
+
+pub(crate) struct LibraryGraph<'a> {
+    /// The libraries reachable from the component, each after the libraries it requires.
+    order: Vec<&'a Library>,
+    /// The type of each library, by lowercase library name.
+    types: HashMap<String, TokenStream>,
+}
+
+impl<'a> LibraryGraph<'a> {
+    /// Builds the graph of the libraries reachable from `roots`, with the instances in `libraries`, keyed
+    /// by lowercase library name.
+    pub(crate) fn new(roots: &[Ident], libraries: &'a HashMap<String, Library>) -> syn::Result<Self> {
+        let mut graph = LibraryGraph { order: vec![], types: HashMap::new() };
+
+        // The path of the walk, with the index of the next required library to visit for each library.
+        let mut path: Vec<(&'a Library, usize)> = vec![];
+        let mut on_path: HashSet<String> = HashSet::new();
+
+        for root in roots {
+            if graph.types.contains_key(&key(root)) {
+                continue;
+            }
+            let library = lookup(root, libraries)?;
+            on_path.insert(key(&library.name));
+            path.push((library, 0));
+
+            while let Some(&(library, next)) = path.last() {
+                if let Some(required) = library.required.get(next) {
+                    path.last_mut().unwrap().1 += 1;
+                    if graph.types.contains_key(&key(required)) {
+                        continue;
+                    }
+
+                    let dependency = lookup(required, libraries)?;
+                    if !on_path.insert(key(&dependency.name)) {
+                        return Err(cycle(&path, dependency));
+                    }
+                    path.push((dependency, 0));
+                    continue;
+                }
+
+                path.pop();
+                on_path.remove(&key(&library.name));
+                graph.finish(library);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Builds the type of `library`, whose required libraries are all finished.
+    fn finish(&mut self, library: &'a Library) {
+        let instance = &library.instance;
+        let ty = if library.arguments.is_empty() {
+            quote!(#instance)
+        } else {
+            let arguments = self.arguments(&library.arguments);
+            quote!(#instance<#(#arguments),*>)
+        };
+
+        self.types.insert(key(&library.name), ty);
+        self.order.push(library);
+    }
+
+    /// The type of the instance of library `name`, which must be one of the roots of the graph or one of the
+    /// libraries they require.
+    pub(crate) fn type_of(&self, name: &Ident) -> &TokenStream {
+        &self.types[&key(name)]
+    }
+
+    /// Generic `arguments`, with each library replaced by the type of its instance.
+    pub(crate) fn arguments(&self, arguments: &[Argument]) -> Vec<TokenStream> {
+        arguments
+            .iter()
+            .map(|argument| match argument {
+                Argument::Library(name) => self.type_of(name).clone(),
+                Argument::Other(tokens) => tokens.clone(),
+            })
+            .collect()
+    }
+
+    /// The libraries of the graph, each after the libraries it requires.
+    pub(crate) fn libraries(&self) -> &[&'a Library] {
+        &self.order
+    }
+
+    /// The library instances of the graph as a list of constructors, `(First, (Second, ()))`, in
+    /// dependency order.
+    pub(crate) fn constructors(&self) -> TokenStream {
+        self.libraries().iter().rev().fold(quote!(()), |rest, library| {
+            let ty = self.type_of(&library.name);
+            quote!((#ty, #rest))
+        })
+    }
+}
+
+fn key(name: &Ident) -> String {
+    name.to_string().to_lowercase()
+}
+
+fn lookup<'a>(name: &Ident, libraries: &'a HashMap<String, Library>) -> syn::Result<&'a Library> {
+    libraries
+        .get(&key(name))
+        .ok_or_else(|| syn::Error::new(name.span(), format!("Library {} not found", name)))
+}
+
+/// Reports the cycle closed by `dependency`, which is already on `path`, as `A -> B -> C -> A`, at the
+/// first library of the cycle.
+fn cycle(path: &[(&Library, usize)], dependency: &Library) -> syn::Error {
+    let start = path.iter().position(|(library, _)| key(&library.name) == key(&dependency.name)).unwrap();
+    let names: Vec<String> = path[start..]
+        .iter()
+        .map(|(library, _)| library.name.to_string())
+        .chain(std::iter::once(dependency.name.to_string()))
+        .collect();
+
+    syn::Error::new(
+        path[start].0.name.span(),
+        format!("Circular dependency detected: {}", names.join(" -> ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn parse_libraries(definitions: &[String]) -> HashMap<String, Library> {
+        definitions
+            .iter()
+            .map(|definition| syn::parse_str::<Library>(definition).unwrap())
+            .map(|library| (key(&library.name), library))
+            .collect()
+    }
+
+    /// Checks that every library is in the order exactly once, after each library it requires.
+    fn assert_topological(graph: &LibraryGraph) {
+        let mut finished = HashSet::new();
+        for library in graph.libraries() {
+            for required in &library.required {
+                assert!(finished.contains(&key(required)), "{} before {}", library.name, required);
+            }
+            assert!(finished.insert(key(&library.name)), "{} twice", library.name);
+        }
+    }
+
+    #[test]
+    fn test_diamond() {
+        let libraries = parse_libraries(&[
+            "Top=TopBase<Left, Right>".to_string(),
+            "Left=LeftBase<Bottom>".to_string(),
+            "Right=RightBase<Bottom>".to_string(),
+            "Bottom=BottomBase".to_string(),
+        ]);
+
+        let graph = LibraryGraph::new(&[parse_quote!(Top)], &libraries).unwrap();
+        assert_eq!(
+            graph.type_of(&parse_quote!(Top)).to_string(),
+            quote!(TopBase<LeftBase<BottomBase>, RightBase<BottomBase> >).to_string()
+        );
+        assert_eq!(
+            graph.constructors().to_string(),
+            quote!((BottomBase, (LeftBase<BottomBase>, (RightBase<BottomBase>, (TopBase<LeftBase<BottomBase>, RightBase<BottomBase> >, ()))))).to_string()
+        );
+    }
+
+    #[test]
+    fn test_unused_libraries() {
+        let libraries = parse_libraries(&[
+            "DebugLib=DebugLibBase".to_string(),
+            "UnusedLib=UnusedLibBase<MissingLib>".to_string(),
+        ]);
+
+        let graph = LibraryGraph::new(&[parse_quote!(DebugLib)], &libraries).unwrap();
+        assert_eq!(graph.libraries().len(), 1);
+    }
+
+    #[test]
+    fn test_hundreds_of_libraries() {
+        // A chain of 500 libraries, each requiring the next, and the shared base library.
+        let mut definitions: Vec<String> = (0..500)
+            .map(|i| format!("Lib{}=Lib{}Impl<Lib{}, Base>", i, i, i + 1))
+            .collect();
+        definitions.push("Lib500=Lib500Impl<Base>".to_string());
+        definitions.push("Base=BaseImpl".to_string());
+        let libraries = parse_libraries(&definitions);
+
+        let graph = LibraryGraph::new(&[parse_quote!(Lib0), parse_quote!(Base)], &libraries).unwrap();
+        assert_eq!(graph.libraries().len(), 502);
+        assert_topological(&graph);
+        assert_eq!(key(&graph.libraries()[0].name), "base");
+        assert_eq!(key(&graph.libraries()[501].name), "lib0");
+    }
+
+    #[test]
+    fn test_wide_graph() {
+        // 300 libraries that all require the same 10 base libraries, which each require the base library
+        // before them.
+        let mut definitions: Vec<String> = (0..300)
+            .map(|i| {
+                let required: Vec<String> = (0..10).map(|j| format!("Base{}", j)).collect();
+                format!("Lib{}=Lib{}Impl<{}>", i, i, required.join(", "))
+            })
+            .collect();
+        definitions.push("Base0=Base0Impl".to_string());
+        definitions.extend((1..10).map(|i| format!("Base{}=Base{}Impl<Base{}>", i, i, i - 1)));
+        let libraries = parse_libraries(&definitions);
+
+        let roots: Vec<Ident> = (0..300).rev().map(|i| Ident::new(&format!("Lib{}", i), proc_macro2::Span::call_site())).collect();
+        let graph = LibraryGraph::new(&roots, &libraries).unwrap();
+        assert_eq!(graph.libraries().len(), 310);
+        assert_topological(&graph);
+    }
+
+    #[test]
+    fn test_deep_diamonds() {
+        // 12 diamonds stacked on each other. Each library's type nests the types below it twice per level,
+        // but each library is still built and constructed once.
+        let mut definitions = vec![];
+        for i in 0..12 {
+            definitions.push(format!("Top{}=Top{}Impl<Left{}, Right{}>", i, i, i, i));
+            definitions.push(format!("Left{}=Left{}Impl<Top{}>", i, i, i + 1));
+            definitions.push(format!("Right{}=Right{}Impl<Top{}>", i, i, i + 1));
+        }
+        definitions.push("Top12=Top12Impl".to_string());
+        let libraries = parse_libraries(&definitions);
+
+        let graph = LibraryGraph::new(&[parse_quote!(Top0)], &libraries).unwrap();
+        assert_eq!(graph.libraries().len(), 37);
+        assert_topological(&graph);
+
+        let top = graph.type_of(&parse_quote!(Top0)).to_string();
+        assert_eq!(top.matches("Top12Impl").count(), 1 << 12);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let libraries = parse_libraries(&[
+            "DebugLib=DebugLibBase<CycleA>".to_string(),
+            "CycleA=CycleABase<CycleB>".to_string(),
+            "CycleB=CycleBBase<CycleC>".to_string(),
+            "CycleC=CycleCBase<CycleA>".to_string(),
+        ]);
+
+        let error = LibraryGraph::new(&[parse_quote!(DebugLib)], &libraries).err().unwrap();
+        assert_eq!(error.to_string(), "Circular dependency detected: CycleA -> CycleB -> CycleC -> CycleA");
+
+        let libraries = parse_libraries(&["SelfLib=SelfLibBase<SelfLib>".to_string()]);
+        let error = LibraryGraph::new(&[parse_quote!(SelfLib)], &libraries).err().unwrap();
+        assert_eq!(error.to_string(), "Circular dependency detected: SelfLib -> SelfLib");
+    }
+
+    #[test]
+    fn test_unknown_library() {
+        let libraries = parse_libraries(&["DebugLib=DebugLibBase<PrintLib>".to_string()]);
+
+        let error = LibraryGraph::new(&[parse_quote!(DebugLib)], &libraries).err().unwrap();
+        assert_eq!(error.to_string(), "Library PrintLib not found");
+
+        let error = LibraryGraph::new(&[parse_quote!(WriteLib)], &libraries).err().unwrap();
+        assert_eq!(error.to_string(), "Library WriteLib not found");
+    }
+}
@@ -1,11 +1,15 @@
 extern crate proc_macro;
 
+mod config;
+mod entry;
 mod from_macro;
 mod from_path;
-
-use std::collections::HashMap;
+mod graph;
+mod pcd;
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use graph::LibraryGraph;
 use quote::{quote, ToTokens};
 use syn::{punctuated::Punctuated, token::Comma, Ident, Token};
 
@@ -14,14 +18,45 @@ pub fn component(tokens: TokenStream) -> TokenStream {
     from_macro::parse(tokens.into()).into()
 }
 
+/// Generates the type of a component, with the library instances of a config file.
+///
+/// The config file is given with `Config = "...";` (or `Path = "...";`) or `Env = "VARIABLE";`. A relative
+/// path is looked up in the crate's directory, and then in the workspace root, and the crate is rebuilt when
+/// the file changes. Library instances given after the config file, `Name=Instance;`, take priority over it,
+/// for the component and every library it requires.
 #[proc_macro]
 pub fn component_from_path(tokens: TokenStream) -> TokenStream {
   from_path::parse(tokens.into()).into()
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Declares the PCDs a crate reads, with their type and default value, and sets them from the `[[pcds]]`
+/// section of a config file.
+///
+/// The config file is given as for `component_from_path!`, and the PCDs are looked up
+/// for the `Arch` and `Module` given (`common` by default). PCDs that are not in the config file keep their
+/// default, and when `Env` names a variable that is not set, all of them do.
+#[proc_macro]
+pub fn pcds(tokens: TokenStream) -> TokenStream {
+  pcd::parse(tokens.into()).into()
+}
+
+/// Generates the entry point of a binary from a component type alias.
+///
+/// On the uefi target this emits the `efi_main` symbol and the panic handler, and everywhere else a
+/// `fn main` that runs the component against the `mu_host` system table. The arguments are the module
+/// type (`DxeDriver` by default) and the panic policy (`panic = Exit` by default):
+/// `#[entry(UefiDriver, panic = Deadloop)]`.
+#[proc_macro_attribute]
+pub fn entry(attr: TokenStream, item: TokenStream) -> TokenStream {
+  entry::parse(attr.into(), item.into()).into()
+}
+
+#[derive(Debug, Clone)]
 struct Component {
     name: Ident,
+    /// The generic arguments of the component, as written.
+    arguments: Vec<Argument>,
+    /// The libraries among `arguments`.
     library_list: Vec<Ident>,
 }
 
@@ -29,16 +64,13 @@ impl syn::parse::Parse for Component {
   fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
       let name: syn::Ident = input.parse()?;
 
-      input.parse::<Token![<]>()?;
-
-      let library_list: Punctuated<Ident, Comma> = input.call(Punctuated::parse_separated_nonempty)?;
-      let library_list: Vec<Ident> = library_list.into_iter().collect();
-      input.parse::<Token![>]>()?;
+      let arguments = Argument::parse_list(input)?;
       input.parse::<Token![;]>()?;
       
       Ok(Component {
           name,
-          library_list,
+          library_list: Argument::libraries(&arguments),
+          arguments,
       })
   }
 }
@@ -47,36 +79,71 @@ impl syn::parse::Parse for Component {
 struct Library {
   name: Ident,
   instance: syn::PatPath,
+  /// The generic arguments of the instance, as written.
+  arguments: Vec<Argument>,
+  /// The libraries among `arguments`, which the instance requires.
   required: Vec<Ident>,
-  resolved: Vec<Library>,
 }
 
-impl PartialEq for Library {
-  fn eq(&self, other: &Self) -> bool {
-    self.name == other.name
-      && self.required == other.required
-      && self.resolved == other.resolved
+/// Rust's primitive types, which are generic arguments but never library names.
+const PRIMITIVE_TYPES: &[&str] = &[
+  "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+  "f32", "f64",
+];
+
+/// A generic argument of a component or library instance.
+///
+/// A single identifier, such as `PrintLib`, is a library, and is replaced with the type of the library's
+/// instance. Anything else, such as `1024`, `{ SIZE }`, `u32` or `crate::Serial`, is used as written, so
+/// a concrete type that is not a primitive must be written as a path.
+#[derive(Debug, Clone)]
+enum Argument {
+  Library(Ident),
+  Other(TokenStream2),
+}
+
+impl Argument {
+  /// Parses a non-empty list of generic arguments, `<A, B, ...>`.
+  fn parse_list(input: syn::parse::ParseStream) -> syn::Result<Vec<Argument>> {
+    input.parse::<Token![<]>()?;
+    let arguments: Punctuated<syn::GenericArgument, Comma> = input.call(Punctuated::parse_separated_nonempty)?;
+    input.parse::<Token![>]>()?;
+    Ok(arguments.into_iter().map(Argument::from).collect())
+  }
+
+  /// The libraries among `arguments`.
+  fn libraries(arguments: &[Argument]) -> Vec<Ident> {
+    arguments
+      .iter()
+      .filter_map(|argument| match argument {
+        Argument::Library(name) => Some(name.clone()),
+        Argument::Other(_) => None,
+      })
+      .collect()
   }
 }
 
-impl Library {
-  /// Attempts to resolve the required libraries for this Library
-  fn resolve(&mut self, impl_map: &HashMap<String, Library>) -> syn::Result<()> {
-    for required in &self.required {
-      if let Some(lib) = impl_map.get(&required.to_string().to_lowercase()) {
-        if lib.is_resolved() {
-          self.resolved.push(lib.clone());
+impl From<syn::GenericArgument> for Argument {
+  fn from(argument: syn::GenericArgument) -> Self {
+    if let syn::GenericArgument::Type(syn::Type::Path(path)) = &argument {
+      if let Some(name) = path.path.get_ident().filter(|_| path.qself.is_none()) {
+        if !PRIMITIVE_TYPES.contains(&name.to_string().as_str()) {
+          return Argument::Library(name.clone());
         }
-      } else {
-        return Err(syn::Error::new(required.span(), format!("Library {} not found", required.to_string())));
       }
     }
-    Ok(())
+    Argument::Other(argument.to_token_stream())
   }
+}
 
-  fn is_resolved(&self) -> bool {
-    self.resolved.len() == self.required.len()
-  }
+/// Wraps `component`, with the library instances of `graph`, in `mu_core::library::Constructed`, along with
+/// every library of the graph in dependency order, so that each library is constructed once before the
+/// component is initialized.
+fn constructed(component: &Component, graph: &LibraryGraph) -> TokenStream2 {
+  let name = &component.name;
+  let arguments = graph.arguments(&component.arguments);
+  let constructors = graph.constructors();
+  quote!(::mu_core::library::Constructed<#name<#(#arguments),*>, #constructors>)
 }
 
 impl syn::parse::Parse for Library {
@@ -85,53 +152,34 @@ impl syn::parse::Parse for Library {
     input.parse::<Token![=]>()?;
     let instance = input.parse::<syn::PatPath>()?;
     
-    // The Library itself has no required libraries
+    // The Library itself has no generic arguments
     if input.is_empty() || input.peek(Token![;]) {
       return Ok(Library {
         name,
         instance,
+        arguments: vec![],
         required: vec![],
-        resolved: vec![],
       });
     }
 
-    // Parse all libraries, which should be a comma separated list between < and >
-    input.parse::<Token![<]>()?;
-    let required: Punctuated<Ident, Comma>
-      = input.call(Punctuated::parse_separated_nonempty)?;
-    let required: Vec<Ident> = required.into_iter().collect();
-    input.parse::<Token![>]>()?;
+    // Parse all arguments, which should be a comma separated list between < and >
+    let arguments = Argument::parse_list(input)?;
 
     Ok(Library {
       name,
       instance,
-      required,
-      resolved: vec![],
+      required: Argument::libraries(&arguments),
+      arguments,
     })
   }
 }
 
-impl quote::ToTokens for Library {
-
-  fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-    let instance = &self.instance;
-    let required = &self.resolved;
-
-    let t_tokens = if required.is_empty() {
-      quote! {#instance}
-    } else {
-      quote! {#instance<#(#required),*>}
-    };
-    tokens.extend(t_tokens);
-  }
-}
-
 impl std::fmt::Debug for Library {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let instance = self.instance.to_token_stream().to_string();
     write!(
-      f, "Library {{ name: {}, instance: {}, required: {:?}, resolved: {:?} }}",
-      self.name, instance, self.required, self.resolved
+      f, "Library {{ name: {}, instance: {}, required: {:?} }}",
+      self.name, instance, self.required
     )
   }
 }
@@ -140,20 +188,31 @@ impl std::fmt::Debug for Library {
 mod tests {
   use super::*;
   use quote::quote;
-  use proc_macro2;
-  use quote::ToTokens;
+  use std::collections::HashMap;
   use syn::parse_quote;
 
+  /// Parses `input`, resolves it with the instances in `dependencies`, and returns its type.
+  fn resolve(input: TokenStream2, dependencies: &[TokenStream2]) -> String {
+    let library = syn::parse2::<Library>(input).unwrap();
+    let name = library.name.clone();
+
+    let mut libraries = HashMap::new();
+    for library in std::iter::once(library).chain(dependencies.iter().map(|d| syn::parse2::<Library>(d.clone()).unwrap())) {
+      libraries.insert(library.name.to_string().to_lowercase(), library);
+    }
+
+    let graph = LibraryGraph::new(&[name.clone()], &libraries).unwrap();
+    graph.type_of(&name).to_string()
+  }
+
   #[test]
   fn test_library_parse1() {
     let input = quote! {
       DebugLib=DebugLibBase
     };
     let expected = quote!(DebugLibBase);
-  
-    let parsed = syn::parse2::<Library>(input).unwrap();
-    let parsed: proc_macro2::TokenStream = parsed.to_token_stream();
-    assert_eq!(parsed.to_string(), expected.to_string());
+
+    assert_eq!(resolve(input, &[]), expected.to_string());
   }
 
   #[test]
@@ -162,17 +221,8 @@ mod tests {
       DebugLib=DebugLibBase<PrintLib>
     };
     let expected = quote!(DebugLibBase<PrintLibBase>);
-    let mut parsed = syn::parse2::<Library>(input).unwrap();
-    // Manually resolve for testing purposes
-    parsed.resolved = vec![Library {
-      name: parse_quote!(PrintLib),
-      instance: parse_quote!(PrintLibBase),
-      required: vec![],
-      resolved: vec![]
-    }];
-
-    let parsed: proc_macro2::TokenStream = parsed.to_token_stream();
-    assert_eq!(parsed.to_string(), expected.to_string());
+
+    assert_eq!(resolve(input, &[quote!(PrintLib=PrintLibBase)]), expected.to_string());
   }
 
   #[test]
@@ -181,25 +231,9 @@ mod tests {
       DebugLib=DebugLibBase<PrintLib, WriteLib>
     };
     let expected = quote!(DebugLibBase<PrintLibBase, WriteLibBase>);
-    let mut parsed = syn::parse2::<Library>(input).unwrap();
-    // Manually resolve for testing purposes
-    parsed.resolved = vec![
-      Library {
-        name: parse_quote!(PrintLib),
-        instance: parse_quote!(PrintLibBase),
-        required: vec![],
-        resolved: vec![]
-      },
-      Library {
-        name: parse_quote!(WriteLib),
-        instance: parse_quote!(WriteLibBase),
-        required: vec![],
-        resolved: vec![]
-      }
-    ];
 
-    let parsed: proc_macro2::TokenStream = parsed.to_token_stream();
-    assert_eq!(parsed.to_string(), expected.to_string());
+    let dependencies = [quote!(PrintLib=PrintLibBase), quote!(WriteLib=WriteLibBase)];
+    assert_eq!(resolve(input, &dependencies), expected.to_string());
   }
 
   #[test]
@@ -208,28 +242,9 @@ mod tests {
       DebugLib=DebugLibBase<PrintLib, WriteLib>
     };
     let expected = quote!(DebugLibBase<PrintLibBase<WriteLibBase>, WriteLibBase>);
-    let mut parsed = syn::parse2::<Library>(input).unwrap();
-    parsed.resolved = vec![
-      Library {
-        name: parse_quote!(PrintLib),
-        instance: parse_quote!(PrintLibBase),
-        required: vec![parse_quote!(WriteLib)],
-        resolved: vec![Library {
-          name: parse_quote!(WriteLib),
-          instance: parse_quote!(WriteLibBase),
-          required: vec![],
-          resolved: vec![]
-        }]
-      },
-      Library {
-        name: parse_quote!(WriteLib),
-        instance: parse_quote!(WriteLibBase),
-        required: vec![],
-        resolved: vec![]
-      }
-    ];
-    let parsed: proc_macro2::TokenStream = parsed.to_token_stream();
-    assert_eq!(parsed.to_string(), expected.to_string());
+
+    let dependencies = [quote!(PrintLib=PrintLibBase<WriteLib>), quote!(WriteLib=WriteLibBase)];
+    assert_eq!(resolve(input, &dependencies), expected.to_string());
   }
 
   #[test]
@@ -240,27 +255,34 @@ mod tests {
     let expected = quote!(
       pkg1::library::DebugLibBase<pkg1::library::PrintLibBase<pkg1::library::WriteLibBase>, pkg1::library::WriteLibBase>
     );
-    let mut parsed = syn::parse2::<Library>(input).unwrap();
-    parsed.resolved = vec![
-      Library {
-        name: parse_quote!(PrintLib),
-        instance: parse_quote!(pkg1::library::PrintLibBase),
-        required: vec![parse_quote!(WriteLib)],
-        resolved: vec![Library {
-          name: parse_quote!(WriteLib),
-          instance: parse_quote!(pkg1::library::WriteLibBase),
-          required: vec![],
-          resolved: vec![]
-        }]
-      },
-      Library {
-        name: parse_quote!(WriteLib),
-        instance: parse_quote!(pkg1::library::WriteLibBase),
-        required: vec![],
-        resolved: vec![]
-      }
+
+    let dependencies = [
+      quote!(PrintLib=pkg1::library::PrintLibBase<WriteLib>),
+      quote!(WriteLib=pkg1::library::WriteLibBase),
     ];
-    let parsed: proc_macro2::TokenStream = parsed.to_token_stream();
-    assert_eq!(parsed.to_string(), expected.to_string());
+    assert_eq!(resolve(input, &dependencies), expected.to_string());
+  }
+
+  #[test]
+  fn test_library_debug() {
+    let library: Library = parse_quote!(DebugLib=pkg1::library::DebugLibBase<PrintLib>);
+    assert_eq!(
+      format!("{:?}", library),
+      "Library { name: DebugLib, instance: pkg1 :: library :: DebugLibBase, required: [Ident(PrintLib)] }"
+    );
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_library_arguments() {
+    let library: Library = parse_quote!(DebugLib=RingBufferDebugLib<1024, u32, { SIZE }, crate::Serial, Vec<u8>, &'static str, PrintLib>);
+    assert_eq!(library.required, vec![parse_quote!(PrintLib)] as Vec<Ident>);
+    assert_eq!(library.arguments.len(), 7);
+
+    let expected = quote!(RingBufferDebugLib<1024, u32, { SIZE }, crate::Serial, Vec<u8>, &'static str, PrintLibBase>);
+    let input = quote!(DebugLib=RingBufferDebugLib<1024, u32, { SIZE }, crate::Serial, Vec<u8>, &'static str, PrintLib>);
+    assert_eq!(resolve(input, &[quote!(PrintLib=PrintLibBase)]), expected.to_string());
+
+    let expected = quote!(RingBufferDebugLib<4096>);
+    assert_eq!(resolve(quote!(DebugLib=RingBufferDebugLib<4096>), &[]), expected.to_string());
+  }
+}
@@ -0,0 +1,439 @@
+use std::path::PathBuf;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Attribute, Expr, Ident, Token, Type, Visibility};
+
+use mu_config::{Architecture, Config, Module, PcdKind};
+
+use super::config::Source;
+
+mod kw {
+    syn::custom_keyword!(arch);
+    syn::custom_keyword!(Arch);
+    syn::custom_keyword!(module);
+    syn::custom_keyword!(Module);
+    syn::custom_keyword!(component);
+    syn::custom_keyword!(Component);
+}
+
+pub fn parse(tokens: TokenStream) -> TokenStream {
+    let parsed = match syn::parse2::<PcdsDescribed>(tokens) {
+        Ok(pcds) => pcds,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    match parsed.expand() {
+        Ok(tokens) => tokens,
+        Err(e) => e.to_compile_error(),
+    }
+}
+
+/// A PCD declaration: `pub PcdName: Type = default;`, or `pub const PcdName: Type = default;` for a PCD
+/// that must be FixedAtBuild, as it is used in const position.
+struct PcdDeclaration {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    fixed: bool,
+    name: Ident,
+    ty: Type,
+    default: Expr,
+}
+
+struct PcdsDescribed {
+    /// Where the config file was found, and the file itself, which the crate is rebuilt on.
+    source: Option<Source>,
+    files: Vec<PathBuf>,
+    config: Option<Config>,
+    /// The arch named by the invocation. Without one, each arch the config sets a PCD for gets its own
+    /// item, and the compiler picks the one for the target with `#[cfg(target_arch = ...)]`.
+    arch: Option<Architecture>,
+    module: Module,
+    /// The component whose crate declares the PCDs, whose own PCD overrides come first, and whose arch and
+    /// module the rest are looked up for.
+    component: Option<syn::LitStr>,
+    pcds: Vec<PcdDeclaration>,
+}
+
+impl PcdsDescribed {
+    fn expand(&self) -> syn::Result<TokenStream> {
+        let mut tokens = match &self.source {
+            Some(source) => source.track_items(&self.files),
+            None => quote!(),
+        };
+
+        for pcd in &self.pcds {
+            if let Some(arch) = &self.arch {
+                let (kind, value) = self.resolve(pcd, arch)?;
+                tokens.extend(item(pcd, kind, value, quote!()));
+                continue;
+            }
+
+            // Only the arches the PCD is set differently for need their own item.
+            let (kind, value) = self.resolve(pcd, &Architecture::Common)?;
+            let mut gated = vec![];
+            for arch in self.arches() {
+                let (arch_kind, arch_value) = self.resolve(pcd, &arch)?;
+                if arch_kind != kind || arch_value.to_string() != value.to_string() {
+                    let target_arch = arch.target_arch().unwrap_or_default().to_string();
+                    tokens.extend(item(pcd, arch_kind, arch_value, quote!(#[cfg(target_arch = #target_arch)])));
+                    gated.push(target_arch);
+                }
+            }
+
+            let cfg = match gated.is_empty() {
+                true => quote!(),
+                false => quote!(#[cfg(not(any(#(target_arch = #gated),*)))]),
+            };
+            tokens.extend(item(pcd, kind, value, cfg));
+        }
+
+        Ok(tokens)
+    }
+
+    /// The kind and value of `pcd` for `arch`, from the config file or else its default.
+    fn resolve(&self, pcd: &PcdDeclaration, arch: &Architecture) -> syn::Result<(PcdKind, TokenStream)> {
+        let PcdDeclaration { fixed, name, ty, default, .. } = pcd;
+
+        let configured = self.config.as_ref().and_then(|config| {
+            let component = self.component.as_ref()
+                .and_then(|component| config.components.get_for_arch(&component.value(), arch));
+            match component {
+                Some(component) => config.pcds.get_for_component(&name.to_string(), &component),
+                None => config.pcds.get(&name.to_string(), arch, &self.module),
+            }
+        });
+        let (kind, value) = match configured {
+            Some(configured) => (configured.kind, pcd_value(&configured.value, ty, name)?),
+            None => (PcdKind::FixedAtBuild, default.to_token_stream()),
+        };
+        if *fixed && kind != PcdKind::FixedAtBuild {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("PCD {} is declared const, so it must be FixedAtBuild, but the config file sets it as {:?}", name, kind),
+            ));
+        }
+        Ok((kind, value))
+    }
+
+    /// Every arch Rust builds UEFI images for, and any other arch the config file sets PCDs for.
+    fn arches(&self) -> Vec<Architecture> {
+        let mut arches = vec![
+            Architecture::Ia32,
+            Architecture::X64,
+            Architecture::Arm,
+            Architecture::Aarch64,
+            Architecture::RiscV64,
+            Architecture::LoongArch64,
+        ];
+        if let Some(config) = &self.config {
+            let mut custom: Vec<Architecture> = config.pcds.values.keys()
+                .map(|key| key.arch.clone())
+                .filter(|arch| matches!(arch, Architecture::Custom(_)))
+                .collect();
+            custom.sort();
+            custom.dedup();
+            arches.extend(custom);
+        }
+        arches
+    }
+}
+
+/// The constant or static of `pcd`, under the attributes in `cfg`.
+fn item(pcd: &PcdDeclaration, kind: PcdKind, value: TokenStream, cfg: TokenStream) -> TokenStream {
+    let PcdDeclaration { attrs, vis, name, ty, .. } = pcd;
+    match kind {
+        PcdKind::FixedAtBuild => quote! {
+            #(#attrs)*
+            #cfg
+            #[allow(non_upper_case_globals)]
+            #vis const #name: ::mu_core::pcd::FixedAtBuild<#ty> = ::mu_core::pcd::FixedAtBuild::new(#value);
+        },
+        PcdKind::PatchableInModule => quote! {
+            #(#attrs)*
+            #cfg
+            #[allow(non_upper_case_globals)]
+            #vis static #name: ::mu_core::pcd::PatchableInModule<#ty> = ::mu_core::pcd::PatchableInModule::new(#value);
+        },
+    }
+}
+
+/// Converts a PCD value from the config file into an expression of the PCD's type. A string is a string
+/// literal for a `&str` PCD, and a Rust expression, such as `log::LevelFilter::Info`, for any other type.
+fn pcd_value(value: &toml::Value, ty: &Type, name: &Ident) -> syn::Result<TokenStream> {
+    let is_str = matches!(ty, Type::Reference(reference) if matches!(&*reference.elem, Type::Path(path) if path.path.is_ident("str")));
+
+    let expr = match value {
+        toml::Value::String(s) if is_str => return Ok(quote!(#s)),
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => value.to_string(),
+        _ => {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("PCD {} must be set to an integer, float, boolean or string", name),
+            ))
+        }
+    };
+
+    syn::parse_str::<Expr>(&expr)
+        .map(|expr| expr.to_token_stream())
+        .map_err(|e| syn::Error::new(name.span(), format!("Invalid value {:?} for PCD {}: {}", expr, name, e)))
+}
+
+impl syn::parse::Parse for PcdDeclaration {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse::<Visibility>()?;
+        let fixed = input.parse::<Option<Token![const]>>()?.is_some();
+        let name = input.parse::<Ident>()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse::<Type>()?;
+        input.parse::<Token![=]>()?;
+        let default = input.parse::<Expr>()?;
+        input.parse::<Token![;]>()?;
+
+        Ok(PcdDeclaration { attrs, vis, fixed, name, ty, default })
+    }
+}
+
+impl syn::parse::Parse for PcdsDescribed {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut source = None;
+        let mut arch = None;
+        let mut module = Module::Common;
+        let mut component = None;
+
+        loop {
+            if Source::peek(input) {
+                source = Some(input.parse::<Source>()?);
+            }
+            else if input.peek(kw::arch) || input.peek(kw::Arch) {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                let value = input.parse::<syn::LitStr>()?;
+                arch = Some((&toml::Value::String(value.value())).try_into()
+                    .map_err(|e: String| syn::Error::new(value.span(), e))?);
+            }
+            else if input.peek(kw::module) || input.peek(kw::Module) {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                let value = input.parse::<syn::LitStr>()?;
+                module = (&toml::Value::String(value.value())).try_into()
+                    .map_err(|e: String| syn::Error::new(value.span(), e))?;
+            }
+            else if input.peek(kw::component) || input.peek(kw::Component) {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                component = Some(input.parse::<syn::LitStr>()?);
+            }
+            else {
+                break;
+            }
+            input.parse::<Token![;]>()?;
+        }
+
+        // Without the `Env` variable, every PCD keeps its default value.
+        let (config, files) = match source.as_ref().and_then(|source| Some((source, source.path()?))) {
+            Some((source, path)) => {
+                let (config, files) = source.read(&path)?;
+                if let Some(component) = component.as_ref().filter(|c| config.components.get(&c.value()).is_none()) {
+                    return Err(syn::Error::new(
+                        component.span(),
+                        format!("Component {} not found in {}", component.value(), path),
+                    ));
+                }
+                (Some(config), files)
+            }
+            None => (None, vec![]),
+        };
+
+        let mut pcds = vec![];
+        while !input.is_empty() {
+            pcds.push(input.parse::<PcdDeclaration>()?);
+        }
+
+        Ok(PcdsDescribed { source, files, config, arch, module, component, pcds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Includes the config file at `path`, as `pcds!` does.
+    fn tracked_file(path: &str) -> TokenStream {
+        let resolved = crate::config::resolve(path).to_string_lossy().into_owned();
+        quote!(const _: &[u8] = include_bytes!(#resolved);)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let expected = quote! {
+            /// The serial port
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(0x3F8);
+        };
+
+        let input = quote! {
+            /// The serial port
+            pub PcdSerialPortBase: u16 = 0x3F8;
+        };
+
+        assert_eq!(parse(input).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_config() {
+        let mut expected = tracked_file("tests/data/test_pcd_config.toml");
+        expected.extend(quote! {
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(1026);
+            #[allow(non_upper_case_globals)]
+            pub const PcdDebugLogLevel: ::mu_core::pcd::FixedAtBuild<log::LevelFilter> = ::mu_core::pcd::FixedAtBuild::new(log::LevelFilter::Info);
+            #[allow(non_upper_case_globals)]
+            const PcdFirmwareVendor: ::mu_core::pcd::FixedAtBuild<&'static str> = ::mu_core::pcd::FixedAtBuild::new("Project Mu");
+            #[allow(non_upper_case_globals)]
+            pub(crate) const PcdRingBufferSize: ::mu_core::pcd::FixedAtBuild<usize> = ::mu_core::pcd::FixedAtBuild::new(1024);
+        });
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+            pub PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+            PcdFirmwareVendor: &'static str = "";
+            pub(crate) PcdRingBufferSize: usize = 1024;
+        };
+
+        assert_eq!(parse(input).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_scoped_config() {
+        let mut expected = tracked_file("tests/data/test_pcd_config.toml");
+        expected.extend(quote! {
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(1026);
+            #[allow(non_upper_case_globals)]
+            pub static PcdDebugLogLevel: ::mu_core::pcd::PatchableInModule<log::LevelFilter> = ::mu_core::pcd::PatchableInModule::new(log::LevelFilter::Trace);
+        });
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Arch = "X64";
+            Module = "DXE_DRIVER";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+            pub PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+        };
+
+        assert_eq!(parse(input).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_arch_cfg() {
+        // Without an arch, the compiler picks the value set for the target's arch.
+        let mut expected = tracked_file("tests/data/test_pcd_config.toml");
+        expected.extend(quote! {
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(1026);
+            #[cfg(target_arch = "x86_64")]
+            #[allow(non_upper_case_globals)]
+            pub static PcdDebugLogLevel: ::mu_core::pcd::PatchableInModule<log::LevelFilter> = ::mu_core::pcd::PatchableInModule::new(log::LevelFilter::Trace);
+            #[cfg(not(any(target_arch = "x86_64")))]
+            #[allow(non_upper_case_globals)]
+            pub const PcdDebugLogLevel: ::mu_core::pcd::FixedAtBuild<log::LevelFilter> = ::mu_core::pcd::FixedAtBuild::new(log::LevelFilter::Info);
+        });
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Module = "DXE_DRIVER";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+            pub PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+        };
+
+        assert_eq!(parse(input).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_component() {
+        // The component's own overrides come first, then the PCDs for its arch and module.
+        let mut expected = tracked_file("tests/data/test_pcd_config.toml");
+        expected.extend(quote! {
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(1016);
+            #[cfg(target_arch = "x86_64")]
+            #[allow(non_upper_case_globals)]
+            pub static PcdDebugLogLevel: ::mu_core::pcd::PatchableInModule<log::LevelFilter> = ::mu_core::pcd::PatchableInModule::new(log::LevelFilter::Trace);
+            #[cfg(not(any(target_arch = "x86_64")))]
+            #[allow(non_upper_case_globals)]
+            pub const PcdDebugLogLevel: ::mu_core::pcd::FixedAtBuild<log::LevelFilter> = ::mu_core::pcd::FixedAtBuild::new(log::LevelFilter::Info);
+        });
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Component = "MyDriver";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+            pub PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+        };
+        assert_eq!(parse(input).to_string(), expected.to_string());
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Component = "OtherDriver";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+        };
+        assert!(parse(input).to_string().contains("Component OtherDriver not found in"));
+    }
+
+    #[test]
+    fn test_const_must_be_fixed() {
+        let mut expected = tracked_file("tests/data/test_pcd_config.toml");
+        expected.extend(quote! {
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(1026);
+        });
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Arch = "X64";
+            Module = "DXE_DRIVER";
+            pub const PcdSerialPortBase: u16 = 0x3F8;
+        };
+        assert_eq!(parse(input).to_string(), expected.to_string());
+
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            Arch = "X64";
+            Module = "DXE_DRIVER";
+            pub const PcdDebugLogLevel: log::LevelFilter = log::LevelFilter::Debug;
+        };
+        let error = parse(input).to_string();
+        assert!(error.contains("compile_error"));
+        assert!(error.contains("must be FixedAtBuild"));
+    }
+
+    #[test]
+    fn test_env() {
+        let expected = quote! {
+            const _: Option<&str> = option_env!("MU_MACRO_TEST_PCD_CONFIG_UNSET");
+            #[allow(non_upper_case_globals)]
+            pub const PcdSerialPortBase: ::mu_core::pcd::FixedAtBuild<u16> = ::mu_core::pcd::FixedAtBuild::new(0x3F8);
+        };
+
+        let input = quote! {
+            Env = "MU_MACRO_TEST_PCD_CONFIG_UNSET";
+            pub PcdSerialPortBase: u16 = 0x3F8;
+        };
+
+        assert_eq!(parse(input).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let input = quote! {
+            Path = "tests/data/test_pcd_config.toml";
+            PcdInvalid: u32 = 0;
+        };
+
+        assert!(parse(input).to_string().contains("compile_error"));
+    }
+}
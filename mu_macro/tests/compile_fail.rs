@@ -0,0 +1,15 @@
+#[test]
+fn compile_fail() {
+    // The ui tests are built outside of this crate, so they find the config file through the environment.
+    std::env::set_var(
+        "MU_MACRO_INVALID_CONFIG",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/test_invalid_config.toml"),
+    );
+    std::env::set_var(
+        "MU_MACRO_MALFORMED_CONFIG",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/test_malformed_config.toml"),
+    );
+
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
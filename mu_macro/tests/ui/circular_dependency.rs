@@ -0,0 +1,10 @@
+use uefi_macro::component;
+
+type Driver = component!(MyDriver<DebugLib>;
+    DebugLib = DebugLibBase<CycleA>;
+    CycleA = CycleABase<CycleB>;
+    CycleB = CycleBBase<CycleC>;
+    CycleC = CycleCBase<CycleA>;
+);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use uefi_macro::component;
+
+type Driver = component!(MyDriver<DebugLib, PrintLib>; DebugLib = DebugLibBase);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use uefi_macro::component_from_path;
+
+type Driver = component_from_path!(MyDriver<CycleA>; Env = "MU_MACRO_INVALID_CONFIG";);
+
+fn main() {}
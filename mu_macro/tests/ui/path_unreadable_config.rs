@@ -0,0 +1,5 @@
+use uefi_macro::component_from_path;
+
+type Driver = component_from_path!(MyDriver<DebugLib>; Path = "tests/data/does_not_exist.toml";);
+
+fn main() {}
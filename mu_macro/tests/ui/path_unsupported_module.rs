@@ -0,0 +1,7 @@
+use uefi_macro::component_from_path;
+
+type Driver = component_from_path!(MyDriver<StdLib>; Env = "MU_MACRO_INVALID_CONFIG";);
+
+type Writer = component_from_path!(MyDriver<WriterLib>; Env = "MU_MACRO_INVALID_CONFIG";);
+
+fn main() {}